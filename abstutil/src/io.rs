@@ -153,6 +153,218 @@ pub fn read_binary<T: DeserializeOwned>(path: String, timer: &mut Timer) -> T {
     }
 }
 
+// Maps, scenarios, and savestates are big, long-lived files whose format changes often. Types
+// implementing this are written with a small header in front of the usual bincode payload, so that
+// old files can be upgraded, or at least rejected with a clear explanation instead of a confusing
+// deserialization error.
+pub trait Versioned: Serialize + DeserializeOwned {
+    // Identifies the kind of file, like "map" or "scenario"
+    const FORMAT: &'static str;
+    // Increase this whenever the serialized form changes.
+    const VERSION: u32;
+
+    // Returns a way to upgrade a payload from from_version to from_version + 1, if there is one.
+    // Version 0 means a file written before versioning existed. Migrations are chained as needed.
+    fn migration(_from_version: u32) -> Option<Migration> {
+        None
+    }
+}
+
+// Transforms a bincoded payload from one version into the next. Usually this deserializes a copy
+// of the old struct definitions and converts them.
+pub type Migration = fn(Vec<u8>) -> Result<Vec<u8>, String>;
+
+const VERSIONED_MAGIC: &[u8; 8] = b"abstbin\0";
+
+// Reading a file written by a newer build fails with an io::Error wrapping this, so callers can tell
+// the user to update instead of re-importing.
+#[derive(Debug)]
+pub struct NewerVersionError {
+    pub path: String,
+    pub format: &'static str,
+    pub version: u32,
+    pub supported_version: u32,
+}
+
+impl std::fmt::Display for NewerVersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: this {} has format version {}, but this build only understands up to version {}. \
+             You need a newer build.",
+            self.path, self.format, self.version, self.supported_version
+        )
+    }
+}
+
+impl std::error::Error for NewerVersionError {}
+
+#[derive(Serialize, Deserialize)]
+struct VersionedHeader {
+    format: String,
+    version: u32,
+    // Of the payload, which follows the header
+    checksum: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_versioned_binary<T: Versioned>(path: String, obj: &T) {
+    if let Err(err) = maybe_write_versioned_binary(&path, obj) {
+        panic!("Can't write_versioned_binary({}): {}", path, err);
+    }
+    println!("Wrote {}", path);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_versioned_binary<T: Versioned>(path: String, obj: &T) {
+    // TODO
+}
+
+fn maybe_write_versioned_binary<T: Versioned>(path: &str, obj: &T) -> Result<(), Error> {
+    if !path.ends_with(".bin") {
+        panic!("write_versioned_binary needs {} to end with .bin", path);
+    }
+
+    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())
+        .expect("Creating parent dir failed");
+
+    let payload = bincode::serialize(obj).map_err(|err| Error::new(ErrorKind::Other, err))?;
+    let header = VersionedHeader {
+        format: T::FORMAT.to_string(),
+        version: T::VERSION,
+        checksum: checksum(&payload),
+    };
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(VERSIONED_MAGIC)?;
+    bincode::serialize_into(&mut file, &header).map_err(|err| Error::new(ErrorKind::Other, err))?;
+    file.write_all(&payload)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn maybe_read_versioned_binary<T: Versioned>(
+    path: String,
+    timer: &mut Timer,
+) -> Result<T, Error> {
+    if !path.ends_with(".bin") {
+        panic!("read_versioned_binary needs {} to end with .bin", path);
+    }
+
+    // Don't read_to_end; the Timer stops tracking the file once every byte has been read.
+    let len = std::fs::metadata(&path)?.len() as usize;
+    timer.read_file(&path)?;
+    let mut bytes = vec![0; len];
+    timer.read_exact(&mut bytes)?;
+    from_versioned_bytes(&path, bytes)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn maybe_read_versioned_binary<T: Versioned>(
+    path: String,
+    _timer: &mut Timer,
+) -> Result<T, Error> {
+    if let Some(raw) = SYSTEM_DATA.get_file(path.trim_start_matches("../data/system/")) {
+        from_versioned_bytes(&path, raw.contents().to_vec())
+    } else {
+        Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "Can't maybe_read_versioned_binary {}, it doesn't exist",
+                path
+            ),
+        ))
+    }
+}
+
+pub fn read_versioned_binary<T: Versioned>(path: String, timer: &mut Timer) -> T {
+    match maybe_read_versioned_binary(path.clone(), timer) {
+        Ok(obj) => obj,
+        Err(err) => panic!("Couldn't read_versioned_binary({}): {}", path, err),
+    }
+}
+
+fn from_versioned_bytes<T: Versioned>(path: &str, bytes: Vec<u8>) -> Result<T, Error> {
+    let err = |msg: String| Error::new(ErrorKind::Other, format!("{}: {}", path, msg));
+
+    let (version, mut payload) = if bytes.starts_with(VERSIONED_MAGIC) {
+        let mut rest = &bytes[VERSIONED_MAGIC.len()..];
+        let header: VersionedHeader = bincode::deserialize_from(&mut rest)
+            .map_err(|e| err(format!("corrupt header: {}", e)))?;
+        if header.format != T::FORMAT {
+            return Err(err(format!(
+                "this is a {} file, not a {}",
+                header.format,
+                T::FORMAT
+            )));
+        }
+        if header.version > T::VERSION {
+            return Err(Error::new(
+                ErrorKind::Other,
+                NewerVersionError {
+                    path: path.to_string(),
+                    format: T::FORMAT,
+                    version: header.version,
+                    supported_version: T::VERSION,
+                },
+            ));
+        }
+        if checksum(rest) != header.checksum {
+            return Err(err(format!(
+                "this {} is corrupt; the checksum doesn't match",
+                T::FORMAT
+            )));
+        }
+        (header.version, rest.to_vec())
+    } else {
+        (0, bytes)
+    };
+
+    for v in version..T::VERSION {
+        if let Some(migrate) = T::migration(v) {
+            payload = migrate(payload).map_err(|e| {
+                err(format!(
+                    "couldn't upgrade {} from version {} to {}: {}",
+                    T::FORMAT,
+                    v,
+                    v + 1,
+                    e
+                ))
+            })?;
+        } else if v == 0 {
+            // Files from before versioning might still be readable, if the format hasn't changed
+            // since. Treat them as version 1 and keep upgrading.
+            continue;
+        } else {
+            return Err(err(format!(
+                "this {} has format version {}, but version {} is needed, and there's no way to \
+                 upgrade it",
+                T::FORMAT,
+                version,
+                T::VERSION
+            )));
+        }
+    }
+    bincode::deserialize(&payload).map_err(|e| {
+        if version == 0 {
+            err(format!(
+                "this {} predates versioned files, and its format is out-of-date",
+                T::FORMAT
+            ))
+        } else {
+            err(e.to_string())
+        }
+    })
+}
+
+// 64-bit FNV-1a. This only needs to catch truncated or damaged files, not tampering.
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
 // For BTreeMaps with struct keys. See https://github.com/serde-rs/json/issues/402.
 
 pub fn serialize_btreemap<S: Serializer, K: Serialize, V: Serialize>(
@@ -437,3 +649,166 @@ pub fn basename(path: &str) -> String {
 pub fn file_exists(path: String) -> bool {
     Path::new(&path).exists()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Thing {
+        x: u32,
+        y: u32,
+        name: String,
+    }
+
+    impl Versioned for Thing {
+        const FORMAT: &'static str = "thing";
+        // 1: Just x. Also the format before versioning.
+        // 2: Added y
+        // 3: Added name
+        const VERSION: u32 = 3;
+
+        fn migration(from_version: u32) -> Option<Migration> {
+            match from_version {
+                1 => Some(add_y),
+                2 => Some(add_name),
+                _ => None,
+            }
+        }
+    }
+
+    fn add_y(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        let old: ThingV1 = bincode::deserialize(&bytes).map_err(|e| e.to_string())?;
+        bincode::serialize(&ThingV2 {
+            x: old.x,
+            y: 2 * old.x,
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn add_name(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        let old: ThingV2 = bincode::deserialize(&bytes).map_err(|e| e.to_string())?;
+        bincode::serialize(&Thing {
+            x: old.x,
+            y: old.y,
+            name: "upgraded".to_string(),
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ThingV1 {
+        x: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ThingV2 {
+        x: u32,
+        y: u32,
+    }
+
+    impl Versioned for ThingV2 {
+        const FORMAT: &'static str = "thing";
+        const VERSION: u32 = 2;
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct FutureThing {
+        x: u32,
+    }
+
+    impl Versioned for FutureThing {
+        const FORMAT: &'static str = "thing";
+        const VERSION: u32 = 4;
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct OtherThing {
+        x: u32,
+    }
+
+    impl Versioned for OtherThing {
+        const FORMAT: &'static str = "other";
+        const VERSION: u32 = 1;
+    }
+
+    fn write<T: Versioned>(name: &str, obj: &T) -> (String, Vec<u8>) {
+        let path = std::env::temp_dir()
+            .join(format!("abst_test_versioned_{}.bin", name))
+            .display()
+            .to_string();
+        maybe_write_versioned_binary(&path, obj).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        (path, bytes)
+    }
+
+    fn thing() -> Thing {
+        Thing {
+            x: 1,
+            y: 2,
+            name: "current".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_versioned_header() {
+        let (path, bytes) = write("header", &thing());
+        assert!(bytes.starts_with(VERSIONED_MAGIC));
+        let mut rest = &bytes[VERSIONED_MAGIC.len()..];
+        let header: VersionedHeader = bincode::deserialize_from(&mut rest).unwrap();
+        assert_eq!(header.format, "thing");
+        assert_eq!(header.version, 3);
+        assert_eq!(header.checksum, checksum(rest));
+        assert_eq!(
+            from_versioned_bytes::<Thing>(&path, bytes.clone()).unwrap(),
+            thing()
+        );
+
+        // The wrong kind of file
+        assert!(from_versioned_bytes::<OtherThing>(&path, bytes).is_err());
+
+        // A file from a newer build
+        let (path, bytes) = write("future", &FutureThing { x: 1 });
+        let err = from_versioned_bytes::<Thing>(&path, bytes).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<NewerVersionError>());
+    }
+
+    #[test]
+    fn test_versioned_checksum() {
+        let (path, mut bytes) = write("checksum", &thing());
+        *bytes.last_mut().unwrap() ^= 1;
+        let err = from_versioned_bytes::<Thing>(&path, bytes.clone()).unwrap_err();
+        assert!(err.to_string().contains("checksum"));
+
+        bytes.pop();
+        assert!(from_versioned_bytes::<Thing>(&path, bytes).is_err());
+    }
+
+    #[test]
+    fn test_versioned_migrations() {
+        // From version 2, one step
+        let (path, bytes) = write("v2", &ThingV2 { x: 5, y: 7 });
+        assert_eq!(
+            from_versioned_bytes::<Thing>(&path, bytes).unwrap(),
+            Thing {
+                x: 5,
+                y: 7,
+                name: "upgraded".to_string(),
+            }
+        );
+
+        // From before versioning, through every step
+        let bytes = bincode::serialize(&ThingV1 { x: 7 }).unwrap();
+        assert_eq!(
+            from_versioned_bytes::<Thing>("old.bin", bytes).unwrap(),
+            Thing {
+                x: 7,
+                y: 14,
+                name: "upgraded".to_string(),
+            }
+        );
+
+        // A failed migration
+        assert!(from_versioned_bytes::<Thing>("old.bin", Vec::new()).is_err());
+    }
+}
@@ -14,9 +14,10 @@ pub use crate::collections::{
 pub use crate::io::{
     basename, deserialize_btreemap, deserialize_multimap, deserialize_usize, file_exists,
     find_next_file, find_prev_file, list_all_objects, list_dir, load_all_objects,
-    maybe_read_binary, maybe_read_json, read_binary, read_json, serialize_btreemap,
-    serialize_multimap, serialize_usize, serialized_size_bytes, slurp_file, to_json, write_binary,
-    write_json, FileWithProgress,
+    maybe_read_binary, maybe_read_json, maybe_read_versioned_binary, read_binary, read_json,
+    read_versioned_binary, serialize_btreemap, serialize_multimap, serialize_usize,
+    serialized_size_bytes, slurp_file, to_json, write_binary, write_json, write_versioned_binary,
+    FileWithProgress, Migration, NewerVersionError, Versioned,
 };
 pub use crate::random::{fork_rng, WeightedUsizeChoice};
 pub use crate::time::{
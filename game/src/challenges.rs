@@ -281,8 +281,10 @@ pub fn prebake_all() {
 
     {
         let map = map_model::Map::new(abstutil::path_map("montlake"), &mut timer);
-        let scenario: Scenario =
-            abstutil::read_binary(abstutil::path_scenario("montlake", "weekday"), &mut timer);
+        let scenario: Scenario = abstutil::read_versioned_binary(
+            abstutil::path_scenario("montlake", "weekday"),
+            &mut timer,
+        );
         prebake(&map, scenario, None, &mut timer);
 
         for generator in TutorialState::scenarios_to_prebake(&map) {
@@ -298,7 +300,7 @@ pub fn prebake_all() {
     for name in vec!["lakeslice"] {
         let map = map_model::Map::new(abstutil::path_map(name), &mut timer);
         let scenario: Scenario =
-            abstutil::read_binary(abstutil::path_scenario(name, "weekday"), &mut timer);
+            abstutil::read_versioned_binary(abstutil::path_scenario(name, "weekday"), &mut timer);
         prebake(&map, scenario, None, &mut timer);
     }
 }
@@ -104,6 +104,7 @@ pub struct ColorScheme {
     pub bus_body: Color,
     pub bus_label: Color,
     pub train_body: Color,
+    pub truck_body: Color,
     pub ped_head: Color,
     pub ped_foot: Color,
     pub ped_preparing_bike_body: Color,
@@ -119,6 +120,7 @@ pub struct ColorScheme {
 
     // Misc
     pub parking_trip: Color,
+    pub ride_hail_trip: Color,
     pub before_changes: Color,
     pub after_changes: Color,
 }
@@ -211,6 +213,7 @@ impl ColorScheme {
             bus_body: Color::rgb(50, 133, 117),
             bus_label: Color::rgb(249, 206, 24),
             train_body: Color::hex("#42B6E9"),
+            truck_body: hex("#8C6239"),
             ped_head: Color::rgb(139, 69, 19),
             ped_foot: Color::BLACK,
             ped_preparing_bike_body: Color::rgb(255, 0, 144),
@@ -226,6 +229,7 @@ impl ColorScheme {
 
             // Misc
             parking_trip: hex("#4E30A6"),
+            ride_hail_trip: hex("#E1BA13"),
             before_changes: Color::BLUE,
             after_changes: Color::RED,
         }
@@ -111,7 +111,7 @@ fn load_scenario(wiz: &mut Wizard, ctx: &mut EventCtx, app: &mut App) -> Option<
     let s = wiz.wrap(ctx).choose_string("Load which scenario?", || {
         abstutil::list_all_objects(abstutil::path_all_scenarios(&map_name))
     })?;
-    let scenario = abstutil::read_binary(
+    let scenario = abstutil::read_versioned_binary(
         abstutil::path_scenario(&map_name, &s),
         &mut Timer::throwaway(),
     );
@@ -0,0 +1,189 @@
+use crate::app::App;
+use crate::game::{DrawBaselayer, State, Transition};
+use ezgui::{
+    hotkey, Btn, Color, Composite, EventCtx, GeomBatch, GfxCtx, Key, Line, Outcome, Text, Widget,
+};
+use geom::{Distance, Duration, PolyLine, Polygon, Pt2D};
+use map_model::{GreenWave, Map, WaveDirection};
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 400.0;
+const BAR_THICKNESS: f64 = 6.0;
+// Show this many cycles
+const NUM_CYCLES: f64 = 2.0;
+
+// A time-space diagram of a corridor. Time goes across and distance along the corridor goes down.
+// Each signal is drawn as a bar, green while traffic along the corridor can go. Diagonal lines
+// follow vehicles leaving the first signal (or the last, going backwards) at the start and end of
+// green, travelling at the progression speed; when the wave works, they pass through every green.
+pub struct TimeSpaceDiagram {
+    composite: Composite,
+}
+
+impl TimeSpaceDiagram {
+    pub fn new(ctx: &mut EventCtx, app: &App, wave: &GreenWave) -> Box<dyn State> {
+        let map = &app.primary.map;
+        let cycle = wave.cycle_length(map);
+        let dists = wave.distances(map);
+        let total_dist = *dists.last().unwrap();
+        let time_to_x = |t: Duration| WIDTH * (t / (NUM_CYCLES * cycle));
+        let dist_to_y = |d: Distance| HEIGHT * (d / total_dist);
+
+        let mut batch = GeomBatch::new();
+        batch.push(Color::grey(0.2), Polygon::rectangle(WIDTH, HEIGHT));
+        let mut directions = Vec::new();
+        if wave.direction != WaveDirection::Backwards {
+            directions.push(true);
+        }
+        if wave.direction != WaveDirection::Forwards {
+            directions.push(false);
+        }
+
+        for (idx, dist) in dists.iter().enumerate() {
+            let y = dist_to_y(*dist) - BAR_THICKNESS / 2.0;
+            batch.push(
+                Color::RED,
+                Polygon::rectangle(WIDTH, BAR_THICKNESS).translate(0.0, y),
+            );
+            for forwards in &directions {
+                if let Some((start, end)) = green_window(wave, idx, *forwards, map) {
+                    for (t1, t2) in repeat(start, end, cycle) {
+                        batch.push(
+                            Color::GREEN,
+                            Polygon::rectangle(time_to_x(t2) - time_to_x(t1), BAR_THICKNESS)
+                                .translate(time_to_x(t1), y),
+                        );
+                    }
+                }
+            }
+        }
+
+        // Trace vehicles through the corridor
+        let travel_time = total_dist / wave.speed;
+        for forwards in directions {
+            let origin = if forwards { 0 } else { dists.len() - 1 };
+            if let Some((start, end)) = green_window(wave, origin, forwards, map) {
+                let mut t = start % cycle - cycle;
+                while t < NUM_CYCLES * cycle {
+                    for depart in &[t, t + (end - start)] {
+                        let (y1, y2) = if forwards {
+                            (0.0, HEIGHT)
+                        } else {
+                            (HEIGHT, 0.0)
+                        };
+                        if let Some(line) = clip(
+                            Pt2D::new(time_to_x(*depart), y1),
+                            Pt2D::new(time_to_x(*depart + travel_time), y2),
+                        ) {
+                            batch.push(Color::WHITE, line);
+                        }
+                    }
+                    t += cycle;
+                }
+            }
+        }
+
+        let mut legend = Text::new();
+        for (idx, i) in wave.intersections.iter().enumerate() {
+            legend.add(Line(format!("{}: {} along the corridor", i, dists[idx])));
+        }
+
+        Box::new(TimeSpaceDiagram {
+            composite: Composite::new(Widget::col(vec![
+                Widget::row(vec![
+                    Line("Time-space diagram").small_heading().draw(ctx),
+                    Btn::plaintext("X")
+                        .build(ctx, "close", hotkey(Key::Escape))
+                        .align_right(),
+                ]),
+                format!(
+                    "{} cycles of {}, progression speed {}",
+                    NUM_CYCLES, cycle, wave.speed
+                )
+                .draw_text(ctx),
+                Widget::draw_batch(ctx, batch),
+                legend.draw(ctx),
+            ]))
+            .build(ctx),
+        })
+    }
+}
+
+impl State for TimeSpaceDiagram {
+    fn event(&mut self, ctx: &mut EventCtx, _: &mut App) -> Transition {
+        match self.composite.event(ctx) {
+            Some(Outcome::Clicked(x)) => match x.as_ref() {
+                "close" => {
+                    return Transition::Pop;
+                }
+                _ => unreachable!(),
+            },
+            None => {}
+        }
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        State::grey_out_map(g, app);
+        self.composite.draw(g);
+    }
+}
+
+// When the corridor phase starts and ends, relative to midnight, during the first cycle
+fn green_window(
+    wave: &GreenWave,
+    idx: usize,
+    forwards: bool,
+    map: &Map,
+) -> Option<(Duration, Duration)> {
+    let phase = wave.corridor_phase(idx, forwards, map)?;
+    let signal = map.get_traffic_signal(wave.intersections[idx]);
+    let start = signal.offset
+        + signal.phases[0..phase]
+            .iter()
+            .map(|p| p.phase_type.simple_duration())
+            .sum::<Duration>();
+    Some((
+        start,
+        start + signal.phases[phase].phase_type.simple_duration(),
+    ))
+}
+
+// Every repetition of a window that overlaps the diagram, trimmed to fit
+fn repeat(start: Duration, end: Duration, cycle: Duration) -> Vec<(Duration, Duration)> {
+    let max = NUM_CYCLES * cycle;
+    let mut results = Vec::new();
+    let mut t = start % cycle - cycle;
+    while t < max {
+        let t1 = t.max(Duration::ZERO);
+        let t2 = (t + (end - start)).min(max);
+        if t1 < t2 {
+            results.push((t1, t2));
+        }
+        t += cycle;
+    }
+    results
+}
+
+// Trim a line to the diagram
+fn clip(pt1: Pt2D, pt2: Pt2D) -> Option<Polygon> {
+    let dx = pt2.x() - pt1.x();
+    let (mut p1, mut p2) = (0.0, 1.0_f64);
+    if dx != 0.0 {
+        let a = (0.0 - pt1.x()) / dx;
+        let b = (WIDTH - pt1.x()) / dx;
+        p1 = p1.max(a.min(b));
+        p2 = p2.min(a.max(b));
+    }
+    if p1 >= p2 {
+        return None;
+    }
+    let at = |p: f64| Pt2D::new(pt1.x() + p * dx, pt1.y() + p * (pt2.y() - pt1.y()));
+    PolyLine::new(vec![at(p1), at(p2)])
+        .ok()
+        .map(|pl| pl.make_polygons(Distance::meters(1.0)))
+}
@@ -1,5 +1,6 @@
 mod bulk;
 mod cluster_traffic_signals;
+mod green_wave;
 mod lanes;
 mod select;
 mod stop_signs;
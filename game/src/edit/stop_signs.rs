@@ -159,13 +159,13 @@ impl State for StopSignEditor {
                     edits.commands.push(EditCmd::ChangeIntersection {
                         i: self.id,
                         old: app.primary.map.get_i_edit(self.id),
-                        new: EditIntersection::TrafficSignal(
-                            ControlTrafficSignal::new(
+                        new: EditIntersection::traffic_signal(
+                            &ControlTrafficSignal::new(
                                 &app.primary.map,
                                 self.id,
                                 &mut Timer::throwaway(),
-                            )
-                            .export(&app.primary.map),
+                            ),
+                            &app.primary.map,
                         ),
                     });
                     apply_map_edits(ctx, app, edits);
@@ -1,5 +1,6 @@
 use crate::app::{App, ShowEverything};
 use crate::common::CommonState;
+use crate::edit::green_wave::TimeSpaceDiagram;
 use crate::edit::{apply_map_edits, check_sidewalk_connectivity, StopSignEditor};
 use crate::game::{msg, DrawBaselayer, State, Transition, WizardState};
 use crate::render::{
@@ -10,12 +11,12 @@ use abstutil::Timer;
 use ezgui::{
     hotkey, lctrl, Btn, Choice, Color, Composite, Drawable, EventCtx, GeomBatch, GfxCtx,
     HorizontalAlignment, Key, Line, Outcome, RewriteColor, Text, TextExt, UpdateType,
-    VerticalAlignment, Widget,
+    VerticalAlignment, Widget, WrappedWizard,
 };
-use geom::{ArrowCap, Distance, Duration, Polygon};
+use geom::{ArrowCap, Distance, Duration, Polygon, Speed};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, Phase,
-    PhaseType, TurnGroupID, TurnPriority,
+    ControlStopSign, ControlTrafficSignal, EditCmd, EditIntersection, GreenWave, IntersectionID,
+    Map, Phase, PhaseType, TransitPriority, TurnGroupID, TurnPriority, WaveDirection,
 };
 use std::collections::BTreeSet;
 
@@ -490,18 +491,33 @@ fn edit_entire_signal(
         .get_turns_in_intersection(i)
         .any(|t| t.between_sidewalks());
     let current_offset = app.primary.map.get_traffic_signal(i).offset;
+    let has_transit_priority = app
+        .primary
+        .map
+        .get_traffic_signal(i)
+        .transit_priority
+        .is_some();
 
     WizardState::new(Box::new(move |wiz, ctx, app| {
         let use_template = "use template";
         let all_walk = "add an all-walk phase at the end";
+        let lpi = "give pedestrians a head start (leading pedestrian interval)";
         let stop_sign = "convert to stop signs";
         let close = "close intersection for construction";
         let offset = "edit signal offset";
+        let transit_priority = if has_transit_priority {
+            "disable transit signal priority"
+        } else {
+            "enable transit signal priority"
+        };
+        let green_wave = "coordinate a green wave along a corridor";
+        let time_space = "show a time-space diagram for a corridor";
         let reset = "reset to default";
 
         let mut choices = vec![use_template];
         if has_sidewalks {
             choices.push(all_walk);
+            choices.push(lpi);
         }
         // TODO Conflating stop signs and construction here
         if mode.can_edit_stop_signs() {
@@ -509,6 +525,9 @@ fn edit_entire_signal(
             choices.push(close);
         }
         choices.push(offset);
+        choices.push(transit_priority);
+        choices.push(green_wave);
+        choices.push(time_space);
         choices.push(reset);
 
         let mut wizard = wiz.wrap(ctx);
@@ -547,6 +566,27 @@ fn edit_entire_signal(
                     }
                 })))
             }
+            x if x == lpi => {
+                let seconds = wizard.input_usize_prefilled(
+                    "How long should conflicting turns wait after each phase starts (seconds)? 0 \
+                     removes the head start.",
+                    "5".to_string(),
+                )?;
+                Some(Transition::PopWithData(Box::new(move |state, ctx, app| {
+                    let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                    let orig_signal = app.primary.map.get_traffic_signal(editor.i);
+                    let mut new_signal = orig_signal.clone();
+                    if new_signal
+                        .set_leading_pedestrian_intervals(Duration::seconds(seconds as f64))
+                    {
+                        editor.command_stack.push(orig_signal.clone());
+                        editor.redo_stack.clear();
+                        editor.top_panel = make_top_panel(ctx, app, true, false);
+                        app.primary.map.incremental_edit_traffic_signal(new_signal);
+                        editor.change_phase(editor.current_phase, ctx, app);
+                    }
+                })))
+            }
             x if x == stop_sign => {
                 // First restore the original signal
                 if let Some(ref orig) = orig_signal {
@@ -608,6 +648,60 @@ fn edit_entire_signal(
                     editor.change_phase(editor.current_phase, ctx, app);
                 })))
             }
+            x if x == transit_priority => {
+                Some(Transition::PopWithData(Box::new(move |state, ctx, app| {
+                    let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                    let mut signal = app.primary.map.get_traffic_signal(editor.i).clone();
+                    editor.command_stack.push(signal.clone());
+                    editor.redo_stack.clear();
+                    editor.top_panel = make_top_panel(ctx, app, true, false);
+                    signal.transit_priority = if has_transit_priority {
+                        None
+                    } else {
+                        Some(TransitPriority::new())
+                    };
+                    app.primary.map.incremental_edit_traffic_signal(signal);
+                    editor.change_phase(editor.current_phase, ctx, app);
+                })))
+            }
+            x if x == green_wave => {
+                let wave = match choose_corridor(&mut wizard, &app.primary.map, i)? {
+                    Ok(wave) => wave,
+                    Err(err) => {
+                        return Some(Transition::Replace(msg("Error", vec![err])));
+                    }
+                };
+                let signals = match wave.coordinate(&app.primary.map) {
+                    Ok(signals) => signals,
+                    Err(err) => {
+                        return Some(Transition::Replace(msg("Error", vec![err])));
+                    }
+                };
+
+                // First restore the original signal, so the edit records the change from it
+                if let Some(ref orig) = orig_signal {
+                    app.primary
+                        .map
+                        .incremental_edit_traffic_signal(orig.clone());
+                }
+
+                let mut edits = app.primary.map.get_edits().clone();
+                for signal in signals {
+                    edits.commands.push(EditCmd::ChangeIntersection {
+                        i: signal.id,
+                        old: app.primary.map.get_i_edit(signal.id),
+                        new: EditIntersection::traffic_signal(&signal, &app.primary.map),
+                    });
+                }
+                apply_map_edits(ctx, app, edits);
+                Some(Transition::PopThenReplace(TimeSpaceDiagram::new(
+                    ctx, app, &wave,
+                )))
+            }
+            x if x == time_space => match choose_corridor(&mut wizard, &app.primary.map, i)? {
+                Ok(wave) => Some(Transition::Replace(TimeSpaceDiagram::new(ctx, app, &wave))),
+                Err(err) => Some(Transition::Replace(msg("Error", vec![err]))),
+            },
             x if x == reset => {
                 Some(Transition::PopWithData(Box::new(move |state, ctx, app| {
                     let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
@@ -634,6 +728,54 @@ fn edit_entire_signal(
     }))
 }
 
+// Asks for the signals along a corridor, in order, and how fast traffic should progress
+fn choose_corridor(
+    wizard: &mut WrappedWizard,
+    map: &Map,
+    i: IntersectionID,
+) -> Option<Result<GreenWave, String>> {
+    let ids = wizard.input_string_prefilled(
+        "Which traffic signals make up the corridor, in order? (comma-separated IDs)",
+        format!("{},", i.0),
+    )?;
+    let mph = wizard.input_usize_prefilled(
+        "What speed should traffic progress at (mph)?",
+        "25".to_string(),
+    )?;
+    let direction = match wizard
+        .choose_string("Which way should the wave go?", || {
+            vec![
+                "forwards, in the order given",
+                "backwards",
+                "both ways, as a compromise",
+            ]
+        })?
+        .as_str()
+    {
+        "forwards, in the order given" => WaveDirection::Forwards,
+        "backwards" => WaveDirection::Backwards,
+        _ => WaveDirection::Both,
+    };
+
+    let mut intersections = Vec::new();
+    for id in ids.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        match id.parse::<usize>() {
+            Ok(idx) if idx < map.all_intersections().len() => {
+                intersections.push(IntersectionID(idx));
+            }
+            _ => {
+                return Some(Err(format!("{} isn't an intersection", id)));
+            }
+        }
+    }
+    Some(GreenWave::new(
+        map,
+        intersections,
+        Speed::miles_per_hour(mph as f64),
+        direction,
+    ))
+}
+
 fn change_duration(app: &App, i: IntersectionID, idx: usize) -> Box<dyn State> {
     let current_type = app.primary.map.get_traffic_signal(i).phases[idx]
         .phase_type
@@ -642,12 +784,13 @@ fn change_duration(app: &App, i: IntersectionID, idx: usize) -> Box<dyn State> {
     // TODO This UI shouldn't be a wizard
     WizardState::new(Box::new(move |wiz, ctx, _| {
         let mut wizard = wiz.wrap(ctx);
+        let current_duration = match current_type {
+            PhaseType::Actuated { min_green, .. } => min_green,
+            ref x => x.simple_duration(),
+        };
         let new_duration = Duration::seconds(wizard.input_something(
-            "How long should this phase be (seconds)?",
-            Some(format!(
-                "{}",
-                current_type.simple_duration().inner_seconds() as usize
-            )),
+            "How long should this phase be (seconds)? For actuated phases, this is the minimum.",
+            Some(format!("{}", current_duration.inner_seconds() as usize)),
             Box::new(|line| {
                 line.parse::<usize>()
                     .ok()
@@ -659,13 +802,51 @@ fn change_duration(app: &App, i: IntersectionID, idx: usize) -> Box<dyn State> {
             "Adaptive: some multiple of {}, based on current demand",
             new_duration
         );
+        let actuated = format!(
+            "Actuated: at least {}, extended while vehicles are detected",
+            new_duration
+        );
         let choice = wizard.choose_string("How should this phase be timed?", move || {
-            vec![fixed.clone(), adaptive.clone()]
+            vec![fixed.clone(), adaptive.clone(), actuated.clone()]
         })?;
         let new_type = if choice.starts_with("Fixed") {
             PhaseType::Fixed(new_duration)
-        } else {
+        } else if choice.starts_with("Adaptive") {
             PhaseType::Adaptive(new_duration)
+        } else {
+            let (default_max, default_passage) = match current_type {
+                PhaseType::Actuated {
+                    max_green, passage, ..
+                } => (max_green.max(new_duration), passage),
+                _ => (2.0 * new_duration, Duration::seconds(3.0)),
+            };
+            let max_green = Duration::seconds(wizard.input_something(
+                "What's the longest this phase can be extended to (seconds)?",
+                Some(format!("{}", default_max.inner_seconds() as usize)),
+                Box::new(move |line| {
+                    line.parse::<usize>().ok().and_then(|n| {
+                        if Duration::seconds(n as f64) >= new_duration {
+                            Some(n)
+                        } else {
+                            None
+                        }
+                    })
+                }),
+            )? as f64);
+            let passage = Duration::seconds(wizard.input_something(
+                "How long should each detected vehicle extend the phase (seconds)?",
+                Some(format!("{}", default_passage.inner_seconds() as usize)),
+                Box::new(|line| {
+                    line.parse::<usize>()
+                        .ok()
+                        .and_then(|n| if n != 0 { Some(n) } else { None })
+                }),
+            )? as f64);
+            PhaseType::Actuated {
+                min_green: new_duration,
+                max_green,
+                passage,
+            }
         };
         Some(Transition::PopWithData(Box::new(move |state, ctx, app| {
             let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
@@ -710,7 +891,7 @@ fn check_for_missing_groups(
                 edits.commands.push(EditCmd::ChangeIntersection {
                     i: new_signal.id,
                     old: app.primary.map.get_i_edit(new_signal.id),
-                    new: EditIntersection::TrafficSignal(new_signal.export(&app.primary.map)),
+                    new: EditIntersection::traffic_signal(&new_signal, &app.primary.map),
                 });
                 apply_map_edits(ctx, app, edits);
             }
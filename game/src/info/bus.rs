@@ -77,6 +77,7 @@ pub fn bus_status(ctx: &mut EventCtx, app: &App, details: &mut Details, id: CarI
 
     let route = app.primary.sim.bus_route_id(id).unwrap();
     rows.push(passenger_delay(ctx, app, details, route));
+    rows.push(crowding(ctx, app, route));
 
     rows
 }
@@ -154,6 +155,48 @@ fn delays_over_time(ctx: &mut EventCtx, app: &App, id: BusRouteID) -> Widget {
     ])
 }
 
+fn crowding(ctx: &mut EventCtx, app: &App, id: BusRouteID) -> Widget {
+    let route = app.primary.map.get_br(id);
+    let now = app.primary.sim.time();
+    let analytics = app.primary.sim.get_analytics();
+    let mut loads = analytics.bus_load_profile(now, id);
+    let pass_ups = analytics.bus_pass_ups_per_stop(now, id);
+
+    let mut txt = Text::new();
+    for idx in 0..route.stops.len() {
+        let stop = route.stops[idx];
+        if idx != route.stops.len() - 1 {
+            if let Some(hgram) = loads.remove(&stop) {
+                txt.add(Line(format!(
+                    "Stop {}->{}: {} riding on average, at most {}",
+                    idx + 1,
+                    idx + 2,
+                    hgram.select(Statistic::Mean).unwrap(),
+                    hgram.select(Statistic::Max).unwrap()
+                )));
+            } else {
+                txt.add(Line(format!("Stop {}->{}: no buses yet", idx + 1, idx + 2)).secondary());
+            }
+        }
+        let cnt = pass_ups.get(stop);
+        if cnt > 0 {
+            txt.add(
+                Line(format!(
+                    "  {} people passed up by full buses at stop {}",
+                    cnt,
+                    idx + 1
+                ))
+                .fg(Color::RED),
+            );
+        }
+    }
+
+    Widget::col(vec![
+        Line("Load profile").small_heading().draw(ctx),
+        txt.draw(ctx),
+    ])
+}
+
 fn passenger_delay(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusRouteID) -> Widget {
     let route = app.primary.map.get_br(id);
     let mut master_col = vec![Line("Passengers waiting").small_heading().draw(ctx)];
@@ -114,9 +114,68 @@ pub fn delay(
 
     rows.push(delay_plot(ctx, app, id, opts));
 
+    if app
+        .primary
+        .map
+        .get_traffic_signal(id)
+        .transit_priority
+        .is_some()
+    {
+        rows.push(transit_priority_summary(ctx, app, id));
+    }
+
     rows
 }
 
+fn transit_priority_summary(ctx: &EventCtx, app: &App, i: IntersectionID) -> Widget {
+    let analytics = app.primary.sim.get_analytics();
+    let mut held = (0, Duration::ZERO);
+    let mut cut = (0, Duration::ZERO);
+    for (_, _, dt, extended) in analytics
+        .transit_signal_priority
+        .get(&i)
+        .unwrap_or(&Vec::new())
+    {
+        let sum = if *extended { &mut held } else { &mut cut };
+        sum.0 += 1;
+        sum.1 += *dt;
+    }
+    // Compare how long buses and everybody else wait here
+    let mut transit_delay = (0, Duration::ZERO);
+    let mut other_delay = (0, Duration::ZERO);
+    for (_, dt, mode) in analytics.intersection_delays.get(&i).unwrap_or(&Vec::new()) {
+        let sum = if *mode == TripMode::Transit {
+            &mut transit_delay
+        } else {
+            &mut other_delay
+        };
+        sum.0 += 1;
+        sum.1 += *dt;
+    }
+
+    let mut txt = Text::from(Line("Transit signal priority").small_heading());
+    txt.add(Line(format!(
+        "{} phases held open, for {} total",
+        prettyprint_usize(held.0),
+        held.1
+    )));
+    txt.add(Line(format!(
+        "{} phases ended early, by {} total",
+        prettyprint_usize(cut.0),
+        cut.1
+    )));
+    for (label, (cnt, total)) in [("buses", transit_delay), ("everyone else", other_delay)].iter() {
+        if *cnt > 0 {
+            txt.add(Line(format!(
+                "Average delay for {}: {}",
+                label,
+                *total / (*cnt as f64)
+            )));
+        }
+    }
+    txt.draw(ctx)
+}
+
 pub fn current_demand(
     ctx: &mut EventCtx,
     app: &App,
@@ -139,7 +139,7 @@ pub fn trips(
                         match trip.mode {
                             TripMode::Walk => "system/assets/meters/pedestrian.svg",
                             TripMode::Bike => "system/assets/meters/bike.svg",
-                            TripMode::Drive => "system/assets/meters/car.svg",
+                            TripMode::Drive | TripMode::RideHail => "system/assets/meters/car.svg",
                             TripMode::Transit => "system/assets/meters/bus.svg",
                         },
                         RewriteColor::ChangeAll(color),
@@ -523,6 +523,9 @@ fn header(
                     }
                     AgentID::Car(c) => match c.1 {
                         VehicleType::Car => ("driving", Some("system/assets/meters/car.svg")),
+                        VehicleType::Truck => {
+                            ("making deliveries", Some("system/assets/meters/car.svg"))
+                        }
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
                         VehicleType::Bus | VehicleType::Train => unreachable!(),
                     },
@@ -505,6 +505,12 @@ fn make_timeline(
                         "system/assets/timeline/waiting_for_bus.svg"
                     }
                     TripPhaseType::RidingBus(_, _, _) => "system/assets/timeline/riding_bus.svg",
+                    // TODO Dedicated icons for ride-hailing
+                    TripPhaseType::WaitingForRideHail => {
+                        "system/assets/timeline/waiting_for_bus.svg"
+                    }
+                    TripPhaseType::RidingRideHail(_) => "system/assets/timeline/driving.svg",
+                    TripPhaseType::Delivering(_) => "system/assets/timeline/parking.svg",
                     TripPhaseType::Aborted | TripPhaseType::Finished => unreachable!(),
                     TripPhaseType::DelayedStart => "system/assets/timeline/delayed_start.svg",
                     // TODO What icon should represent this?
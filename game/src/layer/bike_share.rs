@@ -0,0 +1,121 @@
+use crate::app::App;
+use crate::common::{ColorLegend, ColorNetwork};
+use crate::layer::{Layer, LayerOutcome};
+use abstutil::prettyprint_usize;
+use ezgui::{
+    hotkey, Btn, Composite, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Text,
+    TextExt, VerticalAlignment, Widget,
+};
+use geom::{Circle, Distance, Duration, Time};
+
+pub struct BikeShare {
+    time: Time,
+    unzoomed: Drawable,
+    zoomed: Drawable,
+    composite: Composite,
+}
+
+impl Layer for BikeShare {
+    fn name(&self) -> Option<&'static str> {
+        Some("bike share")
+    }
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        minimap: &Composite,
+    ) -> Option<LayerOutcome> {
+        if app.primary.sim.time() != self.time {
+            *self = BikeShare::new(ctx, app);
+        }
+        Layer::simple_event(ctx, minimap, &mut self.composite)
+    }
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        self.composite.draw(g);
+        if g.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            g.redraw(&self.unzoomed);
+        } else {
+            g.redraw(&self.zoomed);
+        }
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.unzoomed);
+    }
+}
+
+impl BikeShare {
+    pub fn new(ctx: &mut EventCtx, app: &App) -> BikeShare {
+        let map = &app.primary.map;
+        let now = app.primary.sim.time();
+        let docks = app.primary.sim.get_all_bike_share_docks();
+
+        let mut colorer = ColorNetwork::new(app);
+        let mut total_bikes = 0;
+        let mut empty = 0;
+        let mut full = 0;
+        for (_, pos, bikes, capacity) in &docks {
+            total_bikes += bikes;
+            if *bikes == 0 {
+                empty += 1;
+            } else if bikes >= capacity {
+                full += 1;
+            }
+            // Both empty and full docks are bad, but running out of bikes is what people notice
+            let pct = if *capacity == 0 {
+                1.0
+            } else {
+                1.0 - (*bikes as f64) / (*capacity as f64)
+            };
+            let color = app.cs.good_to_bad_red.eval(pct.max(0.0).min(1.0));
+            let pt = pos.pt(map);
+            colorer
+                .unzoomed
+                .push(color, Circle::new(pt, Distance::meters(15.0)).to_polygon());
+            colorer.zoomed.push(
+                color.alpha(0.8),
+                Circle::new(pt, Distance::meters(3.0)).to_polygon(),
+            );
+        }
+        let empty_time = app
+            .primary
+            .sim
+            .get_analytics()
+            .empty_dock_time(now)
+            .into_iter()
+            .fold(Duration::ZERO, |sum, (_, dt)| sum + dt);
+
+        let composite = Composite::new(Widget::col(vec![
+            Widget::row(vec![
+                Widget::draw_svg(ctx, "system/assets/tools/layers.svg"),
+                "Bike share".draw_text(ctx),
+                Btn::plaintext("X")
+                    .build(ctx, "close", hotkey(Key::Escape))
+                    .align_right(),
+            ]),
+            Text::from_multiline(vec![
+                Line(format!(
+                    "{} bikes docked at {} docks",
+                    prettyprint_usize(total_bikes),
+                    prettyprint_usize(docks.len())
+                )),
+                Line(format!(
+                    "{} docks are empty, {} are full",
+                    prettyprint_usize(empty),
+                    prettyprint_usize(full)
+                )),
+                Line(format!("Docks have been empty for {} in total", empty_time)),
+            ])
+            .draw(ctx),
+            ColorLegend::gradient(ctx, &app.cs.good_to_bad_red, vec!["full", "empty"]),
+        ]))
+        .aligned(HorizontalAlignment::Right, VerticalAlignment::Center)
+        .build(ctx);
+
+        BikeShare {
+            time: now,
+            unzoomed: ctx.upload(colorer.unzoomed),
+            zoomed: ctx.upload(colorer.zoomed),
+            composite,
+        }
+    }
+}
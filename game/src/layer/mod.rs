@@ -1,3 +1,4 @@
+mod bike_share;
 mod elevation;
 pub mod map;
 mod pandemic;
@@ -98,6 +99,17 @@ impl PickLayer {
 
         col.push(btn("None", Key::N));
 
+        let mut map_col = vec![
+            "Map".draw_text(ctx),
+            btn("map edits", Key::E),
+            btn("parking occupancy", Key::P),
+            btn("bike network", Key::B),
+            btn("transit network", Key::U),
+            btn("population map", Key::X),
+        ];
+        if !app.primary.sim.get_all_bike_share_docks().is_empty() {
+            map_col.push(btn("bike share", Key::H));
+        }
         col.push(
             Widget::custom_row(vec![
                 Widget::col(vec![
@@ -106,14 +118,7 @@ impl PickLayer {
                     btn("throughput", Key::T),
                     btn("traffic jams", Key::J),
                 ]),
-                Widget::col(vec![
-                    "Map".draw_text(ctx),
-                    btn("map edits", Key::E),
-                    btn("parking occupancy", Key::P),
-                    btn("bike network", Key::B),
-                    btn("transit network", Key::U),
-                    btn("population map", Key::X),
-                ]),
+                Widget::col(map_col),
             ])
             .evenly_spaced(),
         );
@@ -164,6 +169,9 @@ impl State for PickLayer {
                 "bike network" => {
                     app.layer = Some(Box::new(map::BikeNetwork::new(ctx, app)));
                 }
+                "bike share" => {
+                    app.layer = Some(Box::new(bike_share::BikeShare::new(ctx, app)));
+                }
                 "transit network" => {
                     app.layer = Some(Box::new(transit::TransitNetwork::new(
                         ctx, app, false, true, true,
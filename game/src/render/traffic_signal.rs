@@ -309,6 +309,12 @@ pub fn make_signal_diagram(
         txt.add(Line(""));
         txt.add(Line(format!("{} phases", signal.phases.len())).small_heading());
         txt.add(Line(format!("Signal offset: {}", signal.offset)));
+        if let Some(ref tsp) = signal.transit_priority {
+            txt.add(Line(format!(
+                "Transit signal priority: hold up to {}, cut up to {}",
+                tsp.max_extension, tsp.max_early_end
+            )));
+        }
         {
             let mut total = Duration::ZERO;
             for p in &signal.phases {
@@ -377,6 +383,11 @@ pub fn make_signal_diagram(
             )
         };
 
+        let head_start = if let Some(delay) = phase.start_delays.values().max() {
+            format!("Leading pedestrian interval: {}", delay).draw_text(ctx)
+        } else {
+            Widget::nothing()
+        };
         let phase_col = if edit_mode {
             Widget::col(vec![
                 Widget::row(vec![
@@ -385,6 +396,16 @@ pub fn make_signal_diagram(
                         PhaseType::Adaptive(d) => {
                             Line(format!("Phase {}: {} (adaptive)", idx + 1, d))
                         }
+                        PhaseType::Actuated {
+                            min_green,
+                            max_green,
+                            ..
+                        } => Line(format!(
+                            "Phase {}: {} to {} (actuated)",
+                            idx + 1,
+                            min_green,
+                            max_green
+                        )),
                     }
                     .small_heading()
                     .draw(ctx),
@@ -405,6 +426,7 @@ pub fn make_signal_diagram(
                         Widget::nothing()
                     },
                 ]),
+                head_start,
                 Widget::row(vec![
                     phase_btn,
                     Widget::col(vec![
@@ -434,7 +456,19 @@ pub fn make_signal_diagram(
                     PhaseType::Adaptive(d) => {
                         format!("Phase {}: {} (adaptive)", idx + 1, d).draw_text(ctx)
                     }
+                    PhaseType::Actuated {
+                        min_green,
+                        max_green,
+                        ..
+                    } => format!(
+                        "Phase {}: {} to {} (actuated)",
+                        idx + 1,
+                        min_green,
+                        max_green
+                    )
+                    .draw_text(ctx),
                 },
+                head_start,
                 phase_btn,
             ])
         }
@@ -139,6 +139,13 @@ pub fn make_change_traffic(btn: ScreenRectangle, current: String) -> Box<dyn Sta
                         list.push(Choice::new(name.clone(), name));
                     }
                 }
+                list.push(
+                    Choice::new("daily routines", "activity_model".to_string()).tooltip(
+                        "Everybody living in the map will go to work or run errands, with \
+                         morning and evening rush hours. Destinations and modes are picked from \
+                         building types and amenities, so this works for any city.",
+                    ),
+                );
                 list.push(
                     Choice::new("trips between home and work", "home_to_work".to_string()).tooltip(
                         "Randomized people will leave homes in the morning, go to work, then \
@@ -442,7 +449,7 @@ fn path_request(
         end: pos(to, mode, false, map)?,
         constraints: match mode {
             TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-            TripMode::Drive => PathConstraints::Car,
+            TripMode::Drive | TripMode::RideHail => PathConstraints::Car,
             TripMode::Bike => PathConstraints::Bike,
         },
     })
@@ -453,7 +460,9 @@ fn pos(endpt: TripEndpoint, mode: TripMode, from: bool, map: &Map) -> Option<Pos
         TripEndpoint::Bldg(b) => match mode {
             TripMode::Walk | TripMode::Transit => Some(map.get_b(b).front_path.sidewalk),
             TripMode::Bike => Some(DrivingGoal::ParkNear(b).goal_pos(PathConstraints::Bike, map)),
-            TripMode::Drive => Some(DrivingGoal::ParkNear(b).goal_pos(PathConstraints::Car, map)),
+            TripMode::Drive | TripMode::RideHail => {
+                Some(DrivingGoal::ParkNear(b).goal_pos(PathConstraints::Car, map))
+            }
         },
         TripEndpoint::Border(i, _) => match mode {
             TripMode::Walk | TripMode::Transit => if from {
@@ -462,7 +471,7 @@ fn pos(endpt: TripEndpoint, mode: TripMode, from: bool, map: &Map) -> Option<Pos
                 SidewalkSpot::end_at_border(i, None, map)
             }
             .map(|spot| spot.sidewalk_pos),
-            TripMode::Bike | TripMode::Drive => (if from {
+            TripMode::Bike | TripMode::Drive | TripMode::RideHail => (if from {
                 map.get_i(i).some_outgoing_road(map)
             } else {
                 map.get_i(i).some_incoming_road(map)
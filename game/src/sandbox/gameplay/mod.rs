@@ -115,9 +115,11 @@ impl GameplayMode {
             .generate(map, &mut rng, timer)
         } else if name == "home_to_work" {
             ScenarioGenerator::proletariat_robot(map, &mut rng, timer)
+        } else if name == "activity_model" {
+            ScenarioGenerator::activity_model(map, &mut rng, timer)
         } else {
             let path = abstutil::path_scenario(map.get_name(), &name);
-            let mut scenario = match abstutil::maybe_read_binary(path.clone(), timer) {
+            let mut scenario = match abstutil::maybe_read_versioned_binary(path.clone(), timer) {
                 Ok(s) => s,
                 Err(err) => {
                     Map::corrupt_err(path, err);
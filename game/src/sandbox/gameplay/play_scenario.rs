@@ -10,9 +10,9 @@ use ezgui::{
     hotkey, lctrl, AreaSlider, Btn, Choice, Color, Composite, EventCtx, GeomBatch, GfxCtx,
     HorizontalAlignment, Key, Line, Outcome, Spinner, Text, TextExt, VerticalAlignment, Widget,
 };
-use geom::Polygon;
+use geom::{Duration, Polygon, Time};
 use maplit::btreeset;
-use sim::{ScenarioModifier, TripMode};
+use sim::{ModeChoice, ScenarioModifier, TripMode};
 use std::collections::BTreeSet;
 
 pub struct PlayScenario {
@@ -274,7 +274,14 @@ fn new_modifier(scenario_name: String, modifiers: Vec<ScenarioModifier>) -> Box<
         let mut wizard = wiz.wrap(ctx);
         let new_mod = match wizard
             .choose_string("", || {
-                vec!["repeat days", "cancel all trips for some people"]
+                vec![
+                    "repeat days",
+                    "cancel all trips for some people",
+                    "add delivery trucks",
+                    "switch some drivers to park-and-ride",
+                    "add bike share",
+                    "pick everyone's mode by travel time",
+                ]
             })?
             .as_str()
         {
@@ -284,6 +291,29 @@ fn new_modifier(scenario_name: String, modifiers: Vec<ScenarioModifier>) -> Box<
             x if x == "cancel all trips for some people" => ScenarioModifier::CancelPeople(
                 wizard.input_percent("What percent of people should cancel trips? (0 to 100)")?,
             ),
+            x if x == "add delivery trucks" => ScenarioModifier::AddDeliveries {
+                num_tours: wizard.input_usize("How many delivery trucks?")?,
+                stops_per_tour: wizard
+                    .input_usize("How many businesses should each truck visit?")?,
+            },
+            x if x == "switch some drivers to park-and-ride" => ScenarioModifier::ParkAndRide {
+                pct_ppl: wizard.input_percent(
+                    "What percent of people driving somewhere and back should park-and-ride? (0 \
+                     to 100)",
+                )?,
+            },
+            x if x == "add bike share" => ScenarioModifier::BikeShare {
+                pct_ppl: wizard.input_percent(
+                    "What percent of people walking or taking transit should use bike share? (0 \
+                     to 100)",
+                )?,
+                dock_capacity: wizard.input_usize("How many bikes fit in each dock?")?,
+            },
+            x if x == "pick everyone's mode by travel time" => ScenarioModifier::ChooseModes {
+                model: ModeChoice::new(),
+                departure_filter: (Time::START_OF_DAY, Time::START_OF_DAY + Duration::hours(24)),
+                from_modes: TripMode::all().into_iter().collect(),
+            },
             _ => unreachable!(),
         };
         let mut mods = modifiers.clone();
@@ -1172,6 +1172,7 @@ impl TutorialState {
                             percent_driving: 1.0,
                             percent_biking: 0.0,
                             percent_use_transit: 0.0,
+                            mode_choice: None,
                         }],
                         border_spawn_over_time: Vec::new(),
                     }
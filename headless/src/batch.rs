@@ -0,0 +1,258 @@
+// Runs a baseline and a list of experiments (each some combination of map edits and scenario
+// modifiers) to completion, then writes a JSON report comparing every experiment against the
+// baseline. This is the same comparison the game's dashboards make against prebaked results, but
+// it can run unattended.
+//
+// > cd headless; cargo run --release -- --batch=experiments.json --output=report.json
+//
+// The config file looks like:
+//
+// {
+//   "scenario": "../data/system/scenarios/montlake/weekday.bin",
+//   "modifiers": [],
+//   "experiments": [
+//     { "name": "bike lanes", "edits": "../data/player/edits/montlake/bike lanes.json" },
+//     { "name": "fewer drivers", "modifiers": [{ "CancelPeople": 10 }] }
+//   ]
+// }
+//
+// Modifiers listed at the top level apply to the baseline and every experiment. Emissions are
+// estimated with the default fleet mix, unless the config has a "fleet_mix" like:
+//
+//   "fleet_mix": {
+//     "cars": [["Gasoline", 0.5], ["Electric", 0.5]],
+//     "trucks": [["Diesel", 1.0]],
+//     "buses": [["Electric", 1.0]],
+//     "trains": [["Electric", 1.0]]
+//   }
+
+use crate::LoadSim;
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Duration, Time};
+use map_model::IntersectionID;
+use serde::{Deserialize, Serialize};
+use sim::{Analytics, Emissions, FleetMix, ScenarioModifier, SimFlags, TripID, TripMode};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+struct BatchConfig {
+    scenario: String,
+    #[serde(default)]
+    modifiers: Vec<ScenarioModifier>,
+    experiments: Vec<Experiment>,
+    fleet_mix: Option<FleetMix>,
+}
+
+#[derive(Deserialize)]
+struct Experiment {
+    name: String,
+    // Path to a JSON file of map edits
+    edits: Option<String>,
+    #[serde(default)]
+    modifiers: Vec<ScenarioModifier>,
+}
+
+pub fn run(config_path: String, output: String, flags: SimFlags) {
+    let mut timer = Timer::new("run batch experiments");
+    let config: BatchConfig = abstutil::read_json(config_path, &mut timer);
+    let fleet = config.fleet_mix.unwrap_or_else(FleetMix::new);
+
+    let base_load = LoadSim {
+        scenario: config.scenario.clone(),
+        modifiers: config.modifiers.clone(),
+        edits: None,
+        rng_seed: flags.rng_seed,
+        opts: flags.opts,
+    };
+    timer.start("run baseline");
+    let baseline = run_to_completion(&base_load.recording_as("baseline"), &fleet, &mut timer);
+    timer.stop("run baseline");
+
+    let mut experiments = Vec::new();
+    for exp in config.experiments {
+        timer.start(format!("run experiment {}", exp.name));
+        let mut load = base_load.recording_as(&exp.name);
+        if let Some(path) = exp.edits {
+            load.edits = Some(abstutil::read_json(path, &mut timer));
+        }
+        load.modifiers.extend(exp.modifiers);
+        let results = run_to_completion(&load, &fleet, &mut timer);
+        experiments.push(compare(exp.name.clone(), &baseline, &results));
+        timer.stop(format!("run experiment {}", exp.name));
+    }
+
+    let report = BatchReport {
+        scenario: config.scenario,
+        baseline: summarize("baseline".to_string(), &baseline),
+        experiments,
+    };
+    for exp in &report.experiments {
+        println!(
+            "{}: {} trips faster (saving {}), {} slower (losing {}), {} newly aborted, {:.0} kg \
+             CO2 (baseline {:.0} kg)",
+            exp.summary.name,
+            prettyprint_usize(exp.trips_faster),
+            exp.time_saved,
+            prettyprint_usize(exp.trips_slower),
+            exp.time_lost,
+            prettyprint_usize(exp.newly_aborted_trips.len()),
+            exp.summary.emissions.co2_kg,
+            report.baseline.emissions.co2_kg
+        );
+    }
+    abstutil::write_json(output, &report);
+}
+
+struct RunResults {
+    analytics: Analytics,
+    end_time: Time,
+    emissions: Emissions,
+}
+
+fn run_to_completion(load: &LoadSim, fleet: &FleetMix, timer: &mut Timer) -> RunResults {
+    let (map, mut sim) = load
+        .setup(timer)
+        .unwrap_or_else(|err| panic!("Couldn't set up {}: {}", load.scenario, err));
+    sim.run_until_done(&map, |_, _| {}, None);
+    RunResults {
+        analytics: sim.get_analytics().clone(),
+        end_time: sim.time(),
+        emissions: sim.get_analytics().emissions(fleet).total,
+    }
+}
+
+#[derive(Serialize)]
+struct BatchReport {
+    scenario: String,
+    baseline: RunSummary,
+    experiments: Vec<ExperimentReport>,
+}
+
+#[derive(Serialize)]
+struct RunSummary {
+    name: String,
+    finished_trips: usize,
+    aborted_trips: usize,
+    total_trip_time: Duration,
+    end_time: Time,
+    emissions: Emissions,
+}
+
+#[derive(Serialize)]
+struct ExperimentReport {
+    summary: RunSummary,
+    trips_faster: usize,
+    trips_slower: usize,
+    trips_unchanged: usize,
+    time_saved: Duration,
+    time_lost: Duration,
+    // Only trips that finished in both the baseline and the experiment
+    trips: Vec<TripDelta>,
+    // Trips that finished in the baseline, but were aborted in the experiment
+    newly_aborted_trips: Vec<TripID>,
+    // Negative means less delay than the baseline. Only intersections with a change are listed.
+    intersection_delays: Vec<IntersectionDelay>,
+}
+
+#[derive(Serialize)]
+struct TripDelta {
+    id: TripID,
+    mode: TripMode,
+    before: Duration,
+    after: Duration,
+}
+
+#[derive(Serialize)]
+struct IntersectionDelay {
+    id: IntersectionID,
+    change: Duration,
+}
+
+fn summarize(name: String, results: &RunResults) -> RunSummary {
+    let mut finished_trips = 0;
+    let mut aborted_trips = 0;
+    let mut total_trip_time = Duration::ZERO;
+    for (_, _, maybe_mode, dt) in &results.analytics.finished_trips {
+        if maybe_mode.is_some() {
+            finished_trips += 1;
+            total_trip_time += *dt;
+        } else {
+            aborted_trips += 1;
+        }
+    }
+    RunSummary {
+        name,
+        finished_trips,
+        aborted_trips,
+        total_trip_time,
+        end_time: results.end_time,
+        emissions: results.emissions,
+    }
+}
+
+fn compare(name: String, baseline: &RunResults, results: &RunResults) -> ExperimentReport {
+    let now = baseline.end_time.max(results.end_time);
+
+    let mut trips_faster = 0;
+    let mut trips_slower = 0;
+    let mut trips_unchanged = 0;
+    let mut time_saved = Duration::ZERO;
+    let mut time_lost = Duration::ZERO;
+    for (before, after, _) in results
+        .analytics
+        .both_finished_trips(now, &baseline.analytics)
+    {
+        if after < before {
+            trips_faster += 1;
+            time_saved += before - after;
+        } else if after > before {
+            trips_slower += 1;
+            time_lost += after - before;
+        } else {
+            trips_unchanged += 1;
+        }
+    }
+
+    // both_finished_trips doesn't say which trip is which, so match them up here too.
+    let mut before_per_trip: BTreeMap<TripID, Duration> = BTreeMap::new();
+    for (_, id, maybe_mode, dt) in &baseline.analytics.finished_trips {
+        if maybe_mode.is_some() {
+            before_per_trip.insert(*id, *dt);
+        }
+    }
+    let mut trips = Vec::new();
+    let mut newly_aborted_trips = Vec::new();
+    for (_, id, maybe_mode, dt) in &results.analytics.finished_trips {
+        if let Some(before) = before_per_trip.get(id) {
+            if let Some(mode) = maybe_mode {
+                trips.push(TripDelta {
+                    id: *id,
+                    mode: *mode,
+                    before: *before,
+                    after: *dt,
+                });
+            } else {
+                newly_aborted_trips.push(*id);
+            }
+        }
+    }
+
+    let intersection_delays = results
+        .analytics
+        .compare_delay(now, &baseline.analytics)
+        .into_iter()
+        .map(|(id, change)| IntersectionDelay { id, change })
+        .collect();
+
+    ExperimentReport {
+        summary: summarize(name, results),
+        trips_faster,
+        trips_slower,
+        trips_unchanged,
+        time_saved,
+        time_lost,
+        trips,
+        newly_aborted_trips,
+        intersection_delays,
+    }
+}
@@ -0,0 +1,126 @@
+// Runs the same scenario over and over. Each run measures how long cars take to cross every lane,
+// and the next run pathfinds using those times instead of free-flow speeds. Repeating until the
+// times settle down is a simple user-equilibrium assignment: in the end, few drivers could save
+// time by switching routes.
+//
+// > cd headless; cargo run --release -- --equilibrium=10 \
+//     ../data/system/scenarios/montlake/weekday.bin
+//
+// Observations are blended into a running average with weight 1/n on the nth run (the method of
+// successive averages), so routes don't flip back and forth between runs.
+
+use crate::LoadSim;
+use abstutil::Timer;
+use geom::Duration;
+use map_model::ObservedTravelTimes;
+use serde::Serialize;
+use sim::SimFlags;
+
+// Travel times are averaged over bins of this size
+const BIN_SIZE: Duration = Duration::const_seconds(3600.0);
+// Stop once no lane's observed travel time is further than this from the average of the previous
+// runs. Blending shrinks every change by 1/n, so the average alone would look converged too early.
+const CONVERGED: Duration = Duration::const_seconds(5.0);
+
+pub fn run(max_iterations: usize, edits: Option<String>, output: String, flags: SimFlags) {
+    assert!(max_iterations > 0, "--equilibrium must be at least 1");
+
+    let mut timer = Timer::new("find user equilibrium");
+    let load = LoadSim {
+        scenario: flags.load.clone(),
+        modifiers: Vec::new(),
+        edits: edits.map(|path| abstutil::read_json(path, &mut timer)),
+        rng_seed: flags.rng_seed,
+        opts: flags.opts,
+    };
+    let (mut map, scenario) = load
+        .load_map(&mut timer)
+        .unwrap_or_else(|err| panic!("Couldn't set up {}: {}", load.scenario, err));
+
+    let mut travel_times: Option<ObservedTravelTimes> = None;
+    let mut iterations = Vec::new();
+    for iteration in 1..=max_iterations {
+        timer.start(format!("iteration {}", iteration));
+        let mut sim = load
+            .recording_as(&format!("iteration{}", iteration))
+            .make_sim(&map, scenario.clone(), load.rng_seed, &mut timer);
+        sim.run_until_done(&map, |_, _| {}, None);
+
+        let analytics = sim.get_analytics();
+        let observed = analytics.observed_travel_times(BIN_SIZE);
+        let max_change = if let Some(ref mut times) = travel_times {
+            let change = observed.max_difference(times);
+            times.blend(&observed, 1.0 / (iteration as f64));
+            Some(change)
+        } else {
+            travel_times = Some(observed);
+            None
+        };
+
+        let mut finished_trips = 0;
+        let mut total_trip_time = Duration::ZERO;
+        for (_, _, maybe_mode, dt) in &analytics.finished_trips {
+            if maybe_mode.is_some() {
+                finished_trips += 1;
+                total_trip_time += *dt;
+            }
+        }
+        let summary = IterationSummary {
+            iteration,
+            finished_trips,
+            mean_trip_time: if finished_trips == 0 {
+                None
+            } else {
+                Some(total_trip_time / (finished_trips as f64))
+            },
+            max_change,
+        };
+        println!(
+            "Iteration {}: {} trips finished, mean trip time {}, largest change in lane travel \
+             time {}",
+            iteration,
+            summary.finished_trips,
+            summary
+                .mean_trip_time
+                .map(|dt| dt.to_string())
+                .unwrap_or_else(|| "n/a".to_string()),
+            max_change
+                .map(|dt| dt.to_string())
+                .unwrap_or_else(|| "n/a".to_string())
+        );
+        iterations.push(summary);
+        timer.stop(format!("iteration {}", iteration));
+
+        if max_change.map(|dt| dt <= CONVERGED).unwrap_or(false) {
+            println!("Converged after {} iterations", iteration);
+            break;
+        }
+        if iteration != max_iterations {
+            map.set_observed_travel_times(travel_times.clone().unwrap(), &mut timer);
+        }
+    }
+
+    abstutil::write_json(
+        output,
+        &EquilibriumReport {
+            scenario: load.scenario,
+            iterations,
+        },
+    );
+}
+
+#[derive(Serialize)]
+struct EquilibriumReport {
+    scenario: String,
+    iterations: Vec<IterationSummary>,
+}
+
+#[derive(Serialize)]
+struct IterationSummary {
+    iteration: usize,
+    finished_trips: usize,
+    // None if no trips finished
+    mean_trip_time: Option<Duration>,
+    // None for the first iteration
+    max_change: Option<Duration>,
+}
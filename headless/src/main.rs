@@ -1,58 +1,163 @@
-use abstutil::{prettyprint_usize, CmdArgs, Timer};
-use map_model::Map;
+// This runs a simulation without any graphics. By default, it serves a very basic API to control
+// the simulation; see server.rs. Pass --batch to instead run a set of experiments to completion and
+// compare them against a baseline; see batch.rs. Pass --seeds to run the same scenario with many
+// RNG seeds and measure the spread of results; see monte_carlo.rs. Pass --equilibrium to
+// repeatedly run a scenario, routing cars around the congestion seen in the previous run; see
+// equilibrium.rs. Pass --optimize_signals to tune the timing of some traffic signals and save the
+// result as map edits; see signal_optimizer.rs.
+
+mod batch;
+mod equilibrium;
+mod monte_carlo;
+mod server;
+mod signal_optimizer;
+
+use abstutil::{CmdArgs, Timer};
+use geom::Time;
+use map_model::{IntersectionID, Map, PermanentMapEdits};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
-use sim::{AlertHandler, Scenario, ScenarioModifier, Sim, SimFlags};
-
-// This is specialized to experiment with running the pandemic model over long time periods.
-// Original functionality for profiling and debugging gridlock have been removed.
+use sim::{Scenario, ScenarioModifier, Sim, SimFlags, SimOptions};
+use std::error::Error;
 
 fn main() {
     let mut args = CmdArgs::new();
-    let num_days = args
-        .optional_parse("--days", |s| s.parse::<usize>())
-        .unwrap_or(1);
+    let port = args
+        .optional_parse("--port", |s| s.parse::<u16>())
+        .unwrap_or(8080);
+    let batch = args.optional("--batch");
+    let num_seeds = args.optional_parse("--seeds", |s| s.parse::<usize>());
+    let equilibrium_iterations = args.optional_parse("--equilibrium", |s| s.parse::<usize>());
+    // A comma-separated list of intersection IDs
+    let optimize_signals = args.optional_parse("--optimize_signals", |s| {
+        s.split(',')
+            .map(|i| i.parse::<usize>().map(IntersectionID))
+            .collect::<Result<Vec<_>, _>>()
+    });
+    // Each candidate timing is measured by simulating this far, so keep it short
+    let until = args.optional_parse("--until", Time::parse);
+    let edits = args.optional("--edits");
+    let output = args.optional("--output");
+    let sim_flags = SimFlags::from_args(&mut args);
     args.done();
 
-    let mut sim_flags = SimFlags::synthetic_test("montlake", "pandemic");
-    sim_flags.opts.enable_pandemic_model = Some(XorShiftRng::from_seed([sim_flags.rng_seed; 16]));
-    // Less spam
-    sim_flags.opts.alerts = AlertHandler::Silence;
-    let mut timer = Timer::new("setup headless");
-    let (mut map, _, mut rng) = sim_flags.load(&mut timer);
-    map.hack_override_offstreet_spots(num_days);
-    // After the map changes, have to re-create the (empty) Sim, because things like
-    // ParkingSimState are out of sync.
-    let mut sim = Sim::new(&map, sim_flags.opts.clone(), &mut timer);
-
-    let base_scenario: Scenario = abstutil::read_binary(
-        abstutil::path_scenario(map.get_name(), "weekday"),
-        &mut timer,
-    );
-    ScenarioModifier::RepeatDays(num_days)
-        .apply(&map, base_scenario, &mut rng)
-        .instantiate(&mut sim, &map, &mut rng, &mut timer);
-    timer.done();
+    if let Some(config) = batch {
+        batch::run(
+            config,
+            output.unwrap_or_else(|| "batch_report.json".to_string()),
+            sim_flags,
+        );
+        return;
+    }
+    if let Some(n) = num_seeds {
+        monte_carlo::run(
+            n,
+            edits,
+            output.unwrap_or_else(|| "monte_carlo_report.json".to_string()),
+            sim_flags,
+        );
+        return;
+    }
+    if let Some(n) = equilibrium_iterations {
+        equilibrium::run(
+            n,
+            edits,
+            output.unwrap_or_else(|| "equilibrium_report.json".to_string()),
+            sim_flags,
+        );
+        return;
+    }
 
-    run_experiment(&map, &mut sim);
-}
+    if let Some(intersections) = optimize_signals {
+        signal_optimizer::run(
+            intersections,
+            until.expect("--optimize_signals needs --until, like --until=09:00:00"),
+            edits,
+            output.unwrap_or_else(|| "optimized signals".to_string()),
+            sim_flags,
+        );
+        return;
+    }
 
-fn run_experiment(map: &Map, sim: &mut Sim) {
-    let timer = Timer::new("run sim until done");
-    sim.run_until_done(
-        &map,
-        |sim, _map| {
-            // This'll run every 30 sim seconds
-            if false {
-                println!(
-                    "At {}, {} infected",
-                    sim.time(),
-                    prettyprint_usize(sim.get_pandemic_model().unwrap().count_infected())
-                );
-            }
+    server::run(
+        port,
+        LoadSim {
+            scenario: sim_flags.load,
+            modifiers: Vec::new(),
+            edits: None,
+            rng_seed: sim_flags.rng_seed,
+            opts: sim_flags.opts,
         },
-        None,
     );
-    timer.done();
-    println!("Done at {}", sim.time());
+}
+
+// Everything needed to recreate the simulation from scratch
+#[derive(Clone)]
+pub struct LoadSim {
+    // Path to a scenario file
+    pub scenario: String,
+    pub modifiers: Vec<ScenarioModifier>,
+    pub edits: Option<PermanentMapEdits>,
+    pub rng_seed: u8,
+    pub opts: SimOptions,
+}
+
+impl LoadSim {
+    pub fn setup(&self, timer: &mut Timer) -> Result<(Map, Sim), Box<dyn Error>> {
+        let (map, scenario) = self.load_map(timer)?;
+        let sim = self.make_sim(&map, scenario, self.rng_seed, timer);
+        Ok((map, sim))
+    }
+
+    // Runs sharing one --record_events path would overwrite each other's file, so each gets its
+    // own, like events_seed3.bin
+    pub fn recording_as(&self, suffix: &str) -> LoadSim {
+        let mut load = self.clone();
+        if let Some(ref mut path) = load.opts.record_events {
+            let (stem, ext) = match path.rfind('.') {
+                Some(idx) if !path[idx..].contains('/') => path.split_at(idx),
+                _ => (path.as_str(), ""),
+            };
+            *path = format!("{}_{}{}", stem, suffix, ext);
+        }
+        load
+    }
+
+    // Loads the scenario and its map, with edits applied. The result can be reused to create many
+    // simulations.
+    pub fn load_map(&self, timer: &mut Timer) -> Result<(Map, Scenario), Box<dyn Error>> {
+        if !self
+            .scenario
+            .starts_with(&abstutil::path("system/scenarios/"))
+        {
+            return Err(format!("{} isn't a scenario", self.scenario).into());
+        }
+        let scenario: Scenario =
+            abstutil::maybe_read_versioned_binary(self.scenario.clone(), timer)?;
+        let mut map = Map::new(abstutil::path_map(&scenario.map_name), timer);
+        if let Some(ref perma) = self.edits {
+            let edits = PermanentMapEdits::from_permanent(perma.clone(), &map)?;
+            map.must_apply_edits(edits, timer);
+            map.recalculate_pathfinding_after_edits(timer);
+        }
+        Ok((map, scenario))
+    }
+
+    // The modifiers are applied here, since they might use the RNG.
+    pub fn make_sim(
+        &self,
+        map: &Map,
+        mut scenario: Scenario,
+        rng_seed: u8,
+        timer: &mut Timer,
+    ) -> Sim {
+        let mut rng = XorShiftRng::from_seed([rng_seed; 16]);
+        for m in &self.modifiers {
+            scenario = m.apply(map, scenario, &mut rng);
+        }
+
+        let mut sim = Sim::new(map, self.opts.clone(), timer);
+        scenario.instantiate(&mut sim, map, &mut rng, timer);
+        sim
+    }
 }
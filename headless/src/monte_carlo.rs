@@ -0,0 +1,290 @@
+// Runs the same scenario (and optionally map edits) with many different RNG seeds, then reports the
+// spread of results. Any single run is just one sample; this tells whether an effect is larger than
+// the run-to-run noise.
+//
+// > cd headless; cargo run --release -- --seeds=20 --edits=edits.json \
+//     ../data/system/scenarios/montlake/weekday.bin
+//
+// The map is only loaded once and shared between all of the simulations, which run in parallel.
+// All durations in the report are in seconds.
+
+use crate::LoadSim;
+use abstutil::Timer;
+use geom::{Duration, Histogram, Statistic};
+use map_model::IntersectionID;
+use serde::Serialize;
+use sim::{SimFlags, TripMode};
+use std::collections::BTreeMap;
+
+pub fn run(num_seeds: usize, edits: Option<String>, output: String, flags: SimFlags) {
+    // The seed is a u8
+    assert!(
+        num_seeds > 0 && num_seeds <= 256,
+        "--seeds must be between 1 and 256"
+    );
+
+    let mut timer = Timer::new("run with many seeds");
+    let load = LoadSim {
+        scenario: flags.load.clone(),
+        modifiers: Vec::new(),
+        edits: edits
+            .clone()
+            .map(|path| abstutil::read_json(path, &mut timer)),
+        rng_seed: flags.rng_seed,
+        opts: flags.opts,
+    };
+    let (map, scenario) = load
+        .load_map(&mut timer)
+        .unwrap_or_else(|err| panic!("Couldn't set up {}: {}", load.scenario, err));
+
+    let seeds: Vec<u8> = (0..num_seeds)
+        .map(|i| flags.rng_seed.wrapping_add(i as u8))
+        .collect();
+    let load = &load;
+    let map = &map;
+    let scenario = &scenario;
+    let results = timer.parallelize("run simulations", seeds.clone(), |seed| {
+        let mut sim = load.recording_as(&format!("seed{}", seed)).make_sim(
+            map,
+            scenario.clone(),
+            seed,
+            &mut Timer::throwaway(),
+        );
+        sim.run_until_done(map, |_, _| {}, None);
+        SeedResults::new(sim.get_analytics())
+    });
+
+    let report = aggregate(load.scenario.clone(), edits, seeds, results);
+    println!(
+        "Mean trip time over {} runs: {:.1}s (95% CI {:.1}s to {:.1}s)",
+        report.mean_trip_time.n,
+        report.mean_trip_time.mean,
+        report.mean_trip_time.ci95_low,
+        report.mean_trip_time.ci95_high
+    );
+    abstutil::write_json(output, &report);
+}
+
+// The results from one simulation
+struct SeedResults {
+    finished_trips: usize,
+    aborted_trips: usize,
+    trip_times: Histogram<Duration>,
+    total_trip_time: Duration,
+    // (number of finished trips, total time)
+    per_mode: BTreeMap<TripMode, (usize, Duration)>,
+    total_delay_per_intersection: BTreeMap<IntersectionID, Duration>,
+}
+
+impl SeedResults {
+    fn new(analytics: &sim::Analytics) -> SeedResults {
+        let mut results = SeedResults {
+            finished_trips: 0,
+            aborted_trips: 0,
+            trip_times: Histogram::new(),
+            total_trip_time: Duration::ZERO,
+            per_mode: BTreeMap::new(),
+            total_delay_per_intersection: BTreeMap::new(),
+        };
+        for (_, _, maybe_mode, dt) in &analytics.finished_trips {
+            if let Some(mode) = maybe_mode {
+                results.finished_trips += 1;
+                results.trip_times.add(*dt);
+                results.total_trip_time += *dt;
+                let entry = results.per_mode.entry(*mode).or_insert((0, Duration::ZERO));
+                entry.0 += 1;
+                entry.1 += *dt;
+            } else {
+                results.aborted_trips += 1;
+            }
+        }
+        for (i, delays) in &analytics.intersection_delays {
+            let mut sum = Duration::ZERO;
+            for (_, dt, _) in delays {
+                sum += *dt;
+            }
+            results.total_delay_per_intersection.insert(*i, sum);
+        }
+        results
+    }
+
+    fn mean_trip_time(&self) -> Option<f64> {
+        if self.finished_trips == 0 {
+            None
+        } else {
+            Some(self.total_trip_time.inner_seconds() / (self.finished_trips as f64))
+        }
+    }
+
+    // None if no trips finished
+    fn trip_time(&self, stat: Statistic) -> Option<Duration> {
+        self.trip_times.select(stat)
+    }
+}
+
+#[derive(Serialize)]
+struct MonteCarloReport {
+    scenario: String,
+    edits: Option<String>,
+    seeds: Vec<u8>,
+    finished_trips: Summary,
+    aborted_trips: Summary,
+    mean_trip_time: Summary,
+    p50_trip_time: Summary,
+    p90_trip_time: Summary,
+    p99_trip_time: Summary,
+    modes: Vec<ModeSummary>,
+    intersections: Vec<IntersectionSummary>,
+}
+
+#[derive(Serialize)]
+struct ModeSummary {
+    mode: TripMode,
+    finished_trips: Summary,
+    mean_trip_time: Summary,
+}
+
+#[derive(Serialize)]
+struct IntersectionSummary {
+    id: IntersectionID,
+    total_delay: Summary,
+}
+
+fn aggregate(
+    scenario: String,
+    edits: Option<String>,
+    seeds: Vec<u8>,
+    results: Vec<SeedResults>,
+) -> MonteCarloReport {
+    let modes = TripMode::all()
+        .into_iter()
+        .map(|mode| ModeSummary {
+            mode,
+            finished_trips: per_run(&results, |r| {
+                r.per_mode.get(&mode).map(|(cnt, _)| *cnt).unwrap_or(0) as f64
+            }),
+            // Runs where nobody finished a trip with this mode don't count.
+            mean_trip_time: some_runs(&results, |r| match r.per_mode.get(&mode) {
+                Some((cnt, total)) if *cnt > 0 => Some(total.inner_seconds() / (*cnt as f64)),
+                _ => None,
+            }),
+        })
+        .collect();
+
+    // An intersection with no delay measured in some run counts as zero delay there.
+    let mut all_intersections = Vec::new();
+    for r in &results {
+        all_intersections.extend(r.total_delay_per_intersection.keys().cloned());
+    }
+    all_intersections.sort();
+    all_intersections.dedup();
+    let intersections = all_intersections
+        .into_iter()
+        .map(|i| IntersectionSummary {
+            id: i,
+            total_delay: per_run(&results, |r| {
+                r.total_delay_per_intersection
+                    .get(&i)
+                    .cloned()
+                    .unwrap_or(Duration::ZERO)
+                    .inner_seconds()
+            }),
+        })
+        .collect();
+
+    MonteCarloReport {
+        scenario,
+        edits,
+        seeds,
+        finished_trips: per_run(&results, |r| r.finished_trips as f64),
+        aborted_trips: per_run(&results, |r| r.aborted_trips as f64),
+        mean_trip_time: some_runs(&results, |r| r.mean_trip_time()),
+        p50_trip_time: some_runs(&results, |r| {
+            r.trip_time(Statistic::P50).map(|dt| dt.inner_seconds())
+        }),
+        p90_trip_time: some_runs(&results, |r| {
+            r.trip_time(Statistic::P90).map(|dt| dt.inner_seconds())
+        }),
+        p99_trip_time: some_runs(&results, |r| {
+            r.trip_time(Statistic::P99).map(|dt| dt.inner_seconds())
+        }),
+        modes,
+        intersections,
+    }
+}
+
+fn per_run<F: Fn(&SeedResults) -> f64>(results: &[SeedResults], f: F) -> Summary {
+    Summary::new(results.iter().map(f).collect())
+}
+
+// Only from the runs where the measurement makes sense
+fn some_runs<F: Fn(&SeedResults) -> Option<f64>>(results: &[SeedResults], f: F) -> Summary {
+    Summary::new(results.iter().filter_map(f).collect())
+}
+
+// Describes one measurement taken from every run.
+#[derive(Serialize)]
+struct Summary {
+    // How many runs this was measured from
+    n: usize,
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    max: f64,
+    // 95% confidence interval of the mean, using Student's t-distribution
+    ci95_low: f64,
+    ci95_high: f64,
+}
+
+impl Summary {
+    fn new(samples: Vec<f64>) -> Summary {
+        if samples.is_empty() {
+            return Summary {
+                n: 0,
+                mean: 0.0,
+                std_dev: 0.0,
+                min: 0.0,
+                max: 0.0,
+                ci95_low: 0.0,
+                ci95_high: 0.0,
+            };
+        }
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let std_dev = if samples.len() > 1 {
+            (samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+        let margin = t_critical_95(samples.len()) * std_dev / n.sqrt();
+        Summary {
+            n: samples.len(),
+            mean,
+            std_dev,
+            min: samples.iter().cloned().fold(std::f64::INFINITY, f64::min),
+            max: samples
+                .iter()
+                .cloned()
+                .fold(std::f64::NEG_INFINITY, f64::max),
+            ci95_low: mean - margin,
+            ci95_high: mean + margin,
+        }
+    }
+}
+
+// Two-sided 95% critical values of Student's t-distribution, for n - 1 degrees of freedom.
+fn t_critical_95(n: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    if n < 2 {
+        // No spread can be measured from one run
+        0.0
+    } else if n - 1 <= TABLE.len() {
+        TABLE[n - 2]
+    } else {
+        1.96
+    }
+}
@@ -0,0 +1,312 @@
+// Serves a very basic API to control a simulation. To run this:
+//
+// > cd headless; cargo run -- --port=1234 ../data/system/scenarios/montlake/weekday.bin
+// > curl http://localhost:1234/sim/get-time
+// 00:00:00.0
+// > curl http://localhost:1234/sim/goto-time?t=01:01:00
+// it's now 01:01:00.0
+// > curl http://localhost:1234/data/get-delays?threshold=30
+// ... JSON response ...
+//
+// Requests that change things (/sim/load, /map/set-edits) expect a JSON body sent with POST.
+// Everything else is a GET, with arguments passed as query parameters.
+
+use crate::LoadSim;
+use abstutil::Timer;
+use geom::{Duration, Statistic, Time};
+use map_model::{Map, PermanentMapEdits};
+use serde::{Deserialize, Serialize};
+use sim::{AgentType, Emissions, FleetMix, ScenarioModifier, Sim, TripID, TripMode};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::Read;
+
+pub fn run(port: u16, mut load: LoadSim) {
+    let (mut map, mut sim) = load.setup(&mut Timer::new("setup headless")).unwrap();
+
+    let server = tiny_http::Server::http(format!("127.0.0.1:{}", port))
+        .unwrap_or_else(|err| panic!("Couldn't listen on port {}: {}", port, err));
+    println!("Listening on http://127.0.0.1:{}", port);
+
+    for mut request in server.incoming_requests() {
+        let (path, params) = parse_url(request.url());
+        let mut body = String::new();
+        if let Err(err) = request.as_reader().read_to_string(&mut body) {
+            println!("Couldn't read request body for {}: {}", path, err);
+            continue;
+        }
+        let response = match handle_command(&path, &params, &body, &mut load, &mut map, &mut sim) {
+            Ok(resp) => tiny_http::Response::from_string(resp),
+            Err(err) => tiny_http::Response::from_string(format!("Bad command {}: {}", path, err))
+                .with_status_code(500),
+        };
+        if let Err(err) = request.respond(response) {
+            println!("Couldn't respond to {}: {}", path, err);
+        }
+    }
+}
+
+fn handle_command(
+    path: &str,
+    params: &HashMap<String, String>,
+    body: &str,
+    load: &mut LoadSim,
+    map: &mut Map,
+    sim: &mut Sim,
+) -> Result<String, Box<dyn Error>> {
+    match path {
+        // Controlling the simulation
+        "/sim/reset" => {
+            let (new_map, new_sim) = load.setup(&mut Timer::new("reset sim"))?;
+            *map = new_map;
+            *sim = new_sim;
+            Ok("sim reloaded".to_string())
+        }
+        "/sim/load" => {
+            let req: LoadRequest = serde_json::from_str(body)?;
+            let new_load = LoadSim {
+                scenario: req.scenario,
+                modifiers: req.modifiers,
+                edits: req.edits,
+                rng_seed: req.rng_seed.unwrap_or(load.rng_seed),
+                opts: load.opts.clone(),
+            };
+            // Only replace anything once the new setup succeeds
+            let (new_map, new_sim) = new_load.setup(&mut Timer::new("load sim"))?;
+            *load = new_load;
+            *map = new_map;
+            *sim = new_sim;
+            Ok(format!("{} loaded", load.scenario))
+        }
+        "/sim/get-time" => Ok(sim.time().to_string()),
+        "/sim/goto-time" => {
+            let t = Time::parse(get_param(params, "t")?)?;
+            if t <= sim.time() {
+                Err(format!("{} is in the past. call /sim/reset first?", t).into())
+            } else {
+                let dt = t - sim.time();
+                sim.timed_step(map, dt, &mut None, &mut Timer::new("goto-time"));
+                Ok(format!("it's now {}", sim.time()))
+            }
+        }
+        "/sim/step" => {
+            let dt = Duration::parse(get_param(params, "dt")?)?;
+            if let Some(limit) = params.get("real_time_limit") {
+                sim.time_limited_step(map, dt, Duration::parse(limit)?, &mut None);
+            } else {
+                sim.timed_step(map, dt, &mut None, &mut Timer::new("step"));
+            }
+            Ok(format!("it's now {}", sim.time()))
+        }
+        "/sim/is-done" => Ok(sim.is_done().to_string()),
+        // Map edits
+        "/map/get-edits" => Ok(abstutil::to_json(&PermanentMapEdits::to_permanent(
+            map.get_edits(),
+            map,
+        ))),
+        "/map/set-edits" => {
+            // The simulation has to restart from midnight to pick up the edits.
+            let mut new_load = load.clone();
+            new_load.edits = Some(serde_json::from_str(body)?);
+            let (new_map, new_sim) = new_load.setup(&mut Timer::new("apply edits"))?;
+            *load = new_load;
+            *map = new_map;
+            *sim = new_sim;
+            Ok(format!("edits applied, sim reset to {}", sim.time()))
+        }
+        // Querying data
+        "/data/get-num-agents" => {
+            let counts: BTreeMap<AgentType, usize> = sim.num_agents();
+            Ok(abstutil::to_json(&counts))
+        }
+        "/data/get-trips" => {
+            let mut trips = Vec::new();
+            for (id, info) in sim.all_trip_info() {
+                let (duration, blocked_time) = match sim.finished_trip_time(id) {
+                    Some((total, blocked)) => (Some(total), Some(blocked)),
+                    None => (None, None),
+                };
+                trips.push(TripResponse {
+                    id,
+                    departure: info.departure,
+                    mode: info.mode,
+                    duration,
+                    blocked_time,
+                });
+            }
+            Ok(abstutil::to_json(&trips))
+        }
+        "/data/get-finished-trips" => {
+            let mut trips = Vec::new();
+            for (t, id, mode, dt) in &sim.get_analytics().finished_trips {
+                trips.push(FinishedTrip {
+                    time: *t,
+                    id: *id,
+                    mode: *mode,
+                    duration: *dt,
+                });
+            }
+            Ok(abstutil::to_json(&trips))
+        }
+        "/data/get-road-thruput" => {
+            let mut counts = Vec::new();
+            for ((r, agent_type, hr), count) in &sim.get_analytics().road_thruput.counts {
+                counts.push(ThruputCount {
+                    id: r.0,
+                    agent_type: *agent_type,
+                    hour: *hr,
+                    count: *count,
+                });
+            }
+            Ok(abstutil::to_json(&counts))
+        }
+        "/data/get-intersection-thruput" => {
+            let mut counts = Vec::new();
+            for ((i, agent_type, hr), count) in &sim.get_analytics().intersection_thruput.counts {
+                counts.push(ThruputCount {
+                    id: i.0,
+                    agent_type: *agent_type,
+                    hour: *hr,
+                    count: *count,
+                });
+            }
+            Ok(abstutil::to_json(&counts))
+        }
+        "/data/get-delays" => {
+            let threshold = Duration::parse(get_param(params, "threshold")?)?;
+            let mut delays = Vec::new();
+            for (i, since) in sim.delayed_intersections(threshold) {
+                delays.push(DelayedIntersection {
+                    id: i.0,
+                    waiting_since: since,
+                });
+            }
+            Ok(abstutil::to_json(&delays))
+        }
+        "/data/get-ride-hail" => {
+            let analytics = sim.get_analytics();
+            let (idle, driving_empty, carrying_passenger, requests_waiting) =
+                sim.ride_hail_fleet_status();
+            let waits = analytics.ride_hail_waits(sim.time());
+            let (empty_dist, occupied_dist) = analytics.ride_hail_distances(sim.time());
+            Ok(abstutil::to_json(&RideHailSummary {
+                idle,
+                driving_empty,
+                carrying_passenger,
+                requests_waiting,
+                pickups: waits.count(),
+                median_wait: waits.select(Statistic::P50),
+                max_wait: waits.select(Statistic::Max),
+                empty_meters: empty_dist.inner_meters(),
+                occupied_meters: occupied_dist.inner_meters(),
+            }))
+        }
+        "/data/get-emissions" => {
+            let report = sim.get_analytics().emissions(&FleetMix::new());
+            Ok(abstutil::to_json(&EmissionsSummary {
+                total: report.total,
+                per_trip: report.per_trip.into_iter().collect(),
+                per_road: report.per_road.into_iter().map(|(r, e)| (r.0, e)).collect(),
+                per_intersection: report
+                    .per_intersection
+                    .into_iter()
+                    .map(|(i, e)| (i.0, e))
+                    .collect(),
+            }))
+        }
+        _ => Err("Unknown command".into()),
+    }
+}
+
+#[derive(Deserialize)]
+struct LoadRequest {
+    scenario: String,
+    #[serde(default)]
+    modifiers: Vec<ScenarioModifier>,
+    edits: Option<PermanentMapEdits>,
+    rng_seed: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct TripResponse {
+    id: TripID,
+    departure: Time,
+    mode: TripMode,
+    // None if the trip hasn't finished
+    duration: Option<Duration>,
+    blocked_time: Option<Duration>,
+}
+
+#[derive(Serialize)]
+struct FinishedTrip {
+    time: Time,
+    id: TripID,
+    // None means aborted
+    mode: Option<TripMode>,
+    duration: Duration,
+}
+
+#[derive(Serialize)]
+struct ThruputCount {
+    // A RoadID or IntersectionID
+    id: usize,
+    agent_type: AgentType,
+    hour: usize,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct RideHailSummary {
+    // Current state of the fleet
+    idle: usize,
+    driving_empty: usize,
+    carrying_passenger: usize,
+    requests_waiting: usize,
+    // Over the whole day so far
+    pickups: usize,
+    median_wait: Option<Duration>,
+    max_wait: Option<Duration>,
+    // Deadheading and repositioning
+    empty_meters: f64,
+    occupied_meters: f64,
+}
+
+// Using the default fleet mix
+#[derive(Serialize)]
+struct EmissionsSummary {
+    total: Emissions,
+    per_trip: Vec<(TripID, Emissions)>,
+    per_road: Vec<(usize, Emissions)>,
+    per_intersection: Vec<(usize, Emissions)>,
+}
+
+#[derive(Serialize)]
+struct DelayedIntersection {
+    id: usize,
+    waiting_since: Time,
+}
+
+// Splits "/sim/goto-time?t=01:00:00" into the path and query parameters. Doesn't decode anything.
+fn parse_url(url: &str) -> (String, HashMap<String, String>) {
+    let mut params = HashMap::new();
+    let mut parts = url.splitn(2, '?');
+    let path = parts.next().unwrap().to_string();
+    if let Some(query) = parts.next() {
+        for pair in query.split('&') {
+            let mut kv = pair.splitn(2, '=');
+            let key = kv.next().unwrap();
+            if key.is_empty() {
+                continue;
+            }
+            params.insert(key.to_string(), kv.next().unwrap_or("").to_string());
+        }
+    }
+    (path, params)
+}
+
+fn get_param<'a>(params: &'a HashMap<String, String>, key: &str) -> Result<&'a str, String> {
+    params
+        .get(key)
+        .map(|x| x.as_str())
+        .ok_or_else(|| format!("missing ?{}=", key))
+}
@@ -0,0 +1,156 @@
+// Tunes the timing of a few traffic signals by trial and error. Starting from the current timing,
+// nudge each phase's duration and each signal's offset one step at a time, keeping any change that
+// lowers the total delay measured at every intersection in a short simulation. The result is saved
+// as map edits, which can be loaded and reviewed in the game's edit mode.
+//
+// > cd headless; cargo run --release -- --optimize_signals=42,43 --until=09:00:00 \
+//     ../data/system/scenarios/montlake/weekday.bin
+//
+// TODO This is a greedy coordinate search, so it'll get stuck in local minima. Phase layouts aren't
+// searched, and actuated phases are left alone.
+
+use crate::LoadSim;
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{ControlTrafficSignal, EditCmd, IntersectionID, Map, PhaseType};
+use sim::{Scenario, SimFlags};
+
+// How much to nudge durations and offsets by
+const STEP: Duration = Duration::const_seconds(5.0);
+const MIN_PHASE_DURATION: Duration = Duration::const_seconds(5.0);
+// Give up after this many passes over every signal
+const MAX_ROUNDS: usize = 5;
+
+pub fn run(
+    intersections: Vec<IntersectionID>,
+    until: Time,
+    edits: Option<String>,
+    output: String,
+    flags: SimFlags,
+) {
+    let mut timer = Timer::new("optimize traffic signals");
+    let load = LoadSim {
+        scenario: flags.load.clone(),
+        modifiers: Vec::new(),
+        edits: edits.map(|path| abstutil::read_json(path, &mut timer)),
+        rng_seed: flags.rng_seed,
+        opts: flags.opts,
+    };
+    let (mut map, scenario) = load
+        .load_map(&mut timer)
+        .unwrap_or_else(|err| panic!("Couldn't set up {}: {}", load.scenario, err));
+
+    let mut orig_signals = Vec::new();
+    for i in &intersections {
+        if let Some(signal) = map.maybe_get_traffic_signal(*i) {
+            orig_signals.push(signal.clone());
+        } else {
+            panic!("{} isn't a traffic signal", i);
+        }
+    }
+
+    let mut best = total_delay(&load, &map, &scenario, until, &mut timer);
+    println!("Initially, {} total delay", best);
+    for round in 1..=MAX_ROUNDS {
+        let mut improved = false;
+        for i in &intersections {
+            for candidate in neighbors(map.get_traffic_signal(*i)) {
+                let current = map.get_traffic_signal(*i).clone();
+                map.incremental_edit_traffic_signal(candidate);
+                let delay = total_delay(&load, &map, &scenario, until, &mut timer);
+                if delay < best {
+                    println!("Round {}: tweaking {} lowers delay to {}", round, i, delay);
+                    best = delay;
+                    improved = true;
+                } else {
+                    map.incremental_edit_traffic_signal(current);
+                }
+            }
+        }
+        if !improved {
+            println!("Nothing better found in round {}", round);
+            break;
+        }
+    }
+
+    // Express the changes as map edits. Restore the original signals first, so the edits have
+    // something to change.
+    let mut new_edits = map.get_edits().clone();
+    new_edits.edits_name = output;
+    for orig in orig_signals {
+        let i = orig.id;
+        let new = map.get_i_edit(i);
+        map.incremental_edit_traffic_signal(orig);
+        let old = map.get_i_edit(i);
+        if old != new {
+            new_edits
+                .commands
+                .push(EditCmd::ChangeIntersection { i, old, new });
+        }
+    }
+    let num_changed = new_edits.commands.len() - map.get_edits().commands.len();
+    map.must_apply_edits(new_edits, &mut timer);
+    map.save_edits();
+    println!(
+        "Changed {} signals, with {} total delay. Saved edits as \"{}\"",
+        num_changed,
+        best,
+        map.get_edits().edits_name
+    );
+}
+
+// Every signal one step away from this one
+fn neighbors(signal: &ControlTrafficSignal) -> Vec<ControlTrafficSignal> {
+    let mut results = Vec::new();
+    for (idx, phase) in signal.phases.iter().enumerate() {
+        let dt = match phase.phase_type {
+            PhaseType::Fixed(dt) | PhaseType::Adaptive(dt) => dt,
+            PhaseType::Actuated { .. } => {
+                continue;
+            }
+        };
+        for new_dt in [dt + STEP, dt - STEP].iter().cloned() {
+            if new_dt < MIN_PHASE_DURATION {
+                continue;
+            }
+            let mut new_signal = signal.clone();
+            new_signal.phases[idx].phase_type = match phase.phase_type {
+                PhaseType::Adaptive(_) => PhaseType::Adaptive(new_dt),
+                _ => PhaseType::Fixed(new_dt),
+            };
+            results.push(new_signal);
+        }
+    }
+
+    let cycle_length = signal.cycle_length();
+    for new_offset in [signal.offset + STEP, signal.offset + cycle_length - STEP].iter() {
+        let mut new_signal = signal.clone();
+        new_signal.offset = *new_offset % cycle_length;
+        results.push(new_signal);
+    }
+    results
+}
+
+// The delay of every turn finished before the time limit. Agents still waiting then count too, so
+// starving one movement doesn't look like an improvement.
+fn total_delay(
+    load: &LoadSim,
+    map: &Map,
+    scenario: &Scenario,
+    until: Time,
+    timer: &mut Timer,
+) -> Duration {
+    let mut sim = load.make_sim(map, scenario.clone(), load.rng_seed, timer);
+    sim.timed_step(map, until - Time::START_OF_DAY, &mut None, timer);
+
+    let mut total = Duration::ZERO;
+    for delays in sim.get_analytics().intersection_delays.values() {
+        for (_, dt, _) in delays {
+            total += *dt;
+        }
+    }
+    for dt in sim.worst_delay(map).1.values() {
+        total += *dt;
+    }
+    total
+}
@@ -0,0 +1,340 @@
+use abstutil::{Counter, Timer};
+use geom::{Distance, LonLat, Pt2D, Time};
+use map_model::{BusRoute, BusRouteID, BusStopID, Map};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+
+// GTFS stops further than this from any BusStop in the map are ignored.
+const MAX_STOP_DIST: Distance = Distance::const_meters(50.0);
+
+// Reads a GTFS feed (stops.txt, routes.txt, trips.txt, stop_times.txt) from a local directory and
+// replaces the hourly default schedule of every bus route it can match.
+pub fn import(map: &mut Map, dir: &str, timer: &mut Timer) {
+    timer.start("match GTFS stops");
+    let mut stops: HashMap<String, BusStopID> = HashMap::new();
+    for rec in read::<Stop>(dir, "stops.txt", timer) {
+        let pt = Pt2D::from_gps(
+            LonLat::new(rec.stop_lon, rec.stop_lat),
+            map.get_gps_bounds(),
+        );
+        if let Some(id) = match_stop(
+            pt,
+            map.all_bus_stops()
+                .values()
+                .map(|bs| (bs.id, bs.sidewalk_pos.pt(map))),
+        ) {
+            stops.insert(rec.stop_id, id);
+        }
+    }
+    timer.note(format!("{} GTFS stops matched to bus stops", stops.len()));
+    timer.stop("match GTFS stops");
+
+    let mut gtfs_routes: HashMap<String, Route> = HashMap::new();
+    for rec in read::<Route>(dir, "routes.txt", timer) {
+        gtfs_routes.insert(rec.route_id.clone(), rec);
+    }
+
+    // Feeds usually describe weekdays, weekends, and holidays as different services. Without
+    // calendar.txt, guess that the service with the most trips is the typical weekday.
+    let trips: Vec<Trip> = read(dir, "trips.txt", timer);
+    let mut services = Counter::new();
+    for trip in &trips {
+        services.inc(trip.service_id.clone());
+    }
+    let service = match services.borrow().iter().max_by_key(|(_, cnt)| **cnt) {
+        Some((service, _)) => service.clone(),
+        None => {
+            timer.warn(format!("No trips in {}/trips.txt", dir));
+            return;
+        }
+    };
+    let trip_to_route: HashMap<String, String> = trips
+        .into_iter()
+        .filter(|t| t.service_id == service)
+        .map(|t| (t.trip_id, t.route_id))
+        .collect();
+
+    // For every trip, the (stop, departure) sequence
+    let mut schedules: HashMap<String, Vec<(usize, BusStopID, Time)>> = HashMap::new();
+    for rec in read::<StopTime>(dir, "stop_times.txt", timer) {
+        if !trip_to_route.contains_key(&rec.trip_id) {
+            continue;
+        }
+        let stop = match stops.get(&rec.stop_id) {
+            Some(id) => *id,
+            None => continue,
+        };
+        match Time::parse(&rec.departure_time) {
+            Ok(t) => {
+                schedules.entry(rec.trip_id).or_insert_with(Vec::new).push((
+                    rec.stop_sequence,
+                    stop,
+                    t,
+                ));
+            }
+            Err(err) => {
+                timer.warn(format!(
+                    "Bad departure_time for trip {}: {}",
+                    rec.trip_id, err
+                ));
+            }
+        }
+    }
+
+    let mut spawn_times: BTreeMap<BusRouteID, Vec<Time>> = BTreeMap::new();
+    timer.start_iter("match GTFS trips to routes", schedules.len());
+    for (trip, mut schedule) in schedules {
+        timer.next();
+        schedule.sort_by_key(|(seq, _, _)| *seq);
+        let gtfs_route = match gtfs_routes.get(&trip_to_route[&trip]) {
+            Some(r) => r,
+            None => {
+                timer.warn(format!(
+                    "Trip {} has unknown route {}, skipping it",
+                    trip, trip_to_route[&trip]
+                ));
+                continue;
+            }
+        };
+        for route in map.all_bus_routes() {
+            if !same_route(gtfs_route, route) {
+                continue;
+            }
+            if let Some(t) = departure_for_route(&schedule, &route.stops) {
+                spawn_times.entry(route.id).or_insert_with(Vec::new).push(t);
+            }
+        }
+    }
+
+    for route in map.all_bus_routes() {
+        if !spawn_times.contains_key(&route.id) {
+            timer.warn(format!(
+                "No GTFS trips match {}, keeping the default schedule",
+                route.full_name
+            ));
+        }
+    }
+    for (route, mut times) in spawn_times {
+        times.sort();
+        times.dedup();
+        timer.note(format!(
+            "{} has {} scheduled departures",
+            map.get_br(route).full_name,
+            times.len()
+        ));
+        map.set_bus_route_schedule(route, times);
+    }
+}
+
+// The closest bus stop to a GTFS stop, if it's within MAX_STOP_DIST
+fn match_stop<T>(pt: Pt2D, bus_stops: impl Iterator<Item = (T, Pt2D)>) -> Option<T> {
+    bus_stops
+        .map(|(id, stop_pt)| (id, stop_pt.dist_to(pt)))
+        .min_by_key(|(_, dist)| *dist)
+        .filter(|(_, dist)| *dist <= MAX_STOP_DIST)
+        .map(|(id, _)| id)
+}
+
+// The trip, sorted by stop sequence, has to serve the route's first two stops, in order. Returns
+// the departure from the first stop as the spawn time; the time to reach it from the border is
+// ignored.
+fn departure_for_route<T: PartialEq>(schedule: &[(usize, T, Time)], stops: &[T]) -> Option<Time> {
+    let idx = schedule
+        .iter()
+        .position(|(_, stop, _)| Some(stop) == stops.get(0))?;
+    if let Some(second) = stops.get(1) {
+        if !schedule[idx + 1..]
+            .iter()
+            .any(|(_, stop, _)| stop == second)
+        {
+            return None;
+        }
+    }
+    Some(schedule[idx].2)
+}
+
+// OSM short names are sometimes like "Route 44". Without a GTFS short name, fall back to the long
+// name. Without either, don't guess.
+fn same_route(gtfs: &Route, osm: &BusRoute) -> bool {
+    if !gtfs.route_short_name.is_empty() {
+        osm.short_name
+            .split_whitespace()
+            .any(|word| word.eq_ignore_ascii_case(&gtfs.route_short_name))
+    } else if !gtfs.route_long_name.is_empty() {
+        osm.full_name
+            .to_lowercase()
+            .contains(&gtfs.route_long_name.to_lowercase())
+    } else {
+        false
+    }
+}
+
+// Skips (with a warning) a missing file or any rows that can't be parsed
+fn read<T: serde::de::DeserializeOwned>(dir: &str, file: &str, timer: &mut Timer) -> Vec<T> {
+    let path = format!("{}/{}", dir, file);
+    let mut results = Vec::new();
+    let f = match File::open(&path) {
+        Ok(f) => f,
+        Err(err) => {
+            timer.warn(format!("Can't read {}: {}", path, err));
+            return results;
+        }
+    };
+    for (idx, rec) in csv::Reader::from_reader(f).deserialize().enumerate() {
+        match rec {
+            Ok(rec) => {
+                results.push(rec);
+            }
+            Err(err) => {
+                // Row 1 is the header
+                timer.warn(format!("Skipping row {} of {}: {}", idx + 2, path, err));
+            }
+        }
+    }
+    results
+}
+
+#[derive(Debug, Deserialize)]
+struct Stop {
+    stop_id: String,
+    stop_lat: f64,
+    stop_lon: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Route {
+    route_id: String,
+    #[serde(default)]
+    route_short_name: String,
+    #[serde(default)]
+    route_long_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Trip {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StopTime {
+    trip_id: String,
+    departure_time: String,
+    stop_id: String,
+    stop_sequence: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geom::Duration;
+    use map_model::PathConstraints;
+
+    #[test]
+    fn test_read_feed() {
+        let dir = std::env::temp_dir().join("abst_test_gtfs");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("routes.txt"),
+            "route_id,route_short_name\n100,44\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("stop_times.txt"),
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             t1,07:59:00,08:00:00,s1,1\n\
+             t1,08:05:00,08:05:30,s2,two\n\
+             t1,25:09:00,25:10:00,s3,3\n",
+        )
+        .unwrap();
+        let dir = dir.display().to_string();
+        let mut timer = Timer::throwaway();
+
+        // Missing columns with a default are filled in
+        let routes: Vec<Route> = read(&dir, "routes.txt", &mut timer);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].route_short_name, "44");
+        assert_eq!(routes[0].route_long_name, "");
+
+        // Bad rows are skipped, extra columns ignored
+        let stop_times: Vec<StopTime> = read(&dir, "stop_times.txt", &mut timer);
+        assert_eq!(
+            stop_times
+                .iter()
+                .map(|st| (st.stop_id.as_str(), st.stop_sequence))
+                .collect::<Vec<_>>(),
+            vec![("s1", 1), ("s3", 3)]
+        );
+        // GTFS times can run past midnight
+        assert_eq!(
+            Time::parse(&stop_times[1].departure_time).unwrap(),
+            Time::START_OF_DAY + Duration::hours(25) + Duration::minutes(10)
+        );
+
+        // A missing file is just empty
+        assert!(read::<Trip>(&dir, "trips.txt", &mut timer).is_empty());
+    }
+
+    #[test]
+    fn test_match_stop() {
+        let pt = Pt2D::new(100.0, 100.0);
+        assert_eq!(
+            match_stop(
+                pt,
+                vec![
+                    ("far", Pt2D::new(130.0, 100.0)),
+                    ("close", Pt2D::new(100.0, 110.0)),
+                ]
+                .into_iter()
+            ),
+            Some("close")
+        );
+        assert_eq!(
+            match_stop(pt, vec![("too far", Pt2D::new(100.0, 160.0))].into_iter()),
+            None
+        );
+        assert_eq!(match_stop::<&str>(pt, Vec::new().into_iter()), None);
+    }
+
+    #[test]
+    fn test_departure_for_route() {
+        let t = |mins: usize| Time::START_OF_DAY + Duration::minutes(mins);
+        let schedule = vec![(1, "a", t(0)), (2, "b", t(5)), (3, "c", t(10))];
+
+        assert_eq!(departure_for_route(&schedule, &["b", "c", "d"]), Some(t(5)));
+        assert_eq!(departure_for_route(&schedule, &["a"]), Some(t(0)));
+        // Wrong direction
+        assert_eq!(departure_for_route(&schedule, &["c", "b"]), None);
+        // Doesn't start on the route
+        assert_eq!(departure_for_route(&schedule, &["d", "a"]), None);
+        assert_eq!(departure_for_route(&schedule, &[]), None);
+    }
+
+    #[test]
+    fn test_same_route() {
+        let osm = BusRoute {
+            id: BusRouteID(0),
+            full_name: "Route 44: Ballard => UW".to_string(),
+            short_name: "Route 44".to_string(),
+            stops: Vec::new(),
+            start_border: None,
+            end_border: None,
+            route_type: PathConstraints::Bus,
+            spawn_times: Vec::new(),
+        };
+        let gtfs = |short: &str, long: &str| Route {
+            route_id: "100".to_string(),
+            route_short_name: short.to_string(),
+            route_long_name: long.to_string(),
+        };
+
+        assert!(same_route(&gtfs("44", ""), &osm));
+        assert!(!same_route(&gtfs("4", ""), &osm));
+        // The short name wins over the long name
+        assert!(!same_route(&gtfs("45", "Ballard"), &osm));
+        assert!(same_route(&gtfs("", "ballard => uw"), &osm));
+        assert!(!same_route(&gtfs("", ""), &osm));
+    }
+}
@@ -1,10 +1,14 @@
 mod berlin;
+mod gtfs;
 mod krakow;
 mod seattle;
 #[cfg(feature = "scenarios")]
 mod soundcast;
 mod utils;
 
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
 // TODO Might be cleaner to express as a dependency graph?
 
 struct Job {
@@ -13,6 +17,8 @@ struct Job {
     raw_to_map: bool,
     scenario: bool,
     scenario_everyone: bool,
+    activity_model: bool,
+    gtfs: Option<String>,
 
     skip_ch: bool,
 
@@ -35,6 +41,11 @@ fn main() {
         scenario: args.enabled("--scenario"),
         // Produce a variation of the weekday scenario including off-map trips.
         scenario_everyone: args.enabled("--scenario_everyone"),
+        // Synthesize a weekday scenario from building types and amenities. Unlike --scenario, this
+        // works in any city.
+        activity_model: args.enabled("--activity_model"),
+        // Read a GTFS feed from this directory and schedule bus routes from its timetables.
+        gtfs: args.optional("--gtfs"),
         // Skip the most expensive step of --map, building contraction hierarchies. The resulting
         // map won't be usable for simulation; as soon as you try to pathfind, it'll crash.
         skip_ch: args.enabled("--skip_ch"),
@@ -53,11 +64,13 @@ fn main() {
         && !job.raw_to_map
         && !job.scenario
         && !job.scenario_everyone
+        && !job.activity_model
+        && job.gtfs.is_none()
         && job.oneshot.is_none()
     {
         println!(
             "Nothing to do! Pass some combination of --raw, --map, --scenario, \
-             --scenario_everyone or --oneshot"
+             --scenario_everyone, --activity_model, --gtfs or --oneshot"
         );
         std::process::exit(1);
     }
@@ -111,12 +124,21 @@ fn main() {
 
         let mut maybe_map = if job.raw_to_map {
             Some(utils::raw_to_map(&name, !job.skip_ch, &mut timer))
-        } else if job.scenario || job.scenario_everyone {
+        } else if job.scenario || job.scenario_everyone || job.activity_model || job.gtfs.is_some()
+        {
             Some(map_model::Map::new(abstutil::path_map(&name), &mut timer))
         } else {
             None
         };
 
+        if let Some(ref dir) = job.gtfs {
+            timer.start(format!("GTFS timetables for {}", name));
+            let map = maybe_map.as_mut().unwrap();
+            gtfs::import(map, dir, &mut timer);
+            map.save();
+            timer.stop(format!("GTFS timetables for {}", name));
+        }
+
         #[cfg(feature = "scenarios")]
         if job.scenario {
             timer.start(format!("scenario for {}", name));
@@ -148,6 +170,18 @@ fn main() {
             .save();
             timer.stop(format!("scenario_everyone for {}", name));
         }
+
+        if job.activity_model {
+            timer.start(format!("activity model for {}", name));
+            let mut rng = XorShiftRng::from_seed([42; 16]);
+            sim::ScenarioGenerator::activity_model(
+                maybe_map.as_ref().unwrap(),
+                &mut rng,
+                &mut timer,
+            )
+            .save();
+            timer.stop(format!("activity model for {}", name));
+        }
     }
 }
 
@@ -215,12 +215,14 @@ fn clip_trips(map: &Map, popdat: &PopDat, huge_map: &Map, timer: &mut Timer) ->
                     TripMode::Walk | TripMode::Transit => {
                         (&incoming_borders_walking, &outgoing_borders_walking)
                     }
-                    TripMode::Drive => (&incoming_borders_driving, &outgoing_borders_driving),
+                    TripMode::Drive | TripMode::RideHail => {
+                        (&incoming_borders_driving, &outgoing_borders_driving)
+                    }
                     TripMode::Bike => (&incoming_borders_biking, &outgoing_borders_biking),
                 },
                 match orig.mode {
                     TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                    TripMode::Drive => PathConstraints::Car,
+                    TripMode::Drive | TripMode::RideHail => PathConstraints::Car,
                     TripMode::Bike => PathConstraints::Bike,
                 },
                 maybe_huge_map.as_ref(),
@@ -306,6 +308,7 @@ pub fn make_weekday_scenario(
         map_name: map.get_name().to_string(),
         people,
         only_seed_buses: None,
+        bike_share_docks: Vec::new(),
     }
     .remove_weird_schedules(map)
 }
@@ -373,6 +376,7 @@ pub fn make_weekday_scenario_with_everyone(
         map_name: map.get_name().to_string(),
         people,
         only_seed_buses: None,
+        bike_share_docks: Vec::new(),
     }
     .remove_weird_schedules(map)
 }
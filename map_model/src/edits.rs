@@ -1,11 +1,11 @@
 use crate::raw::{OriginalIntersection, OriginalRoad};
 use crate::{
     connectivity, ControlStopSign, ControlTrafficSignal, IntersectionID, IntersectionType, LaneID,
-    LaneType, Map, PathConstraints, RoadID, TurnID, Zone,
+    LaneType, Map, PathConstraints, PhaseType, RoadID, TransitPriority, TurnID, Zone,
 };
 use abstutil::{deserialize_btreemap, retain_btreemap, retain_btreeset, serialize_btreemap, Timer};
 use enumset::EnumSet;
-use geom::{Distance, Speed};
+use geom::{Distance, Duration, Speed};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -31,8 +31,17 @@ pub struct MapEdits {
 pub enum EditIntersection {
     StopSign(ControlStopSign),
     // Don't keep ControlTrafficSignal here, because it contains turn groups that should be
-    // generated after all lane edits are applied.
-    TrafficSignal(seattle_traffic_signals::TrafficSignal),
+    // generated after all lane edits are applied. The offset, transit priority, actuated timing,
+    // and start delays aren't part of the seattle_traffic_signals format, so they're kept
+    // separately.
+    TrafficSignal {
+        signal: seattle_traffic_signals::TrafficSignal,
+        offset: Duration,
+        transit_priority: Option<TransitPriority>,
+        // Per phase
+        phase_types: Vec<PhaseType>,
+        start_delays: Vec<Vec<(seattle_traffic_signals::Turn, Duration)>>,
+    },
     Closed,
 }
 
@@ -244,7 +253,20 @@ enum PermanentEditIntersection {
         )]
         must_stop: BTreeMap<OriginalRoad, bool>,
     },
-    TrafficSignal(seattle_traffic_signals::TrafficSignal),
+    TrafficSignal {
+        // Flattened, so edits saved before offsets were recorded still load
+        #[serde(flatten)]
+        signal: seattle_traffic_signals::TrafficSignal,
+        #[serde(default)]
+        offset: Duration,
+        #[serde(default)]
+        transit_priority: Option<TransitPriority>,
+        // Empty for edits saved before these were recorded
+        #[serde(default)]
+        phase_types: Vec<PhaseType>,
+        #[serde(default)]
+        start_delays: Vec<Vec<(seattle_traffic_signals::Turn, Duration)>>,
+    },
     Closed,
 }
 
@@ -413,6 +435,16 @@ impl PermanentMapEdits {
 }
 
 impl EditIntersection {
+    pub fn traffic_signal(ts: &ControlTrafficSignal, map: &Map) -> EditIntersection {
+        EditIntersection::TrafficSignal {
+            signal: ts.export(map),
+            offset: ts.offset,
+            transit_priority: ts.transit_priority.clone(),
+            phase_types: ts.phases.iter().map(|p| p.phase_type.clone()).collect(),
+            start_delays: ts.export_start_delays(map),
+        }
+    }
+
     fn to_permanent(&self, map: &Map) -> PermanentEditIntersection {
         match self {
             EditIntersection::StopSign(ref ss) => PermanentEditIntersection::StopSign {
@@ -422,9 +454,19 @@ impl EditIntersection {
                     .map(|(r, val)| (map.get_r(*r).orig_id, val.must_stop))
                     .collect(),
             },
-            EditIntersection::TrafficSignal(ref raw_ts) => {
-                PermanentEditIntersection::TrafficSignal(raw_ts.clone())
-            }
+            EditIntersection::TrafficSignal {
+                signal,
+                offset,
+                transit_priority,
+                phase_types,
+                start_delays,
+            } => PermanentEditIntersection::TrafficSignal {
+                signal: signal.clone(),
+                offset: *offset,
+                transit_priority: transit_priority.clone(),
+                phase_types: phase_types.clone(),
+                start_delays: start_delays.clone(),
+            },
             EditIntersection::Closed => PermanentEditIntersection::Closed,
         }
     }
@@ -454,9 +496,19 @@ impl PermanentEditIntersection {
 
                 Some(EditIntersection::StopSign(ss))
             }
-            PermanentEditIntersection::TrafficSignal(ts) => {
-                Some(EditIntersection::TrafficSignal(ts))
-            }
+            PermanentEditIntersection::TrafficSignal {
+                signal,
+                offset,
+                transit_priority,
+                phase_types,
+                start_delays,
+            } => Some(EditIntersection::TrafficSignal {
+                signal,
+                offset,
+                transit_priority,
+                phase_types,
+                start_delays,
+            }),
             PermanentEditIntersection::Closed => Some(EditIntersection::Closed),
         }
     }
@@ -501,7 +553,7 @@ impl EditCmd {
             EditCmd::ChangeSpeedLimit { id, new, .. } => format!("limit {} for {}", new, id),
             EditCmd::ChangeIntersection { i, new, .. } => match new {
                 EditIntersection::StopSign(_) => format!("stop sign #{}", i.0),
-                EditIntersection::TrafficSignal(_) => format!("traffic signal #{}", i.0),
+                EditIntersection::TrafficSignal { .. } => format!("traffic signal #{}", i.0),
                 EditIntersection::Closed => format!("close {}", i),
             },
             // TODO "allow/ban X on Y"
@@ -597,15 +649,27 @@ impl EditCmd {
                         map.intersections[i.0].intersection_type = IntersectionType::StopSign;
                         map.stop_signs.insert(*i, ss.clone());
                     }
-                    EditIntersection::TrafficSignal(ref raw_ts) => {
+                    EditIntersection::TrafficSignal {
+                        ref signal,
+                        offset,
+                        ref transit_priority,
+                        ref phase_types,
+                        ref start_delays,
+                    } => {
                         map.intersections[i.0].intersection_type = IntersectionType::TrafficSignal;
                         if old == &EditIntersection::Closed {
                             recalculate_turns(*i, map, effects, timer);
                         }
-                        map.traffic_signals.insert(
-                            *i,
-                            ControlTrafficSignal::import(raw_ts.clone(), *i, map).unwrap(),
-                        );
+                        let mut ts = ControlTrafficSignal::import(signal.clone(), *i, map).unwrap();
+                        ts.offset = *offset;
+                        ts.transit_priority = transit_priority.clone();
+                        if phase_types.len() == ts.phases.len() {
+                            for (phase, phase_type) in ts.phases.iter_mut().zip(phase_types) {
+                                phase.phase_type = phase_type.clone();
+                            }
+                        }
+                        ts.import_start_delays(start_delays.clone(), map);
+                        map.traffic_signals.insert(*i, ts);
                     }
                     EditIntersection::Closed => {
                         map.intersections[i.0].intersection_type = IntersectionType::Construction;
@@ -758,7 +822,7 @@ impl Map {
         match self.get_i(i).intersection_type {
             IntersectionType::StopSign => EditIntersection::StopSign(self.get_stop_sign(i).clone()),
             IntersectionType::TrafficSignal => {
-                EditIntersection::TrafficSignal(self.get_traffic_signal(i).export(self))
+                EditIntersection::traffic_signal(self.get_traffic_signal(i), self)
             }
             IntersectionType::Construction => EditIntersection::Closed,
             IntersectionType::Border => unreachable!(),
@@ -20,6 +20,7 @@ pub use crate::objects::building::{
     Building, BuildingID, BuildingType, FrontPath, OffstreetParking,
 };
 pub use crate::objects::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
+pub use crate::objects::green_wave::{GreenWave, WaveDirection};
 pub use crate::objects::intersection::{Intersection, IntersectionID, IntersectionType};
 pub use crate::objects::lane::{
     Lane, LaneID, LaneType, PARKING_LOT_SPOT_LENGTH, PARKING_SPOT_LENGTH,
@@ -27,12 +28,14 @@ pub use crate::objects::lane::{
 pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
 pub use crate::objects::road::{DirectedRoadID, Road, RoadID};
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::objects::traffic_signals::{ControlTrafficSignal, Phase, PhaseType};
+pub use crate::objects::traffic_signals::{
+    ControlTrafficSignal, Phase, PhaseType, TransitPriority,
+};
 pub use crate::objects::turn::{Turn, TurnGroup, TurnGroupID, TurnID, TurnPriority, TurnType};
 pub use crate::objects::zone::Zone;
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn, UberTurnGroup};
 use crate::pathfind::Pathfinder;
-pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
+pub use crate::pathfind::{ObservedTravelTimes, Path, PathConstraints, PathRequest, PathStep};
 pub use crate::traversable::{Position, Traversable};
 use abstutil::Cloneable;
 use abstutil::{deserialize_btreemap, serialize_btreemap};
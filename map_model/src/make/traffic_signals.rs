@@ -59,9 +59,23 @@ pub fn get_possible_policies(
         "all walk, then free-for-all yield".to_string(),
         all_walk_all_yield(map, id),
     ));
+
+    // Pedestrian-focused variations of the preferred policy
+    let (name, base) = results[0].clone();
+    let mut scramble = base.clone();
+    if scramble.convert_to_ped_scramble() {
+        results.push((format!("{}, with a pedestrian scramble", name), scramble));
+    }
+    let mut lpi = base;
+    if lpi.set_leading_pedestrian_intervals(LEADING_PEDESTRIAN_INTERVAL) {
+        results.push((format!("{}, with leading pedestrian intervals", name), lpi));
+    }
     results
 }
 
+// A common default in practice
+const LEADING_PEDESTRIAN_INTERVAL: Duration = Duration::const_seconds(5.0);
+
 fn greedy_assignment(map: &Map, intersection: IntersectionID) -> ControlTrafficSignal {
     let turn_groups = TurnGroup::for_i(intersection, map);
 
@@ -97,6 +111,7 @@ fn greedy_assignment(map: &Map, intersection: IntersectionID) -> ControlTrafficS
         id: intersection,
         phases,
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups,
     };
     // This must succeed
@@ -119,6 +134,7 @@ fn degenerate(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
         id: i,
         phases,
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups: TurnGroup::for_i(i, map),
     };
     ts.validate().ok()
@@ -164,6 +180,7 @@ fn three_way(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
         id: i,
         phases,
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups,
     };
     ts.validate().ok()
@@ -205,6 +222,7 @@ fn four_way_four_phase(map: &Map, i: IntersectionID) -> Option<ControlTrafficSig
         id: i,
         phases,
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups: TurnGroup::for_i(i, map),
     };
     ts.validate().ok()
@@ -245,6 +263,7 @@ fn four_way_two_phase(map: &Map, i: IntersectionID) -> Option<ControlTrafficSign
         id: i,
         phases,
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups: TurnGroup::for_i(i, map),
     };
     ts.validate().ok()
@@ -296,6 +315,7 @@ fn four_oneways(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
         id: i,
         phases,
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups: TurnGroup::for_i(i, map),
     };
     ts.validate().ok()
@@ -322,6 +342,7 @@ fn all_walk_all_yield(map: &Map, i: IntersectionID) -> ControlTrafficSignal {
         id: i,
         phases: vec![all_walk, all_yield],
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups,
     };
     // This must succeed
@@ -359,6 +380,7 @@ fn phase_per_road(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal>
         id: i,
         phases,
         offset: Duration::ZERO,
+        transit_priority: None,
         turn_groups,
     };
     ts.validate().ok()
@@ -132,6 +132,7 @@ fn make_route(
         route_type,
         start_border,
         end_border,
+        spawn_times: BusRoute::default_spawn_times(),
     };
 
     // Make sure the route is connected
@@ -2,11 +2,11 @@ use crate::raw::{DrivingSide, RawMap};
 use crate::{
     Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop, BusStopID, ControlStopSign,
     ControlTrafficSignal, Intersection, IntersectionID, Lane, LaneID, LaneType, Map, MapEdits,
-    ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, Position, Road, RoadID, Turn,
-    TurnGroupID, TurnID, TurnType,
+    ObservedTravelTimes, ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, Position,
+    Road, RoadID, Turn, TurnGroupID, TurnID, TurnType,
 };
-use abstutil::Timer;
-use geom::{Angle, Bounds, Distance, GPSBounds, Line, PolyLine, Polygon, Pt2D};
+use abstutil::{Timer, Versioned};
+use geom::{Angle, Bounds, Distance, GPSBounds, Line, PolyLine, Polygon, Pt2D, Time};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
@@ -18,10 +18,20 @@ pub struct MapConfig {
     pub bikes_can_use_bus_lanes: bool,
 }
 
+// Bump this whenever Map or anything inside it changes how it's serialized.
+impl Versioned for Map {
+    const FORMAT: &'static str = "map";
+    // 2: Bus route timetables
+    // 3: Transit signal priority
+    // 4: Leading pedestrian intervals
+    // 5: Intersections with uber-turns cached in the pathfinder
+    const VERSION: u32 = 5;
+}
+
 impl Map {
     pub fn new(path: String, timer: &mut Timer) -> Map {
         if path.starts_with(&abstutil::path_all_maps()) {
-            match abstutil::maybe_read_binary(path.clone(), timer) {
+            match abstutil::maybe_read_versioned_binary(path.clone(), timer) {
                 Ok(map) => {
                     let map: Map = map;
 
@@ -99,6 +109,16 @@ impl Map {
                 "{} is missing. You may need to do: cargo run --bin updater",
                 path
             );
+        } else if err
+            .get_ref()
+            .map(|e| e.is::<abstutil::NewerVersionError>())
+            .unwrap_or(false)
+        {
+            println!(
+                "{} was created by a newer version of the code. You need to update your build \
+                 (git pull).",
+                path
+            );
         } else {
             println!(
                 "{} is out-of-date. You may need to update your build (git pull) or download new \
@@ -446,7 +466,7 @@ impl Map {
         assert_eq!(self.edits.edits_name, "untitled edits");
         assert!(self.edits.commands.is_empty());
         assert!(!self.pathfinder_dirty);
-        abstutil::write_binary(abstutil::path_map(&self.name), self);
+        abstutil::write_versioned_binary(abstutil::path_map(&self.name), self);
     }
 
     pub fn find_closest_lane(
@@ -564,6 +584,39 @@ impl Map {
         self.pathfinder.as_ref().unwrap().pathfind(req, self)
     }
 
+    // Like pathfind, but driving paths account for congestion observed around this time of day, if
+    // set_observed_travel_times has been called.
+    pub fn pathfind_at(&self, req: PathRequest, time: Time) -> Option<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .as_ref()
+            .unwrap()
+            .pathfind_at(req, time, self)
+    }
+
+    // Weight driving paths by how long each lane took to cross in a previous simulation. This
+    // prepares one contraction hierarchy per time-of-day bin, so it's slow.
+    pub fn set_observed_travel_times(&mut self, observed: ObservedTravelTimes, timer: &mut Timer) {
+        let mut pathfinder = self.pathfinder.take().unwrap();
+        pathfinder.set_observed_travel_times(self, observed, timer);
+        self.pathfinder = Some(pathfinder);
+    }
+
+    // Much slower than pathfind, but the caller can make some lanes more expensive to use, like to
+    // avoid live congestion. Only for vehicles.
+    pub fn pathfind_with_penalties<F: Fn(LaneID) -> usize>(
+        &self,
+        req: PathRequest,
+        penalty: F,
+    ) -> Option<Path> {
+        assert_ne!(req.constraints, PathConstraints::Pedestrian);
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .as_ref()
+            .unwrap()
+            .pathfind_with_penalties(req, self, penalty)
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -659,6 +712,9 @@ impl Map {
             }
         }
     }
+    pub fn set_bus_route_schedule(&mut self, route: BusRouteID, spawn_times: Vec<Time>) {
+        self.bus_routes[route.0].spawn_times = spawn_times;
+    }
     pub fn hack_override_offstreet_spots_individ(&mut self, b: BuildingID, spots: usize) {
         let b = &mut self.buildings[b.0];
         if let Some(ref mut p) = b.parking {
@@ -1,5 +1,6 @@
 use crate::{LaneID, Map, PathConstraints, PathRequest, Position};
 use abstutil::{deserialize_usize, serialize_usize};
+use geom::{Duration, Time};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -52,9 +53,36 @@ pub struct BusRoute {
     pub start_border: Option<LaneID>,
     pub end_border: Option<LaneID>,
     pub route_type: PathConstraints,
+    // Sorted times when a vehicle starts the route. Hourly by default; a GTFS timetable can
+    // override this.
+    pub spawn_times: Vec<Time>,
 }
 
 impl BusRoute {
+    pub fn default_spawn_times() -> Vec<Time> {
+        (0..24)
+            .map(|hour| Time::START_OF_DAY + Duration::hours(hour))
+            .collect()
+    }
+
+    // The first departure after some time, or at that time if inclusive. The timetable covers one
+    // day and repeats every day after; times past midnight (like 25:10:00 in GTFS) wrap around.
+    pub fn next_departure(&self, time: Time, inclusive: bool) -> Option<Time> {
+        let day = Duration::hours(24);
+        let today = Time::START_OF_DAY + day * ((time - Time::START_OF_DAY) / day).floor();
+        let mut best: Option<Time> = None;
+        // Today's departures, then tomorrow's
+        for days in 0..2 {
+            for t in &self.spawn_times {
+                let t = today + day * (days as f64) + (*t - Time::START_OF_DAY) % day;
+                if (t > time || (inclusive && t == time)) && best.map(|b| t < b).unwrap_or(true) {
+                    best = Some(t);
+                }
+            }
+        }
+        best
+    }
+
     pub fn all_steps(&self, map: &Map) -> Vec<PathRequest> {
         let mut steps = Vec::new();
         if let Some(start) = self.start_border {
@@ -0,0 +1,274 @@
+use crate::{ControlTrafficSignal, DirectedRoadID, IntersectionID, Map, PhaseType, RoadID};
+use geom::{Distance, Duration, Speed};
+
+// A sequence of traffic signals along a route, timed so that vehicles travelling at the
+// progression speed keep arriving on green.
+#[derive(Clone, Debug)]
+pub struct GreenWave {
+    pub intersections: Vec<IntersectionID>,
+    // roads[i] connects intersections[i] and intersections[i + 1]
+    pub roads: Vec<RoadID>,
+    pub speed: Speed,
+    pub direction: WaveDirection,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WaveDirection {
+    // In the order of the intersections
+    Forwards,
+    Backwards,
+    // A compromise between the two
+    Both,
+}
+
+impl GreenWave {
+    pub fn new(
+        map: &Map,
+        intersections: Vec<IntersectionID>,
+        speed: Speed,
+        direction: WaveDirection,
+    ) -> Result<GreenWave, String> {
+        if intersections.len() < 2 {
+            return Err("A corridor needs at least two intersections".to_string());
+        }
+        if speed <= Speed::ZERO {
+            return Err("The progression speed has to be positive".to_string());
+        }
+        for i in &intersections {
+            if map.maybe_get_traffic_signal(*i).is_none() {
+                return Err(format!("{} isn't a traffic signal", i));
+            }
+        }
+        let mut roads = Vec::new();
+        for pair in intersections.windows(2) {
+            let r = map
+                .get_i(pair[0])
+                .roads
+                .iter()
+                .find(|r| {
+                    let road = map.get_r(**r);
+                    (road.src_i == pair[0] && road.dst_i == pair[1])
+                        || (road.src_i == pair[1] && road.dst_i == pair[0])
+                })
+                .ok_or_else(|| format!("No road connects {} and {}", pair[0], pair[1]))?;
+            roads.push(*r);
+        }
+        Ok(GreenWave {
+            intersections,
+            roads,
+            speed,
+            direction,
+        })
+    }
+
+    // How far along the corridor each intersection is
+    pub fn distances(&self, map: &Map) -> Vec<Distance> {
+        let mut dists = vec![Distance::ZERO];
+        for r in &self.roads {
+            let last = *dists.last().unwrap();
+            dists.push(last + map.get_r(*r).center_pts.length());
+        }
+        dists
+    }
+
+    // Every signal along the corridor has to share the longest cycle to stay in step.
+    pub fn cycle_length(&self, map: &Map) -> Duration {
+        self.intersections
+            .iter()
+            .map(|i| map.get_traffic_signal(*i).cycle_length())
+            .max()
+            .unwrap()
+    }
+
+    // Which phase lets vehicles continue along the corridor in this direction? Prefers a phase
+    // protecting the whole through movement, then one protecting part of it.
+    pub fn corridor_phase(&self, idx: usize, forwards: bool, map: &Map) -> Option<usize> {
+        let i = self.intersections[idx];
+        let before = idx.checked_sub(1).map(|x| self.roads[x]);
+        let after = self.roads.get(idx).cloned();
+        let (from, to) = if forwards {
+            (before, after)
+        } else {
+            (after, before)
+        };
+        let from = from.map(|r| directed(r, i, true, map));
+        let to = to.map(|r| directed(r, i, false, map));
+
+        let mut best: Option<(usize, usize)> = None;
+        for (phase_idx, phase) in map.get_traffic_signal(i).phases.iter().enumerate() {
+            let score = phase
+                .protected_groups
+                .iter()
+                .filter(|g| !g.crosswalk)
+                .map(|g| (Some(g.from) == from) as usize + (Some(g.to) == to) as usize)
+                .max()
+                .unwrap_or(0);
+            if score > 0 && best.map(|(s, _)| score > s).unwrap_or(true) {
+                best = Some((score, phase_idx));
+            }
+        }
+        best.map(|(_, phase_idx)| phase_idx)
+    }
+
+    // Stretches every signal to the common cycle length, then offsets each one so its corridor
+    // phase turns green just as vehicles from the first signal arrive. Actuated phases vary in
+    // length, so they'll drift out of the wave.
+    pub fn coordinate(&self, map: &Map) -> Result<Vec<ControlTrafficSignal>, String> {
+        let cycle = self.cycle_length(map);
+        let dists = self.distances(map);
+        let total_dist = *dists.last().unwrap();
+
+        let mut signals = Vec::new();
+        for (idx, i) in self.intersections.iter().enumerate() {
+            let mut signal = map.get_traffic_signal(*i).clone();
+            stretch(&mut signal, cycle);
+
+            let mut offsets = Vec::new();
+            if self.direction != WaveDirection::Backwards {
+                offsets.push(self.offset(&signal, idx, true, dists[idx], cycle, map)?);
+            }
+            if self.direction != WaveDirection::Forwards {
+                offsets.push(self.offset(
+                    &signal,
+                    idx,
+                    false,
+                    total_dist - dists[idx],
+                    cycle,
+                    map,
+                )?);
+            }
+            signal.offset = if offsets.len() == 1 {
+                offsets[0]
+            } else {
+                // Halfway between the two, going the short way around the cycle
+                let mut diff = (offsets[1] - offsets[0] + cycle) % cycle;
+                if diff > cycle / 2.0 {
+                    diff -= cycle;
+                }
+                (offsets[0] + diff / 2.0 + cycle) % cycle
+            };
+            signal.offset = Duration::seconds(signal.offset.inner_seconds().round()) % cycle;
+            signals.push(signal);
+        }
+        Ok(signals)
+    }
+
+    fn offset(
+        &self,
+        signal: &ControlTrafficSignal,
+        idx: usize,
+        forwards: bool,
+        dist: Distance,
+        cycle: Duration,
+        map: &Map,
+    ) -> Result<Duration, String> {
+        let phase = self
+            .corridor_phase(idx, forwards, map)
+            .ok_or_else(|| format!("{} has no phase for traffic along the corridor", signal.id))?;
+        let arrival = dist / self.speed;
+        let phase_start: Duration = signal.phases[0..phase]
+            .iter()
+            .map(|p| p.phase_type.simple_duration())
+            .sum();
+        Ok(((arrival - phase_start) % cycle + cycle) % cycle)
+    }
+}
+
+// The road leading into or away from an intersection
+fn directed(r: RoadID, i: IntersectionID, into: bool, map: &Map) -> DirectedRoadID {
+    if (map.get_r(r).dst_i == i) == into {
+        r.forwards()
+    } else {
+        r.backwards()
+    }
+}
+
+// Scale every phase to fit the new cycle length, keeping durations in whole seconds
+fn stretch(signal: &mut ControlTrafficSignal, cycle: Duration) {
+    let ratio = cycle / signal.cycle_length();
+    let mut remaining = cycle;
+    let num_phases = signal.phases.len();
+    for (idx, phase) in signal.phases.iter_mut().enumerate() {
+        let old = phase.phase_type.simple_duration();
+        let new = if idx == num_phases - 1 {
+            remaining
+        } else {
+            Duration::seconds((ratio * old.inner_seconds()).round())
+        };
+        remaining -= new;
+        phase.phase_type = match phase.phase_type {
+            PhaseType::Fixed(_) => PhaseType::Fixed(new),
+            PhaseType::Adaptive(_) => PhaseType::Adaptive(new),
+            PhaseType::Actuated {
+                min_green, passage, ..
+            } => PhaseType::Actuated {
+                min_green: Duration::seconds((min_green * (new / old)).inner_seconds().round()),
+                max_green: new,
+                passage,
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abstutil::Timer;
+
+    #[test]
+    fn test_coordinate_offsets() {
+        let map = Map::new(
+            abstutil::path_synthetic_map("signal_double"),
+            &mut Timer::throwaway(),
+        );
+        let signals: Vec<IntersectionID> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_traffic_signal())
+            .map(|i| i.id)
+            .collect();
+        assert_eq!(signals.len(), 2);
+        let speed = Speed::miles_per_hour(25.0);
+
+        for forwards in vec![true, false] {
+            let wave = GreenWave::new(
+                &map,
+                signals.clone(),
+                speed,
+                if forwards {
+                    WaveDirection::Forwards
+                } else {
+                    WaveDirection::Backwards
+                },
+            )
+            .unwrap();
+            let cycle = wave.cycle_length(&map);
+            let dists = wave.distances(&map);
+            let total_dist = *dists.last().unwrap();
+
+            for (idx, signal) in wave.coordinate(&map).unwrap().into_iter().enumerate() {
+                assert_eq!(signal.cycle_length(), cycle);
+                assert!(signal.offset >= Duration::ZERO && signal.offset < cycle);
+                assert_eq!(signal.offset.inner_seconds().fract(), 0.0);
+
+                // The corridor phase turns green just as the wave arrives, give or take rounding
+                let dist = if forwards {
+                    dists[idx]
+                } else {
+                    total_dist - dists[idx]
+                };
+                let arrival = dist / speed;
+                let phase = wave.corridor_phase(idx, forwards, &map).unwrap();
+                let phase_start: Duration = signal.phases[0..phase]
+                    .iter()
+                    .map(|p| p.phase_type.simple_duration())
+                    .sum();
+                let mut error = (signal.offset + phase_start - arrival) % cycle;
+                if error < Duration::ZERO {
+                    error += cycle;
+                }
+                assert!(error <= Duration::seconds(1.0) || cycle - error <= Duration::seconds(1.0));
+            }
+        }
+    }
+}
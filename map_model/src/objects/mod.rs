@@ -1,6 +1,7 @@
 pub mod area;
 pub mod building;
 pub mod bus_stop;
+pub mod green_wave;
 pub mod intersection;
 pub mod lane;
 pub mod parking_lot;
@@ -12,6 +12,8 @@ pub struct ControlTrafficSignal {
     pub id: IntersectionID,
     pub phases: Vec<Phase>,
     pub offset: Duration,
+    // Opt-in. Adjust phases for approaching buses and trains.
+    pub transit_priority: Option<TransitPriority>,
 
     #[serde(
         serialize_with = "serialize_btreemap",
@@ -25,6 +27,13 @@ pub struct Phase {
     pub protected_groups: BTreeSet<TurnGroupID>,
     pub yield_groups: BTreeSet<TurnGroupID>,
     pub phase_type: PhaseType,
+    // Groups that don't start until this long into the phase. A leading pedestrian interval delays
+    // the vehicle turns conflicting with a crosswalk, so pedestrians get a head start.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub start_delays: BTreeMap<TurnGroupID, Duration>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -34,6 +43,30 @@ pub enum PhaseType {
     // repeat the phase entirely.
     // TODO This is a silly policy, but a start towards variable timers.
     Adaptive(Duration),
+    // Stay green for at least min_green. After that, extend the phase by passage each time a
+    // vehicle is detected approaching a protected movement, up to max_green in total.
+    Actuated {
+        min_green: Duration,
+        max_green: Duration,
+        passage: Duration,
+    },
+}
+
+// Transit signal priority. When a bus or train approaches, hold its green phase open or end the
+// phase before it early, within these bounds. Only one adjustment is made per phase.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransitPriority {
+    pub max_extension: Duration,
+    pub max_early_end: Duration,
+}
+
+impl TransitPriority {
+    pub fn new() -> TransitPriority {
+        TransitPriority {
+            max_extension: Duration::seconds(10.0),
+            max_early_end: Duration::seconds(10.0),
+        }
+    }
 }
 
 impl PhaseType {
@@ -41,6 +74,8 @@ impl PhaseType {
     pub fn simple_duration(&self) -> Duration {
         match self {
             PhaseType::Fixed(d) | PhaseType::Adaptive(d) => *d,
+            // The longest the phase could last
+            PhaseType::Actuated { max_green, .. } => *max_green,
         }
     }
 }
@@ -61,6 +96,14 @@ impl ControlTrafficSignal {
     ) -> Vec<(String, ControlTrafficSignal)> {
         get_possible_policies(map, id, timer)
     }
+    // The longest each cycle through all of the phases could take
+    pub fn cycle_length(&self) -> Duration {
+        self.phases
+            .iter()
+            .map(|p| p.phase_type.simple_duration())
+            .sum()
+    }
+
     // TODO tmp
     pub fn brute_force(map: &Map, id: IntersectionID) {
         brute_force(map, id)
@@ -107,6 +150,14 @@ impl ControlTrafficSignal {
             for g in phase.yield_groups.iter().map(|g| &self.turn_groups[g]) {
                 assert!(g.turn_type != TurnType::Crosswalk);
             }
+
+            // Do the delayed groups belong to the phase? (If a delay outlasts a shortened phase,
+            // that group just doesn't get to go.)
+            for g in phase.start_delays.keys() {
+                if phase.get_priority_of_group(*g) == TurnPriority::Banned {
+                    return Err(format!("{:?} is delayed in a phase it isn't part of", g));
+                }
+            }
         }
 
         Ok(self)
@@ -156,6 +207,40 @@ impl ControlTrafficSignal {
         }
         self != &orig
     }
+
+    // In every phase, hold the vehicle turns that conflict with a protected crosswalk for lpi, so
+    // pedestrians can get into the crosswalk first. Zero removes the intervals. Returns true if
+    // this did anything.
+    pub fn set_leading_pedestrian_intervals(&mut self, lpi: Duration) -> bool {
+        let orig = self.clone();
+        let turn_groups = &self.turn_groups;
+        for phase in self.phases.iter_mut() {
+            phase.start_delays.clear();
+            // Don't delay anything past the end of the phase
+            if lpi == Duration::ZERO || lpi >= phase.phase_type.simple_duration() {
+                continue;
+            }
+            let crosswalks: Vec<&TurnGroup> = phase
+                .protected_groups
+                .iter()
+                .map(|g| &turn_groups[g])
+                .filter(|g| g.turn_type == TurnType::Crosswalk)
+                .collect();
+            for g in phase
+                .protected_groups
+                .iter()
+                .chain(phase.yield_groups.iter())
+            {
+                let group = &turn_groups[g];
+                if group.turn_type != TurnType::Crosswalk
+                    && crosswalks.iter().any(|c| c.conflicts_with(group))
+                {
+                    phase.start_delays.insert(*g, lpi);
+                }
+            }
+        }
+        self != &orig
+    }
 }
 
 impl Phase {
@@ -164,6 +249,7 @@ impl Phase {
             protected_groups: BTreeSet::new(),
             yield_groups: BTreeSet::new(),
             phase_type: PhaseType::Fixed(Duration::seconds(30.0)),
+            start_delays: BTreeMap::new(),
         }
     }
 
@@ -195,6 +281,18 @@ impl Phase {
         }
     }
 
+    // How long into the phase this turn has to wait to start, if at all
+    pub fn get_start_delay(&self, t: TurnID, parent: &ControlTrafficSignal) -> Option<Duration> {
+        if self.start_delays.is_empty() {
+            return None;
+        }
+        let g = parent
+            .turn_groups
+            .values()
+            .find(|g| g.members.contains(&t))?;
+        self.start_delays.get(&g.id).cloned()
+    }
+
     pub fn get_priority_of_group(&self, g: TurnGroupID) -> TurnPriority {
         if self.protected_groups.contains(&g) {
             TurnPriority::Protected
@@ -218,6 +316,9 @@ impl Phase {
         for id in ids {
             self.protected_groups.remove(&id);
             self.yield_groups.remove(&id);
+            if pri == TurnPriority::Banned {
+                self.start_delays.remove(&id);
+            }
             if pri == TurnPriority::Protected {
                 self.protected_groups.insert(id);
             } else if pri == TurnPriority::Yield {
@@ -252,12 +353,20 @@ impl ControlTrafficSignal {
                         PhaseType::Adaptive(d) => {
                             seattle_traffic_signals::PhaseType::Adaptive(d.inner_seconds() as usize)
                         }
+                        PhaseType::Actuated { max_green, .. } => {
+                            seattle_traffic_signals::PhaseType::Fixed(
+                                max_green.inner_seconds() as usize
+                            )
+                        }
                     },
                 })
                 .collect(),
         }
     }
 
+    // TODO Actuated phases and start delays aren't part of the seattle_traffic_signals format yet,
+    // so they're lost here. Actuated phases become fixed at their max_green. Map edits record the
+    // offset, transit priority, phase types, and start delays separately.
     pub fn import(
         raw: seattle_traffic_signals::TrafficSignal,
         id: IntersectionID,
@@ -289,6 +398,7 @@ impl ControlTrafficSignal {
                             PhaseType::Adaptive(Duration::seconds(d as f64))
                         }
                     },
+                    start_delays: BTreeMap::new(),
                 });
             } else {
                 return Err(format!(
@@ -301,10 +411,48 @@ impl ControlTrafficSignal {
             id,
             phases,
             offset: Duration::ZERO,
+            transit_priority: None,
             turn_groups: TurnGroup::for_i(id, map),
         }
         .validate()
     }
+
+    // Per phase. Map edits keep these, since the seattle_traffic_signals format doesn't.
+    pub fn export_start_delays(
+        &self,
+        map: &Map,
+    ) -> Vec<Vec<(seattle_traffic_signals::Turn, Duration)>> {
+        self.phases
+            .iter()
+            .map(|p| {
+                p.start_delays
+                    .iter()
+                    .map(|(g, delay)| (export_turn_group(g, map), *delay))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Delays for turn groups that no longer exist or aren't in the phase are skipped.
+    pub fn import_start_delays(
+        &mut self,
+        delays: Vec<Vec<(seattle_traffic_signals::Turn, Duration)>>,
+        map: &Map,
+    ) {
+        if delays.len() != self.phases.len() {
+            return;
+        }
+        for (phase, phase_delays) in self.phases.iter_mut().zip(delays) {
+            phase.start_delays.clear();
+            for (turn, delay) in phase_delays {
+                if let Some(g) = import_turn_group(turn, map) {
+                    if phase.get_priority_of_group(g) != TurnPriority::Banned {
+                        phase.start_delays.insert(g, delay);
+                    }
+                }
+            }
+        }
+    }
 }
 
 fn export_turn_group(id: &TurnGroupID, map: &Map) -> seattle_traffic_signals::Turn {
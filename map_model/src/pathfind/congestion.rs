@@ -0,0 +1,79 @@
+use crate::LaneID;
+use geom::{Duration, Time};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// How long vehicles actually took to cross each lane (including waiting to turn at the end),
+// averaged over fixed time-of-day bins. Usually measured from a previous simulation, then used to
+// weight pathfinding.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ObservedTravelTimes {
+    pub bin_size: Duration,
+    // Indexed by bin. None if nobody crossed the lane during that bin.
+    pub lanes: BTreeMap<LaneID, Vec<Option<Duration>>>,
+}
+
+impl ObservedTravelTimes {
+    pub fn new(bin_size: Duration) -> ObservedTravelTimes {
+        ObservedTravelTimes {
+            bin_size,
+            lanes: BTreeMap::new(),
+        }
+    }
+
+    pub fn num_bins(&self) -> usize {
+        self.lanes
+            .values()
+            .map(|bins| bins.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn bin(&self, time: Time) -> usize {
+        ((time - Time::START_OF_DAY) / self.bin_size).floor() as usize
+    }
+
+    pub fn get(&self, l: LaneID, bin: usize) -> Option<Duration> {
+        self.lanes
+            .get(&l)
+            .and_then(|bins| bins.get(bin).cloned().flatten())
+    }
+
+    pub fn set(&mut self, l: LaneID, bin: usize, dt: Duration) {
+        let bins = self.lanes.entry(l).or_insert_with(Vec::new);
+        if bins.len() <= bin {
+            bins.resize(bin + 1, None);
+        }
+        bins[bin] = Some(dt);
+    }
+
+    // Move each observation this fraction of the way towards a newer one. Using 1/n on the nth
+    // iteration is the method of successive averages, which keeps route choice from oscillating.
+    pub fn blend(&mut self, newer: &ObservedTravelTimes, weight: f64) {
+        assert_eq!(self.bin_size, newer.bin_size);
+        for (l, bins) in &newer.lanes {
+            for (bin, dt) in bins.iter().enumerate() {
+                if let Some(dt) = dt {
+                    let blended = match self.get(*l, bin) {
+                        Some(old) => old + weight * (*dt - old),
+                        None => *dt,
+                    };
+                    self.set(*l, bin, blended);
+                }
+            }
+        }
+    }
+
+    // The largest absolute change in any lane's travel time between the two
+    pub fn max_difference(&self, other: &ObservedTravelTimes) -> Duration {
+        let mut max = Duration::ZERO;
+        for (l, bins) in &self.lanes {
+            for (bin, dt) in bins.iter().enumerate() {
+                if let (Some(dt1), Some(dt2)) = (dt, other.get(*l, bin)) {
+                    max = max.max(if *dt1 > dt2 { *dt1 - dt2 } else { dt2 - *dt1 });
+                }
+            }
+        }
+        max
+    }
+}
@@ -1,10 +1,16 @@
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
-use crate::{Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID};
-use abstutil::MultiMap;
+use crate::{
+    IntersectionID, Lane, LaneID, Map, ObservedTravelTimes, Path, PathConstraints, PathRequest,
+    PathStep, Turn, TurnID,
+};
+use abstutil::{MultiMap, Timer};
 use fast_paths::{deserialize_32, serialize_32, FastGraph, InputGraph, PathCalculator};
+use geom::Time;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
 use thread_local::ThreadLocal;
 
 #[derive(Serialize, Deserialize)]
@@ -14,10 +20,19 @@ pub struct VehiclePathfinder {
     #[serde(deserialize_with = "deserialize_nodemap")]
     nodes: NodeMap<Node>,
     uber_turns: Vec<UberTurn>,
+    // Every intersection that's part of some uber-turn
+    uber_turn_intersections: BTreeSet<IntersectionID>,
     constraints: PathConstraints,
 
     #[serde(skip_serializing, skip_deserializing)]
     path_calc: ThreadLocal<RefCell<PathCalculator>>,
+
+    // Only set for one simulation at a time, so don't bother saving it with the map.
+    #[serde(skip_serializing, skip_deserializing)]
+    observed: Option<ObservedTravelTimes>,
+    // One graph per bin of observed, weighted by those travel times
+    #[serde(skip_serializing, skip_deserializing)]
+    congested_graphs: Vec<FastGraph>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
@@ -49,7 +64,11 @@ impl VehiclePathfinder {
             }
         }
 
-        let input_graph = make_input_graph(map, &nodes, &uber_turns, constraints);
+        let uber_turn_intersections = uber_turns
+            .iter()
+            .flat_map(|ut| ut.path.iter().map(|t| t.parent))
+            .collect();
+        let input_graph = make_input_graph(map, &nodes, &uber_turns, constraints, None);
 
         // All VehiclePathfinders have the same nodes (lanes), so if we're not the first being
         // built, seed from the node ordering.
@@ -64,19 +83,51 @@ impl VehiclePathfinder {
             graph,
             nodes,
             uber_turns,
+            uber_turn_intersections,
             constraints,
             path_calc: ThreadLocal::new(),
+            observed: None,
+            congested_graphs: Vec::new(),
         }
     }
 
     pub fn pathfind(&self, req: &PathRequest, map: &Map) -> Option<(Path, usize)> {
+        self.pathfind_with_graph(&self.graph, req, map)
+    }
+
+    // Uses observed travel times from the bin covering this time of day, if there are any.
+    pub fn pathfind_at(&self, req: &PathRequest, time: Time, map: &Map) -> Option<(Path, usize)> {
+        if let Some(ref observed) = self.observed {
+            if let Some(graph) = self.congested_graphs.get(observed.bin(time)) {
+                return self.pathfind_with_graph(graph, req, map);
+            }
+        }
+        self.pathfind(req, map)
+    }
+
+    pub fn pathfind_with_penalties<F: Fn(LaneID) -> usize>(
+        &self,
+        req: &PathRequest,
+        map: &Map,
+        penalty: F,
+    ) -> Option<Path> {
+        pathfind_with_penalties(req, map, &self.uber_turn_intersections, penalty)
+    }
+
+    fn pathfind_with_graph(
+        &self,
+        graph: &FastGraph,
+        req: &PathRequest,
+        map: &Map,
+    ) -> Option<(Path, usize)> {
         assert!(!map.get_l(req.start.lane()).is_sidewalk());
+        // Every graph has the same nodes, so one calculator works for all of them.
         let mut calc = self
             .path_calc
             .get_or(|| RefCell::new(fast_paths::create_calculator(&self.graph)))
             .borrow_mut();
         let raw_path = calc.calc_path(
-            &self.graph,
+            graph,
             self.nodes.get(Node::Lane(req.start.lane())),
             self.nodes.get(Node::Lane(req.end.lane())),
         )?;
@@ -121,9 +172,41 @@ impl VehiclePathfinder {
         // the node ordering.
         // TODO Make sure the result of this is deterministic and equivalent to computing from
         // scratch.
-        let input_graph = make_input_graph(map, &self.nodes, &self.uber_turns, self.constraints);
+        let input_graph =
+            make_input_graph(map, &self.nodes, &self.uber_turns, self.constraints, None);
         let node_ordering = self.graph.get_node_ordering();
         self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
+
+        if let Some(observed) = self.observed.take() {
+            self.set_observed_travel_times(map, observed, &mut Timer::throwaway());
+        }
+    }
+
+    pub fn set_observed_travel_times(
+        &mut self,
+        map: &Map,
+        observed: ObservedTravelTimes,
+        timer: &mut Timer,
+    ) {
+        // The weights change, but the graph's shape doesn't, so the node ordering is still a
+        // decent choice.
+        let node_ordering = self.graph.get_node_ordering();
+        self.congested_graphs.clear();
+        let num_bins = observed.num_bins();
+        timer.start_iter("prepare congested pathfinding", num_bins);
+        for bin in 0..num_bins {
+            timer.next();
+            let input_graph = make_input_graph(
+                map,
+                &self.nodes,
+                &self.uber_turns,
+                self.constraints,
+                Some((&observed, bin)),
+            );
+            self.congested_graphs
+                .push(fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap());
+        }
+        self.observed = Some(observed);
     }
 }
 
@@ -132,8 +215,24 @@ fn make_input_graph(
     nodes: &NodeMap<Node>,
     uber_turns: &Vec<UberTurn>,
     constraints: PathConstraints,
+    observed: Option<(&ObservedTravelTimes, usize)>,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
+    // The observed time to cross a lane includes waiting to turn at the end, but not the turn
+    // itself. Never assume a lane is faster than free-flow.
+    let edge_cost = |lane: &Lane, turn: &Turn| {
+        let free_flow = cost(lane, turn, constraints, map);
+        match observed.and_then(|(times, bin)| times.get(lane.id, bin)) {
+            Some(dt) => {
+                let turn_time = (turn.geom.length() / map.get_parent(turn.id.dst).speed_limit)
+                    .inner_seconds()
+                    .round() as usize;
+                let lane_free_flow = free_flow.saturating_sub(turn_time);
+                lane_free_flow.max(dt.inner_seconds().round() as usize) + turn_time
+            }
+            None => free_flow,
+        }
+    };
 
     // From some lanes, instead of adding edges to turns, add edges to these (indexed) uber-turns.
     let mut uber_turn_entrances: MultiMap<LaneID, usize> = MultiMap::new();
@@ -178,7 +277,7 @@ fn make_input_graph(
                         from,
                         nodes.get(Node::Lane(turn.id.dst)),
                         // Round up! 0 cost edges are ignored
-                        cost(l, turn, constraints, map).max(1),
+                        edge_cost(l, turn).max(1),
                     );
                 }
             } else {
@@ -188,7 +287,7 @@ fn make_input_graph(
 
                     let mut sum_cost = 0;
                     for t in &ut.path {
-                        sum_cost += cost(map.get_l(t.src), map.get_t(*t), constraints, map);
+                        sum_cost += edge_cost(map.get_l(t.src), map.get_t(*t));
                     }
                     input_graph.add_edge(from, nodes.get(Node::UberTurn(*idx)), sum_cost.max(1));
                     input_graph.add_edge(
@@ -259,3 +358,177 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
         PathConstraints::Pedestrian => unreachable!(),
     }
 }
+
+// Slower Dijkstra's over the whole map, without the contraction hierarchy, so the caller can add an
+// extra cost to entering any lane. The resulting path can't express uber-turns, so it never goes
+// through any of the intersections in them.
+// TODO Follow the uber-turns instead of avoiding them.
+pub fn pathfind_with_penalties<F: Fn(LaneID) -> usize>(
+    req: &PathRequest,
+    map: &Map,
+    uber_turn_intersections: &BTreeSet<IntersectionID>,
+    penalty: F,
+) -> Option<Path> {
+    let start = req.start.lane();
+    let end = req.end.lane();
+    if start == end && req.start.dist_along() <= req.end.dist_along() {
+        return Some(Path::new(
+            map,
+            vec![PathStep::Lane(start)],
+            req.end.dist_along(),
+            Vec::new(),
+        ));
+    }
+    let start_r = map.get_l(start).parent;
+    let end_r = map.get_l(end).parent;
+
+    let mut queue: BinaryHeap<(Reverse<usize>, LaneID)> = BinaryHeap::new();
+    let mut best_cost: HashMap<LaneID, usize> = HashMap::new();
+    let mut backrefs: HashMap<LaneID, LaneID> = HashMap::new();
+    // Don't mark the start as visited yet; it might be the end too, if we have to loop around.
+    queue.push((Reverse(0), start));
+    while let Some((Reverse(cost_so_far), l)) = queue.pop() {
+        if l == end && cost_so_far > 0 {
+            break;
+        }
+        if best_cost.get(&l).map(|c| *c < cost_so_far).unwrap_or(false) {
+            continue;
+        }
+        let lane = map.get_l(l);
+        for turn in map.get_turns_for(l, req.constraints) {
+            if uber_turn_intersections.contains(&turn.id.parent) {
+                continue;
+            }
+            let next = turn.id.dst;
+            let next_r = map.get_l(next).parent;
+            // Don't cut through private zones
+            if next_r != start_r
+                && next_r != end_r
+                && !map
+                    .get_r(next_r)
+                    .allow_through_traffic
+                    .contains(req.constraints)
+            {
+                continue;
+            }
+            let total = cost_so_far + cost(lane, turn, req.constraints, map) + penalty(next);
+            if best_cost.get(&next).map(|c| total < *c).unwrap_or(true) {
+                best_cost.insert(next, total);
+                backrefs.insert(next, l);
+                queue.push((Reverse(total), next));
+            }
+        }
+    }
+    if !backrefs.contains_key(&end) {
+        return None;
+    }
+
+    let mut lanes = vec![end];
+    let mut current = end;
+    loop {
+        current = backrefs[&current];
+        lanes.push(current);
+        if current == start {
+            break;
+        }
+    }
+    lanes.reverse();
+    let mut steps = Vec::new();
+    for pair in lanes.windows(2) {
+        steps.push(PathStep::Lane(pair[0]));
+        // We don't need to look for this turn in the map; we know it exists.
+        steps.push(PathStep::Turn(TurnID {
+            parent: map.get_l(pair[0]).dst_i,
+            src: pair[0],
+            dst: pair[1],
+        }));
+    }
+    steps.push(PathStep::Lane(end));
+    Some(Path::new(map, steps, req.end.dist_along(), Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Position, TurnType};
+    use geom::Distance;
+
+    #[test]
+    fn test_pathfind_with_penalties() {
+        let mut timer = Timer::throwaway();
+        let map = Map::new(abstutil::path_synthetic_map("signal_single"), &mut timer);
+        let turn = map
+            .all_turns()
+            .values()
+            .find(|t| {
+                t.turn_type != TurnType::Crosswalk
+                    && map.get_l(t.id.src).is_driving()
+                    && map.get_l(t.id.dst).is_driving()
+            })
+            .unwrap();
+        let (src, dst) = (map.get_l(turn.id.src), map.get_l(turn.id.dst));
+        let req = |start: Position, end: Position| PathRequest {
+            start,
+            end,
+            constraints: PathConstraints::Car,
+        };
+
+        // Through one intersection
+        let path = pathfind_with_penalties(
+            &req(
+                Position::new(src.id, Distance::ZERO),
+                Position::new(dst.id, dst.length()),
+            ),
+            &map,
+            &BTreeSet::new(),
+            |_| 0,
+        )
+        .unwrap();
+        let steps = path.get_steps();
+        assert_eq!(steps[0], PathStep::Lane(src.id));
+        assert_eq!(*steps.back().unwrap(), PathStep::Lane(dst.id));
+        for step in steps {
+            if let PathStep::Turn(t) = step {
+                assert!(map.maybe_get_t(*t).is_some());
+            }
+        }
+
+        // Not if the intersection is part of an uber-turn
+        assert!(pathfind_with_penalties(
+            &req(
+                Position::new(src.id, Distance::ZERO),
+                Position::new(dst.id, dst.length())
+            ),
+            &map,
+            &vec![turn.id.parent].into_iter().collect(),
+            |_| 0,
+        )
+        .is_none());
+
+        // Staying on one lane
+        let path = pathfind_with_penalties(
+            &req(
+                Position::new(src.id, Distance::ZERO),
+                Position::new(src.id, src.length()),
+            ),
+            &map,
+            &BTreeSet::new(),
+            |_| 0,
+        )
+        .unwrap();
+        assert_eq!(path.get_steps().len(), 1);
+
+        // Ending behind the start means looping around, but there are no U-turns or cycles on this
+        // map.
+        assert!(pathfind_with_penalties(
+            &req(
+                Position::new(src.id, src.length()),
+                Position::new(src.id, Distance::ZERO),
+            ),
+            &map,
+            &BTreeSet::new(),
+            |_| 0,
+        )
+        .is_none());
+    }
+}
@@ -1,11 +1,13 @@
+mod congestion;
 mod driving;
 mod node_map;
 // TODO tmp
 pub mod uber_turns;
 mod walking;
 
-pub use self::driving::cost;
+pub use self::congestion::ObservedTravelTimes;
 use self::driving::VehiclePathfinder;
+pub use self::driving::{cost, pathfind_with_penalties};
 use self::walking::{one_step_walking_path, walking_path_to_steps, SidewalkPathfinder};
 pub use self::walking::{walking_cost, WalkingNode};
 use crate::{
@@ -14,7 +16,7 @@ use crate::{
 };
 use abstutil::Timer;
 use enumset::EnumSetType;
-use geom::{Distance, PolyLine, EPSILON_DIST};
+use geom::{Distance, PolyLine, Time, EPSILON_DIST};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::error::Error;
@@ -291,6 +293,20 @@ impl Path {
         }
     }
 
+    // Move over to a parallel lane partway along the current step, turning from there instead.
+    // Also trusting the caller.
+    pub fn change_current_lane(&mut self, lane: LaneID, turn: TurnID, map: &Map) {
+        assert!(self.currently_inside_ut.is_none());
+        for (idx, step) in [PathStep::Lane(lane), PathStep::Turn(turn)]
+            .iter()
+            .enumerate()
+        {
+            self.total_length -= self.steps[idx].as_traversable().length(map);
+            self.steps[idx] = *step;
+            self.total_length += self.steps[idx].as_traversable().length(map);
+        }
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -303,6 +319,10 @@ impl Path {
         self.steps[self.steps.len() - 1]
     }
 
+    pub fn end_dist(&self) -> Distance {
+        self.end_dist
+    }
+
     // dist_ahead is unlimited when None.
     pub fn trace(
         &self,
@@ -699,6 +719,44 @@ impl Pathfinder {
         }
     }
 
+    // Like pathfind, but driving paths use travel times observed around this time of day.
+    pub fn pathfind_at(&self, req: PathRequest, time: Time, map: &Map) -> Option<Path> {
+        // Paths to or from private zones get stitched together specially; just use free-flow
+        // times for them.
+        if req.constraints == PathConstraints::Car
+            && map.get_parent(req.start.lane()).get_zone(map).is_none()
+            && map.get_parent(req.end.lane()).get_zone(map).is_none()
+        {
+            return self.car_graph.pathfind_at(&req, time, map).map(|(p, _)| p);
+        }
+        self.pathfind(req, map)
+    }
+
+    pub fn set_observed_travel_times(
+        &mut self,
+        map: &Map,
+        observed: ObservedTravelTimes,
+        timer: &mut Timer,
+    ) {
+        self.car_graph
+            .set_observed_travel_times(map, observed, timer);
+    }
+
+    pub fn pathfind_with_penalties<F: Fn(LaneID) -> usize>(
+        &self,
+        req: PathRequest,
+        map: &Map,
+        penalty: F,
+    ) -> Option<Path> {
+        match req.constraints {
+            PathConstraints::Pedestrian => unreachable!(),
+            PathConstraints::Car => self.car_graph.pathfind_with_penalties(&req, map, penalty),
+            PathConstraints::Bike => self.bike_graph.pathfind_with_penalties(&req, map, penalty),
+            PathConstraints::Bus => self.bus_graph.pathfind_with_penalties(&req, map, penalty),
+            PathConstraints::Train => self.train_graph.pathfind_with_penalties(&req, map, penalty),
+        }
+    }
+
     // TODO Alright, reconsider refactoring pieces of this again. :)
     fn pathfind_from_zone(
         &self,
@@ -1,12 +1,19 @@
-use crate::{AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType};
+use crate::{
+    AgentID, AgentType, AlertLocation, CarID, DockID, Emissions, EmissionsReport, Event, FleetMix,
+    ParkingSpot, TripID, TripMode, TripPhaseType, VehicleType,
+};
 use abstutil::Counter;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use geom::{Distance, Duration, Histogram, Time};
 use map_model::{
-    BusRouteID, BusStopID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathRequest, RoadID,
-    Traversable, TurnGroupID,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, ObservedTravelTimes,
+    ParkingLotID, Path, PathRequest, RoadID, Traversable, TurnGroupID,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
+use std::io::{Error, ErrorKind};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Analytics {
@@ -17,6 +24,19 @@ pub struct Analytics {
     pub demand: BTreeMap<TurnGroupID, usize>,
     pub bus_arrivals: Vec<(Time, CarID, BusRouteID, BusStopID)>,
     pub bus_passengers_waiting: Vec<(Time, BusStopID, BusRouteID)>,
+    // How many passengers were on board when a bus left a stop, heading to the next one
+    pub bus_loads: Vec<(Time, BusRouteID, BusStopID, usize)>,
+    // How many people a full bus left waiting at a stop
+    pub bus_pass_ups: Vec<(Time, BusRouteID, BusStopID, usize)>,
+    // When was somebody picked up, where, and how long did they wait?
+    pub ride_hail_pickups: Vec<(Time, TripID, BuildingID, Duration)>,
+    pub ride_hail_dropoffs: Vec<(Time, TripID, BuildingID)>,
+    // Distance driven per leg, and whether a passenger was inside
+    pub ride_hail_legs: Vec<(Time, CarID, Distance, bool)>,
+    // When did a truck start double-parking in front of a building, and for how long?
+    pub deliveries: Vec<(Time, CarID, BuildingID, Duration)>,
+    // How many bikes are at each bike share dock over time
+    pub bike_share_docks: BTreeMap<DockID, Vec<(Time, usize)>>,
     pub started_trips: BTreeMap<TripID, Time>,
     // TODO Hack: No TripMode means aborted
     // Finish time, ID, mode (or None as aborted), trip duration
@@ -24,14 +44,32 @@ pub struct Analytics {
     // TODO This subsumes finished_trips
     pub trip_log: Vec<(Time, TripID, Option<PathRequest>, TripPhaseType)>,
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(Time, Duration, TripMode)>>,
+    // When a signal adjusted a phase for a bus, by how much, and whether the phase was extended
+    // (true) or ended early (false)
+    pub transit_signal_priority: BTreeMap<IntersectionID, Vec<(Time, CarID, Duration, bool)>>,
+    // When did a driver give up waiting at the end of a lane and find another route?
+    pub reroutes: Vec<(Time, CarID, LaneID)>,
+    // When did a car enter a lane, and how long did it take to start the turn at the end?
+    pub lane_travel_times: Vec<(Time, LaneID, Duration)>,
+    // The lane each car is crossing right now, and when it entered. Just to fill out
+    // lane_travel_times.
+    #[serde(skip_serializing, skip_deserializing)]
+    lane_entered_at: BTreeMap<CarID, (LaneID, Time)>,
+    // Per (trip, road, type of vehicle): total distance driven, total time, and how long was
+    // spent stopped. The trip is None for buses and trains. For estimating emissions.
+    pub road_driving:
+        BTreeMap<(Option<TripID>, RoadID, VehicleType), (Distance, Duration, Duration)>,
+    // Same, but for driving through intersections
+    pub intersection_driving:
+        BTreeMap<(Option<TripID>, IntersectionID, VehicleType), (Distance, Duration, Duration)>,
     // Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
-    // After we restore from a savestate, don't record anything. This is only going to make sense
-    // if savestates are only used for quickly previewing against prebaked results, where we have
-    // the full Analytics anyway.
+    // After we restore from a savestate without Analytics, don't record anything. This is only
+    // going to make sense if savestates are only used for quickly previewing against prebaked
+    // results, where we have the full Analytics anyway.
     record_anything: bool,
 }
 
@@ -43,10 +81,23 @@ impl Analytics {
             demand: BTreeMap::new(),
             bus_arrivals: Vec::new(),
             bus_passengers_waiting: Vec::new(),
+            bus_loads: Vec::new(),
+            bus_pass_ups: Vec::new(),
+            ride_hail_pickups: Vec::new(),
+            ride_hail_dropoffs: Vec::new(),
+            ride_hail_legs: Vec::new(),
+            deliveries: Vec::new(),
+            bike_share_docks: BTreeMap::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
+            transit_signal_priority: BTreeMap::new(),
+            reroutes: Vec::new(),
+            lane_travel_times: Vec::new(),
+            lane_entered_at: BTreeMap::new(),
+            road_driving: BTreeMap::new(),
+            intersection_driving: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
             alerts: Vec::new(),
@@ -76,6 +127,21 @@ impl Analytics {
                 }
             };
         }
+        // Lane travel times, just for cars. Buses stop along the way, and bikes are slow anyway.
+        if let Event::AgentEntersTraversable(AgentID::Car(car), to) = ev {
+            if car.1 == VehicleType::Car {
+                match to {
+                    Traversable::Lane(l) => {
+                        self.lane_entered_at.insert(car, (l, time));
+                    }
+                    Traversable::Turn(_) => {
+                        if let Some((l, entered)) = self.lane_entered_at.remove(&car) {
+                            self.lane_travel_times.push((entered, l, time - entered));
+                        }
+                    }
+                }
+            }
+        }
         match ev {
             Event::PersonLeavesMap(_, maybe_a, i, _) => {
                 // Ignore aborted trips
@@ -94,6 +160,42 @@ impl Analytics {
             self.bus_arrivals.push((time, bus, route, stop));
         }
 
+        // Emissions
+        if let Event::VehicleTraversed {
+            car,
+            trip,
+            on,
+            dist,
+            duration,
+            idle,
+        } = ev
+        {
+            let totals = match on {
+                Traversable::Lane(l) => self
+                    .road_driving
+                    .entry((trip, map.get_l(l).parent, car.1))
+                    .or_insert((Distance::ZERO, Duration::ZERO, Duration::ZERO)),
+                Traversable::Turn(t) => self
+                    .intersection_driving
+                    .entry((trip, t.parent, car.1))
+                    .or_insert((Distance::ZERO, Duration::ZERO, Duration::ZERO)),
+            };
+            totals.0 += dist;
+            totals.1 += duration;
+            totals.2 += idle;
+        }
+
+        // Crowding
+        match ev {
+            Event::BusDepartedFromStop(_, route, stop, load) => {
+                self.bus_loads.push((time, route, stop, load));
+            }
+            Event::BusPassedUpRiders(_, route, stop, cnt) => {
+                self.bus_pass_ups.push((time, route, stop, cnt));
+            }
+            _ => {}
+        }
+
         // Bus passengers
         if let Event::TripPhaseStarting(_, _, _, ref tpt) = ev {
             if let TripPhaseType::WaitingForBus(route, stop) = tpt {
@@ -101,6 +203,33 @@ impl Analytics {
             }
         }
 
+        // Ride-hailing
+        match ev {
+            Event::RideHailPickup(trip, _, b, wait) => {
+                self.ride_hail_pickups.push((time, trip, b, wait));
+            }
+            Event::RideHailDropoff(trip, _, b) => {
+                self.ride_hail_dropoffs.push((time, trip, b));
+            }
+            Event::RideHailLegFinished(car, dist, occupied) => {
+                self.ride_hail_legs.push((time, car, dist, occupied));
+            }
+            _ => {}
+        }
+
+        // Deliveries
+        if let Event::TruckDoubleParked(car, b, dt) = ev {
+            self.deliveries.push((time, car, b, dt));
+        }
+
+        // Bike share
+        if let Event::BikeShareDockUpdated(dock, bikes) = ev {
+            self.bike_share_docks
+                .entry(dock)
+                .or_insert_with(Vec::new)
+                .push((time, bikes));
+        }
+
         // Started trips
         if let Event::TripPhaseStarting(id, _, _, _) = ev {
             self.started_trips.entry(id).or_insert(time);
@@ -128,6 +257,12 @@ impl Analytics {
                 .or_insert_with(Vec::new)
                 .push((time, delay, mode));
         }
+        if let Event::TransitSignalPriority(id, bus, dt, extended) = ev {
+            self.transit_signal_priority
+                .entry(id)
+                .or_insert_with(Vec::new)
+                .push((time, bus, dt, extended));
+        }
 
         // Parking spot changes
         if let Event::CarReachedParkingSpot(_, spot) = ev {
@@ -172,6 +307,28 @@ impl Analytics {
             Event::PathAmended(path) => {
                 self.record_demand(&path, map);
             }
+            Event::CarRerouted {
+                car,
+                lane,
+                old_path,
+                new_path,
+            } => {
+                self.reroutes.push((time, car, lane));
+                self.forget_demand(&old_path, map);
+                self.record_demand(&new_path, map);
+            }
+            Event::CarChangedLanes {
+                old_turn, new_turn, ..
+            } => {
+                if let Some(id) = map.get_turn_group(old_turn) {
+                    if let Some(cnt) = self.demand.get_mut(&id) {
+                        *cnt = cnt.saturating_sub(1);
+                    }
+                }
+                if let Some(id) = map.get_turn_group(new_turn) {
+                    *self.demand.entry(id).or_insert(0) += 1;
+                }
+            }
             Event::Alert(loc, msg) => {
                 self.alerts.push((time, loc, msg));
             }
@@ -189,6 +346,18 @@ impl Analytics {
         }
     }
 
+    fn forget_demand(&mut self, path: &Path, map: &Map) {
+        for step in path.get_steps() {
+            if let Traversable::Turn(t) = step.as_traversable() {
+                if let Some(id) = map.get_turn_group(t) {
+                    if let Some(cnt) = self.demand.get_mut(&id) {
+                        *cnt = cnt.saturating_sub(1);
+                    }
+                }
+            }
+        }
+    }
+
     // TODO If these ever need to be speeded up, just cache the histogram and index in the events
     // list.
 
@@ -326,6 +495,71 @@ impl Analytics {
         delays_to_stop
     }
 
+    // Up to some moment in time, how many passengers were on board as a route's buses left each
+    // stop?
+    pub fn bus_load_profile(
+        &self,
+        now: Time,
+        r: BusRouteID,
+    ) -> BTreeMap<BusStopID, Histogram<usize>> {
+        let mut per_stop = BTreeMap::new();
+        for (t, route, stop, load) in &self.bus_loads {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                per_stop
+                    .entry(*stop)
+                    .or_insert_with(Histogram::new)
+                    .add(*load);
+            }
+        }
+        per_stop
+    }
+
+    pub fn bus_pass_ups_per_stop(&self, now: Time, r: BusRouteID) -> Counter<BusStopID> {
+        let mut per_stop = Counter::new();
+        for (t, route, stop, cnt) in &self.bus_pass_ups {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                per_stop.add(*stop, *cnt);
+            }
+        }
+        per_stop
+    }
+
+    // How many drivers gave up on each road and went another way?
+    pub fn reroutes_per_road(&self, now: Time, map: &Map) -> Counter<RoadID> {
+        let mut cnt = Counter::new();
+        for (t, _, l) in &self.reroutes {
+            if *t > now {
+                break;
+            }
+            cnt.inc(map.get_l(*l).parent);
+        }
+        cnt
+    }
+
+    // Average the time cars took to cross each lane, grouped by when they entered it. Only lanes
+    // that somebody crossed show up.
+    pub fn observed_travel_times(&self, bin_size: Duration) -> ObservedTravelTimes {
+        let mut sums: BTreeMap<(LaneID, usize), (Duration, usize)> = BTreeMap::new();
+        let mut result = ObservedTravelTimes::new(bin_size);
+        for (t, l, dt) in &self.lane_travel_times {
+            let entry = sums
+                .entry((*l, result.bin(*t)))
+                .or_insert((Duration::ZERO, 0));
+            entry.0 += *dt;
+            entry.1 += 1;
+        }
+        for ((l, bin), (sum, cnt)) in sums {
+            result.set(l, bin, sum / (cnt as f64));
+        }
+        result
+    }
+
     // At some moment in time, what's the distribution of passengers waiting for a route like?
     pub fn bus_passenger_delays(
         &self,
@@ -369,6 +603,119 @@ impl Analytics {
         })
     }
 
+    pub fn ride_hail_waits(&self, now: Time) -> Histogram<Duration> {
+        let mut waits = Histogram::new();
+        for (t, _, _, wait) in &self.ride_hail_pickups {
+            if *t > now {
+                break;
+            }
+            waits.add(*wait);
+        }
+        waits
+    }
+
+    // (total distance driven empty, total distance driven with a passenger)
+    pub fn ride_hail_distances(&self, now: Time) -> (Distance, Distance) {
+        let mut empty = Distance::ZERO;
+        let mut occupied = Distance::ZERO;
+        for (t, _, dist, with_passenger) in &self.ride_hail_legs {
+            if *t > now {
+                break;
+            }
+            if *with_passenger {
+                occupied += *dist;
+            } else {
+                empty += *dist;
+            }
+        }
+        (empty, occupied)
+    }
+
+    // Estimated fuel, CO2, NOx and energy for everything driven so far. Each trip's driving along
+    // one road is estimated at its average speed there.
+    pub fn emissions(&self, fleet: &FleetMix) -> EmissionsReport {
+        let mut report = EmissionsReport {
+            total: Emissions::ZERO,
+            per_trip: BTreeMap::new(),
+            per_road: BTreeMap::new(),
+            per_intersection: BTreeMap::new(),
+        };
+        for ((trip, r, vehicle_type), (dist, duration, idle)) in &self.road_driving {
+            let emissions = fleet.estimate(*vehicle_type, *dist, *duration, *idle);
+            report.total += emissions;
+            if let Some(trip) = trip {
+                *report.per_trip.entry(*trip).or_insert(Emissions::ZERO) += emissions;
+            }
+            *report.per_road.entry(*r).or_insert(Emissions::ZERO) += emissions;
+        }
+        for ((trip, i, vehicle_type), (dist, duration, idle)) in &self.intersection_driving {
+            let emissions = fleet.estimate(*vehicle_type, *dist, *duration, *idle);
+            report.total += emissions;
+            if let Some(trip) = trip {
+                *report.per_trip.entry(*trip).or_insert(Emissions::ZERO) += emissions;
+            }
+            *report.per_intersection.entry(*i).or_insert(Emissions::ZERO) += emissions;
+        }
+        report
+    }
+
+    // How many pickups and dropoffs happened at each building? Useful for estimating curb demand.
+    pub fn ride_hail_curb_demand(&self, now: Time) -> Counter<BuildingID> {
+        let mut cnt = Counter::new();
+        for (t, _, b, _) in &self.ride_hail_pickups {
+            if *t > now {
+                break;
+            }
+            cnt.inc(*b);
+        }
+        for (t, _, b) in &self.ride_hail_dropoffs {
+            if *t > now {
+                break;
+            }
+            cnt.inc(*b);
+        }
+        cnt
+    }
+
+    // How long has a truck blocked the street in front of each building?
+    pub fn double_parked_time(&self, now: Time) -> BTreeMap<BuildingID, Duration> {
+        let mut per_bldg = BTreeMap::new();
+        for (t, _, b, dt) in &self.deliveries {
+            if *t > now {
+                break;
+            }
+            // Don't count the part of the delivery that hasn't happened yet
+            *per_bldg.entry(*b).or_insert(Duration::ZERO) += (*dt).min(now - *t);
+        }
+        per_bldg
+    }
+
+    // How long has each bike share dock sat empty?
+    pub fn empty_dock_time(&self, now: Time) -> BTreeMap<DockID, Duration> {
+        let mut per_dock = BTreeMap::new();
+        for (dock, changes) in &self.bike_share_docks {
+            let mut total = Duration::ZERO;
+            let mut empty_since = None;
+            for (t, bikes) in changes {
+                if *t > now {
+                    break;
+                }
+                if *bikes == 0 {
+                    if empty_since.is_none() {
+                        empty_since = Some(*t);
+                    }
+                } else if let Some(since) = empty_since.take() {
+                    total += *t - since;
+                }
+            }
+            if let Some(since) = empty_since {
+                total += now - since;
+            }
+            per_dock.insert(*dock, total);
+        }
+        per_dock
+    }
+
     pub fn get_trip_phases(&self, trip: TripID, map: &Map) -> Vec<TripPhase> {
         let mut phases: Vec<TripPhase> = Vec::new();
         for (t, id, maybe_req, phase_type) in &self.trip_log {
@@ -527,6 +874,22 @@ impl Analytics {
     }
 }
 
+// Savestates
+impl Analytics {
+    // Everything is kept, so the dashboards look the same after resuming, but compressed. The trip
+    // log and raw throughput are very repetitive.
+    pub(crate) fn pack(&self) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        bincode::serialize_into(&mut encoder, self).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    pub(crate) fn unpack(bytes: &[u8]) -> Result<Analytics, Error> {
+        bincode::deserialize_from(DeflateDecoder::new(bytes))
+            .map_err(|err| Error::new(ErrorKind::Other, err))
+    }
+}
+
 impl Default for Analytics {
     fn default() -> Analytics {
         let mut a = Analytics::new();
@@ -0,0 +1,147 @@
+use crate::{SidewalkPOI, SidewalkSpot, BIKE_LENGTH};
+use geom::Distance;
+use map_model::{Map, Position};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Where people can pick up or return a shared bike. There has to be a bike or driving lane next to
+// the sidewalk.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BikeShareDock {
+    pub sidewalk_pos: Position,
+    pub capacity: usize,
+    // How many bikes are docked here at the start of the simulation
+    pub initial_bikes: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DockID(pub usize);
+
+impl fmt::Display for DockID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bike share dock #{}", self.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+struct Dock {
+    spot: SidewalkSpot,
+    capacity: usize,
+    bikes: usize,
+}
+
+// Individual bikes aren't tracked. Somebody riding a shared bike uses a bike from their own list of
+// vehicles, and the docks just count how many bikes are sitting there.
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct BikeShareSimState {
+    docks: Vec<Dock>,
+}
+
+impl BikeShareSimState {
+    pub fn new() -> BikeShareSimState {
+        BikeShareSimState { docks: Vec::new() }
+    }
+
+    // None if there's no way to bike away from the sidewalk here
+    pub fn add_dock(&mut self, spec: &BikeShareDock, map: &Map) -> Option<DockID> {
+        let sidewalk = spec.sidewalk_pos.lane();
+        let driving_lane = map.get_parent(sidewalk).sidewalk_to_bike(sidewalk)?;
+        // Same as bike_from_bike_rack, don't start biking on a blackhole
+        if map.get_l(driving_lane).parking_blackhole.is_some() {
+            return None;
+        }
+        let driving_pos = spec
+            .sidewalk_pos
+            .equiv_pos(driving_lane, Distance::ZERO, map);
+        if driving_pos.dist_along() < BIKE_LENGTH {
+            return None;
+        }
+
+        let id = DockID(self.docks.len());
+        self.docks.push(Dock {
+            spot: SidewalkSpot {
+                connection: SidewalkPOI::BikeRack(driving_pos),
+                sidewalk_pos: spec.sidewalk_pos,
+            },
+            capacity: spec.capacity,
+            bikes: spec.initial_bikes.min(spec.capacity),
+        });
+        Some(id)
+    }
+
+    pub fn get_spot(&self, id: DockID) -> &SidewalkSpot {
+        &self.docks[id.0].spot
+    }
+
+    pub fn bikes_at(&self, id: DockID) -> usize {
+        self.docks[id.0].bikes
+    }
+
+    pub fn dock_at(&self, spot: &SidewalkSpot) -> Option<DockID> {
+        self.docks.iter().position(|d| d.spot == *spot).map(DockID)
+    }
+
+    // Returns false if the dock is empty
+    pub fn take_bike(&mut self, id: DockID) -> bool {
+        let dock = &mut self.docks[id.0];
+        if dock.bikes == 0 {
+            return false;
+        }
+        dock.bikes -= 1;
+        true
+    }
+
+    // Returns false if the dock is full. If force is true, the bike is left there anyway.
+    pub fn return_bike(&mut self, id: DockID, force: bool) -> bool {
+        let dock = &mut self.docks[id.0];
+        if dock.bikes >= dock.capacity && !force {
+            return false;
+        }
+        dock.bikes += 1;
+        true
+    }
+
+    pub fn nearest_with_bikes(
+        &self,
+        pos: Position,
+        except: Option<DockID>,
+        map: &Map,
+    ) -> Option<DockID> {
+        self.nearest(pos, except, map, |d| d.bikes > 0)
+    }
+
+    pub fn nearest_with_room(
+        &self,
+        pos: Position,
+        except: Option<DockID>,
+        map: &Map,
+    ) -> Option<DockID> {
+        self.nearest(pos, except, map, |d| d.bikes < d.capacity)
+    }
+
+    // TODO Straight-line distance is a bad proxy for walking distance
+    fn nearest<F: Fn(&Dock) -> bool>(
+        &self,
+        pos: Position,
+        except: Option<DockID>,
+        map: &Map,
+        ok: F,
+    ) -> Option<DockID> {
+        let pt = pos.pt(map);
+        self.docks
+            .iter()
+            .enumerate()
+            .filter(|(idx, d)| Some(DockID(*idx)) != except && ok(d))
+            .min_by_key(|(_, d)| d.spot.sidewalk_pos.pt(map).dist_to(pt))
+            .map(|(idx, _)| DockID(idx))
+    }
+
+    // (ID, position, bikes, capacity)
+    pub fn get_all_docks(&self) -> Vec<(DockID, Position, usize, usize)> {
+        self.docks
+            .iter()
+            .enumerate()
+            .map(|(idx, d)| (DockID(idx), d.spot.sidewalk_pos, d.bikes, d.capacity))
+            .collect()
+    }
+}
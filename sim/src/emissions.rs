@@ -0,0 +1,222 @@
+// Rough estimates of fuel burned, CO2 and NOx emitted, and energy used by vehicles. This is an
+// average-speed model: everything a trip drives along one road is summarized by its length, how
+// long the vehicle sat idling, and its average speed the rest of the time. That's much cruder than
+// a model using second-by-second speed profiles, but it's enough to compare proposals.
+//
+// The rates are ballpark figures for an urban fleet, not calibrated against anything. People,
+// bikes, and time spent parked don't count.
+
+use crate::{TripID, VehicleType};
+use geom::{Distance, Duration};
+use map_model::{IntersectionID, RoadID};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops;
+
+// Fuel burns most efficiently around this speed
+const OPTIMAL_KM_PER_HOUR: f64 = 60.0;
+// kg of CO2 per liter burned
+const CO2_GASOLINE: f64 = 2.31;
+const CO2_DIESEL: f64 = 2.68;
+// grams of NOx per liter burned
+const NOX_GASOLINE: f64 = 1.0;
+const NOX_DIESEL: f64 = 10.0;
+// kWh per liter
+const ENERGY_GASOLINE: f64 = 8.9;
+const ENERGY_DIESEL: f64 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Powertrain {
+    Gasoline,
+    Diesel,
+    // Burns gasoline, but shuts off the engine when stopped and recovers energy from braking
+    Hybrid,
+    Electric,
+}
+
+// What fraction of each type of vehicle uses each powertrain. The fractions for one type of
+// vehicle should add up to 1.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FleetMix {
+    pub cars: Vec<(Powertrain, f64)>,
+    // Delivery trucks and vans
+    pub trucks: Vec<(Powertrain, f64)>,
+    pub buses: Vec<(Powertrain, f64)>,
+    pub trains: Vec<(Powertrain, f64)>,
+}
+
+impl FleetMix {
+    pub fn new() -> FleetMix {
+        FleetMix {
+            cars: vec![
+                (Powertrain::Gasoline, 0.85),
+                (Powertrain::Diesel, 0.03),
+                (Powertrain::Hybrid, 0.07),
+                (Powertrain::Electric, 0.05),
+            ],
+            trucks: vec![(Powertrain::Gasoline, 0.3), (Powertrain::Diesel, 0.7)],
+            buses: vec![
+                (Powertrain::Diesel, 0.4),
+                (Powertrain::Hybrid, 0.4),
+                (Powertrain::Electric, 0.2),
+            ],
+            trains: vec![(Powertrain::Electric, 1.0)],
+        }
+    }
+
+    fn mix(&self, vehicle_type: VehicleType) -> &[(Powertrain, f64)] {
+        match vehicle_type {
+            VehicleType::Car => &self.cars,
+            VehicleType::Truck => &self.trucks,
+            VehicleType::Bus => &self.buses,
+            VehicleType::Train => &self.trains,
+            VehicleType::Bike => &[],
+        }
+    }
+
+    // The expected emissions of a vehicle of this type, driving dist over duration, of which it
+    // spent idle stopped.
+    pub fn estimate(
+        &self,
+        vehicle_type: VehicleType,
+        dist: Distance,
+        duration: Duration,
+        idle: Duration,
+    ) -> Emissions {
+        let mut total = Emissions::ZERO;
+        for (powertrain, fraction) in self.mix(vehicle_type) {
+            total += estimate(vehicle_type, *powertrain, dist, duration, idle) * *fraction;
+        }
+        total
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Emissions {
+    pub fuel_liters: f64,
+    pub co2_kg: f64,
+    pub nox_grams: f64,
+    // Fuel and electricity together
+    pub energy_kwh: f64,
+}
+
+impl Emissions {
+    pub const ZERO: Emissions = Emissions {
+        fuel_liters: 0.0,
+        co2_kg: 0.0,
+        nox_grams: 0.0,
+        energy_kwh: 0.0,
+    };
+}
+
+pub struct EmissionsReport {
+    // For the whole scenario
+    pub total: Emissions,
+    pub per_trip: BTreeMap<TripID, Emissions>,
+    pub per_road: BTreeMap<RoadID, Emissions>,
+    // Driving through intersections
+    pub per_intersection: BTreeMap<IntersectionID, Emissions>,
+}
+
+impl ops::AddAssign for Emissions {
+    fn add_assign(&mut self, other: Emissions) {
+        self.fuel_liters += other.fuel_liters;
+        self.co2_kg += other.co2_kg;
+        self.nox_grams += other.nox_grams;
+        self.energy_kwh += other.energy_kwh;
+    }
+}
+
+impl ops::Mul<f64> for Emissions {
+    type Output = Emissions;
+
+    fn mul(self, scalar: f64) -> Emissions {
+        Emissions {
+            fuel_liters: self.fuel_liters * scalar,
+            co2_kg: self.co2_kg * scalar,
+            nox_grams: self.nox_grams * scalar,
+            energy_kwh: self.energy_kwh * scalar,
+        }
+    }
+}
+
+// (gasoline liters per 100km at the optimal speed, gasoline liters per hour idling, kWh per km
+// for an electric version)
+fn base_rates(vehicle_type: VehicleType) -> (f64, f64, f64) {
+    match vehicle_type {
+        VehicleType::Car => (7.0, 0.8, 0.18),
+        VehicleType::Truck => (14.0, 1.5, 0.5),
+        VehicleType::Bus => (50.0, 3.0, 1.3),
+        VehicleType::Train => (150.0, 10.0, 6.0),
+        VehicleType::Bike => (0.0, 0.0, 0.0),
+    }
+}
+
+// Relative to cruising at the optimal speed. A slow average speed means stop-and-go driving,
+// which wastes energy unless it's recovered by braking. Going faster fights more air resistance.
+fn speed_factor(km_per_hour: f64, regenerative_braking: bool) -> f64 {
+    let ratio = (km_per_hour / OPTIMAL_KM_PER_HOUR).max(0.1);
+    if ratio >= 1.0 {
+        1.0 + 0.5 * (ratio - 1.0).powi(2)
+    } else if regenerative_braking {
+        1.0
+    } else {
+        1.0 + 0.6 * (1.0 / ratio - 1.0)
+    }
+}
+
+fn estimate(
+    vehicle_type: VehicleType,
+    powertrain: Powertrain,
+    dist: Distance,
+    duration: Duration,
+    idle: Duration,
+) -> Emissions {
+    let km = dist.inner_meters().max(0.0) / 1000.0;
+    let idle_hours = idle.inner_seconds().max(0.0) / 3600.0;
+    let moving_hours = (duration - idle).inner_seconds() / 3600.0;
+    let km_per_hour = if moving_hours > 0.0 {
+        km / moving_hours
+    } else {
+        OPTIMAL_KM_PER_HOUR
+    };
+    let (fuel_per_100km, fuel_per_hour_idling, kwh_per_km) = base_rates(vehicle_type);
+
+    let (fuel, co2, nox, energy) = match powertrain {
+        Powertrain::Electric => {
+            return Emissions {
+                fuel_liters: 0.0,
+                co2_kg: 0.0,
+                nox_grams: 0.0,
+                energy_kwh: kwh_per_km * km * speed_factor(km_per_hour, true),
+            };
+        }
+        Powertrain::Gasoline => (
+            fuel_per_100km / 100.0 * km * speed_factor(km_per_hour, false)
+                + fuel_per_hour_idling * idle_hours,
+            CO2_GASOLINE,
+            NOX_GASOLINE,
+            ENERGY_GASOLINE,
+        ),
+        // Diesel engines are more efficient
+        Powertrain::Diesel => (
+            0.8 * (fuel_per_100km / 100.0 * km * speed_factor(km_per_hour, false)
+                + fuel_per_hour_idling * idle_hours),
+            CO2_DIESEL,
+            NOX_DIESEL,
+            ENERGY_DIESEL,
+        ),
+        Powertrain::Hybrid => (
+            0.7 * fuel_per_100km / 100.0 * km * speed_factor(km_per_hour, true),
+            CO2_GASOLINE,
+            NOX_GASOLINE,
+            ENERGY_GASOLINE,
+        ),
+    };
+    Emissions {
+        fuel_liters: fuel,
+        co2_kg: fuel * co2,
+        nox_grams: fuel * nox,
+        energy_kwh: fuel * energy,
+    }
+}
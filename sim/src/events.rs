@@ -1,9 +1,10 @@
 use crate::{
-    AgentID, CarID, OffMapLocation, ParkingSpot, PedestrianID, PersonID, TripID, TripMode,
+    AgentID, CarID, DockID, OffMapLocation, ParkingSpot, PedestrianID, PersonID, TripID, TripMode,
 };
-use geom::Duration;
+use geom::{Distance, Duration};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, Traversable,
+    TurnID,
 };
 use serde::{Deserialize, Serialize};
 
@@ -16,7 +17,24 @@ pub enum Event {
     CarLeftParkingSpot(CarID, ParkingSpot),
 
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
-    BusDepartedFromStop(CarID, BusRouteID, BusStopID),
+    // How many passengers are on board when the bus leaves?
+    BusDepartedFromStop(CarID, BusRouteID, BusStopID, usize),
+    // A full bus left this many people waiting at a stop
+    BusPassedUpRiders(CarID, BusRouteID, BusStopID, usize),
+
+    // How long did the passenger wait since requesting the ride?
+    RideHailPickup(TripID, CarID, BuildingID, Duration),
+    RideHailDropoff(TripID, CarID, BuildingID),
+    // How far did a ride-hail vehicle just drive, and was somebody inside? The empty distance
+    // counts deadheading to a pickup and repositioning afterwards.
+    RideHailLegFinished(CarID, Distance, bool),
+
+    // A delivery truck stopped in the driving lane in front of a building, blocking it for this
+    // long
+    TruckDoubleParked(CarID, BuildingID, Duration),
+
+    // How many bikes are at a bike share dock now
+    BikeShareDockUpdated(DockID, usize),
 
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
@@ -36,7 +54,22 @@ pub enum Event {
     BikeStoppedAtSidewalk(CarID, LaneID),
 
     AgentEntersTraversable(AgentID, Traversable),
+    // A vehicle finished driving part of a traversable -- usually all of it, except where it
+    // started or stopped. Just for estimating emissions.
+    VehicleTraversed {
+        car: CarID,
+        // None for buses and other vehicles without a trip
+        trip: Option<TripID>,
+        on: Traversable,
+        dist: Distance,
+        duration: Duration,
+        // How much of the duration was spent stopped
+        idle: Duration,
+    },
     IntersectionDelayMeasured(IntersectionID, Duration, TripMode),
+    // A traffic signal held the current phase open (true) or ended it early (false) by this much
+    // for an approaching bus or train
+    TransitSignalPriority(IntersectionID, CarID, Duration, bool),
 
     TripFinished {
         trip: TripID,
@@ -47,9 +80,25 @@ pub enum Event {
     TripAborted(TripID),
     TripPhaseStarting(TripID, PersonID, Option<PathRequest>, TripPhaseType),
 
-    // Just use for parking replanning. Not happy about copying the full path in here, but the way
-    // to plumb info into Analytics is Event.
+    // An agent started following this path, or had to change it for parking replanning. Analytics
+    // counts turn demand from it. Not happy about copying the full path in here, but the way to
+    // plumb info into Analytics is Event.
     PathAmended(Path),
+    // A driver stuck at the end of this lane found another way around. The remaining old path and
+    // the new one are here to keep turn demand correct.
+    CarRerouted {
+        car: CarID,
+        lane: LaneID,
+        old_path: Path,
+        new_path: Path,
+    },
+    // A car moved over to the lane next to it partway along a road, so it'll make a different turn
+    // at the end. Only that first turn changes.
+    CarChangedLanes {
+        car: CarID,
+        old_turn: TurnID,
+        new_turn: TurnID,
+    },
 
     Alert(AlertLocation, String),
 }
@@ -71,6 +120,9 @@ pub enum TripPhaseType {
     WaitingForBus(BusRouteID, BusStopID),
     // What stop did they board at?
     RidingBus(BusRouteID, BusStopID, CarID),
+    WaitingForRideHail,
+    RidingRideHail(CarID),
+    Delivering(BuildingID),
     Aborted,
     Finished,
     DelayedStart,
@@ -88,6 +140,9 @@ impl TripPhaseType {
                 format!("waiting for bus {}", map.get_br(r).full_name)
             }
             TripPhaseType::RidingBus(r, _, _) => format!("riding bus {}", map.get_br(r).full_name),
+            TripPhaseType::WaitingForRideHail => "waiting for a ride-hail pickup".to_string(),
+            TripPhaseType::RidingRideHail(_) => "riding in a ride-hail vehicle".to_string(),
+            TripPhaseType::Delivering(b) => format!("unloading a delivery at {}", b),
             TripPhaseType::Aborted => "trip aborted due to some bug".to_string(),
             TripPhaseType::Finished => "trip finished".to_string(),
             TripPhaseType::DelayedStart => "delayed by previous trip taking too long".to_string(),
@@ -1,9 +1,13 @@
 mod analytics;
+mod bikeshare;
+mod emissions;
 mod events;
 mod make;
 mod mechanics;
 mod pandemic;
+mod recorder;
 mod render;
+mod ridehail;
 mod router;
 mod scheduler;
 mod sim;
@@ -11,19 +15,27 @@ mod transit;
 mod trips;
 
 pub use self::analytics::{Analytics, TripPhase};
+pub(crate) use self::bikeshare::BikeShareSimState;
+pub use self::bikeshare::{BikeShareDock, DockID};
+pub use self::emissions::{Emissions, EmissionsReport, FleetMix, Powertrain};
 pub(crate) use self::events::Event;
 pub use self::events::{AlertLocation, TripPhaseType};
 pub use self::make::{
-    BorderSpawnOverTime, IndividTrip, OffMapLocation, OriginDestination, PersonSpec, Scenario,
-    ScenarioGenerator, ScenarioModifier, SimFlags, SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
+    BorderSpawnOverTime, IndividTrip, ModeChoice, OffMapLocation, OriginDestination, PersonSpec,
+    Scenario, ScenarioGenerator, ScenarioModifier, SimFlags, SpawnOverTime, SpawnTrip, TripSpawner,
+    TripSpec,
 };
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
 pub(crate) use self::pandemic::PandemicModel;
+pub use self::recorder::analytics_from_event_log;
+pub(crate) use self::recorder::EventRecorder;
+pub(crate) use self::ridehail::{RideHailSimState, DEFAULT_FLEET_SIZE};
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{AgentProperties, AlertHandler, Sim, SimCallback, SimOptions};
+pub use self::transit::DwellTime;
 pub(crate) use self::transit::TransitSimState;
 pub use self::trips::{Person, PersonState, TripInfo, TripResult};
 pub use self::trips::{TripEndpoint, TripMode};
@@ -48,7 +60,13 @@ pub const MIN_CAR_LENGTH: Distance = Distance::const_meters(4.5);
 pub const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub const BUS_LENGTH: Distance = Distance::const_meters(12.5);
+// Box trucks and vans making deliveries. These don't fit in on-street parking spots.
+pub const MIN_TRUCK_LENGTH: Distance = Distance::const_meters(7.5);
+pub const MAX_TRUCK_LENGTH: Distance = Distance::const_meters(11.0);
 pub const LIGHT_RAIL_LENGTH: Distance = Distance::const_meters(60.0);
+// Seated and standing passengers
+pub const BUS_CAPACITY: usize = 60;
+pub const LIGHT_RAIL_CAPACITY: usize = 400;
 
 // At all speeds (including at rest), cars must be at least this far apart, measured from front of
 // one car to the back of the other.
@@ -74,6 +92,7 @@ impl fmt::Display for CarID {
             VehicleType::Bus => write!(f, "Bus #{}", self.0),
             VehicleType::Train => write!(f, "Train #{}", self.0),
             VehicleType::Bike => write!(f, "Bike #{}", self.0),
+            VehicleType::Truck => write!(f, "Truck #{}", self.0),
         }
     }
 }
@@ -116,6 +135,8 @@ impl AgentID {
                 VehicleType::Bike => AgentType::Bike,
                 VehicleType::Bus => AgentType::Bus,
                 VehicleType::Train => AgentType::Train,
+                // TODO Count freight separately?
+                VehicleType::Truck => AgentType::Car,
             },
             AgentID::Pedestrian(_) => AgentType::Pedestrian,
             AgentID::BusPassenger(_, _) => AgentType::TransitRider,
@@ -227,6 +248,7 @@ pub enum VehicleType {
     Bus,
     Train,
     Bike,
+    Truck,
 }
 
 impl fmt::Display for VehicleType {
@@ -236,6 +258,7 @@ impl fmt::Display for VehicleType {
             VehicleType::Bus => write!(f, "bus"),
             VehicleType::Train => write!(f, "train"),
             VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Truck => write!(f, "truck"),
         }
     }
 }
@@ -247,6 +270,27 @@ impl VehicleType {
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Train => PathConstraints::Train,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Truck => PathConstraints::Car,
+        }
+    }
+
+    // Comfortable acceleration, in meters per second squared. Only the car-following model uses
+    // this.
+    pub fn default_max_accel(self) -> f64 {
+        match self {
+            VehicleType::Car => 1.5,
+            VehicleType::Bus | VehicleType::Truck | VehicleType::Bike => 1.0,
+            VehicleType::Train => 0.8,
+        }
+    }
+
+    // Comfortable braking, in meters per second squared
+    pub fn default_max_decel(self) -> f64 {
+        match self {
+            VehicleType::Car => 2.5,
+            VehicleType::Truck => 2.0,
+            VehicleType::Bus | VehicleType::Bike => 1.5,
+            VehicleType::Train => 1.2,
         }
     }
 }
@@ -258,6 +302,11 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    // How many passengers fit. Only buses and trains have a limit.
+    pub capacity: Option<usize>,
+    // In meters per second squared. Only the car-following model uses these.
+    pub max_accel: f64,
+    pub max_decel: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -265,6 +314,9 @@ pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    pub capacity: Option<usize>,
+    pub max_accel: f64,
+    pub max_decel: f64,
 }
 
 impl VehicleSpec {
@@ -276,6 +328,9 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            capacity: self.capacity,
+            max_accel: self.max_accel,
+            max_decel: self.max_decel,
         }
     }
 }
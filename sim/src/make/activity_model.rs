@@ -0,0 +1,192 @@
+use crate::{
+    IndividTrip, ModeChoice, PersonID, PersonSpec, Scenario, ScenarioGenerator, SpawnTrip,
+    TripEndpoint,
+};
+use abstutil::{fork_rng, Timer};
+use geom::{Duration, Time};
+use map_model::{BuildingID, BuildingType, Map};
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use rand_xorshift::XorShiftRng;
+
+// How many residents have a job to go to. Everybody else just runs errands.
+const EMPLOYMENT_RATE: f64 = 0.65;
+// Errands happen nearby; pick the closest of this many random candidates.
+const ERRAND_CANDIDATES: usize = 5;
+
+impl ScenarioGenerator {
+    // Synthesizes a weekday for everybody living in the map, using only building types and
+    // amenities from OSM. Workers go home -> work -> (maybe an errand) -> home; everybody else
+    // runs one or two errands in the middle of the day. Each person picks one mode for the whole
+    // day using the default ModeChoice model.
+    pub fn activity_model(map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) -> Scenario {
+        let mut residences: Vec<(BuildingID, usize)> = Vec::new();
+        // Buildings with more amenities attract more workers and errands
+        let mut workplaces: Vec<(BuildingID, usize)> = Vec::new();
+        let mut errands: Vec<(BuildingID, usize)> = Vec::new();
+        let mut total_ppl = 0;
+        for b in map.all_buildings() {
+            match b.bldg_type {
+                BuildingType::Residential(num_ppl) => {
+                    residences.push((b.id, num_ppl));
+                    total_ppl += num_ppl;
+                }
+                BuildingType::ResidentialCommercial(num_ppl) => {
+                    residences.push((b.id, num_ppl));
+                    total_ppl += num_ppl;
+                    workplaces.push((b.id, 1 + b.amenities.len()));
+                }
+                BuildingType::Commercial => {
+                    workplaces.push((b.id, 1 + b.amenities.len()));
+                }
+                BuildingType::Empty => {}
+            }
+            if !b.amenities.is_empty() {
+                errands.push((b.id, b.amenities.len()));
+            }
+        }
+        let pick_work = WeightedIndex::new(workplaces.iter().map(|(_, w)| *w)).ok();
+        let pick_errand = WeightedIndex::new(errands.iter().map(|(_, w)| *w)).ok();
+        let mode_choice = ModeChoice::new();
+
+        // Schedules are cheap to draw, but choosing a mode and creating trips needs pathfinding.
+        // Give each person their own RNG, so that part can run in parallel.
+        let mut days: Vec<(BuildingID, Vec<(Time, BuildingID)>, XorShiftRng)> = Vec::new();
+        timer.start_iter("schedule people", total_ppl);
+        for (home, num_ppl) in residences {
+            for _ in 0..num_ppl {
+                timer.next();
+
+                // Build the day as a list of (departure time, destination)
+                let mut schedule: Vec<(Time, BuildingID)> = Vec::new();
+                let employed = pick_work.is_some() && rng.gen_bool(EMPLOYMENT_RATE);
+                if employed {
+                    let work = workplaces[pick_work.as_ref().unwrap().sample(rng)].0;
+                    if work == home {
+                        continue;
+                    }
+                    let depart_am = normal_time(rng, 8.0, 1.0, 5.0, 11.0);
+                    schedule.push((depart_am, work));
+                    let depart_pm =
+                        depart_am + normal_duration(rng, 8.5 * 60.0, 60.0, 4.0 * 60.0, 11.0 * 60.0);
+                    match pick_errand {
+                        Some(ref pick) if rng.gen_bool(0.35) => {
+                            let errand = nearby_errand(work, &errands, pick, map, rng);
+                            schedule.push((depart_pm, errand));
+                            schedule.push((
+                                depart_pm + normal_duration(rng, 45.0, 20.0, 10.0, 120.0),
+                                home,
+                            ));
+                        }
+                        _ => {
+                            schedule.push((depart_pm, home));
+                        }
+                    }
+                } else if let Some(ref pick) = pick_errand {
+                    let mut depart = normal_time(rng, 11.5, 2.5, 7.0, 20.0);
+                    let mut at = home;
+                    let num_errands = if rng.gen_bool(0.3) { 2 } else { 1 };
+                    for _ in 0..num_errands {
+                        let errand = nearby_errand(at, &errands, pick, map, rng);
+                        if errand == at {
+                            continue;
+                        }
+                        schedule.push((depart, errand));
+                        depart += normal_duration(rng, 60.0, 30.0, 10.0, 180.0);
+                        at = errand;
+                    }
+                    if at == home {
+                        continue;
+                    }
+                    schedule.push((depart, home));
+                } else {
+                    continue;
+                }
+
+                // Nobody goes out past midnight
+                if schedule.last().unwrap().0 >= Time::START_OF_DAY + Duration::hours(24) {
+                    continue;
+                }
+                days.push((home, schedule, fork_rng(rng)));
+            }
+        }
+
+        let mode_choice = &mode_choice;
+        let people = timer.parallelize("choose modes", days, |(home, schedule, mut rng)| {
+            make_trips(home, schedule, mode_choice, map, &mut rng)
+        });
+
+        let mut s = Scenario::empty(map, "activity_model");
+        s.only_seed_buses = None;
+        for trips in people.into_iter().flatten() {
+            s.people.push(PersonSpec {
+                id: PersonID(s.people.len()),
+                orig_id: None,
+                trips,
+            });
+        }
+        s
+    }
+}
+
+// Picks one mode for the whole day. None if no mode works or any trip can't be created.
+fn make_trips(
+    home: BuildingID,
+    schedule: Vec<(Time, BuildingID)>,
+    mode_choice: &ModeChoice,
+    map: &Map,
+    rng: &mut XorShiftRng,
+) -> Option<Vec<IndividTrip>> {
+    let mut endpoints = Vec::new();
+    let mut from = home;
+    for (_, to) in &schedule {
+        endpoints.push((TripEndpoint::Bldg(from), TripEndpoint::Bldg(*to)));
+        from = *to;
+    }
+    let mode = mode_choice.choose(&endpoints, map, rng)?;
+
+    // If somebody's still travelling when their next trip is scheduled, the sim delays it until
+    // they arrive.
+    let mut trips = Vec::new();
+    for ((depart, _), (from, to)) in schedule.into_iter().zip(endpoints.into_iter()) {
+        trips.push(IndividTrip::new(
+            depart,
+            SpawnTrip::new(from, to, mode, map)?,
+        ));
+    }
+    Some(trips)
+}
+
+// Of a few weighted random candidates, the one closest to where the person already is
+fn nearby_errand(
+    near: BuildingID,
+    errands: &[(BuildingID, usize)],
+    pick: &WeightedIndex<usize>,
+    map: &Map,
+    rng: &mut XorShiftRng,
+) -> BuildingID {
+    let pt = map.get_b(near).label_center;
+    (0..ERRAND_CANDIDATES)
+        .map(|_| errands[pick.sample(rng)].0)
+        .min_by_key(|b| map.get_b(*b).label_center.dist_to(pt))
+        .unwrap()
+}
+
+// Hours since midnight, normally distributed and clamped to [low, high]
+fn normal_time(rng: &mut XorShiftRng, mean: f64, std_dev: f64, low: f64, high: f64) -> Time {
+    let hours = Normal::new(mean, std_dev).unwrap().sample(rng);
+    Time::START_OF_DAY + Duration::seconds(3600.0 * hours.max(low).min(high))
+}
+
+// Minutes, normally distributed and clamped to [low, high]
+fn normal_duration(
+    rng: &mut XorShiftRng,
+    mean: f64,
+    std_dev: f64,
+    low: f64,
+    high: f64,
+) -> Duration {
+    let mins = Normal::new(mean, std_dev).unwrap().sample(rng);
+    Duration::minutes(1) * mins.max(low).min(high)
+}
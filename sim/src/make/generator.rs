@@ -1,5 +1,5 @@
 use crate::{
-    DrivingGoal, IndividTrip, PersonID, PersonSpec, Scenario, SidewalkSpot, SpawnTrip,
+    DrivingGoal, IndividTrip, ModeChoice, PersonID, PersonSpec, Scenario, SidewalkSpot, SpawnTrip,
     TripEndpoint, TripMode,
 };
 use abstutil::Timer;
@@ -34,6 +34,9 @@ pub struct SpawnOverTime {
     pub percent_driving: f64,
     pub percent_biking: f64,
     pub percent_use_transit: f64,
+    // If set, ignore the percentages and pick modes by comparing travel time and cost
+    #[serde(default)]
+    pub mode_choice: Option<ModeChoice>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -103,6 +106,7 @@ impl ScenarioGenerator {
                 percent_driving: 0.5,
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                mode_choice: None,
             }],
             // If there are no sidewalks/driving lanes at a border, scenario instantiation will
             // just warn and skip them.
@@ -130,6 +134,7 @@ impl ScenarioGenerator {
                 percent_driving: 0.5,
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                mode_choice: None,
             });
         }
         s
@@ -157,6 +162,7 @@ impl ScenarioGenerator {
                 percent_driving: 0.5,
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                mode_choice: None,
             }],
             border_spawn_over_time: Vec::new(),
         }
@@ -177,6 +183,24 @@ impl SpawnOverTime {
         let from_bldg = map.all_buildings().choose(rng).unwrap().id;
         let id = PersonID(scenario.people.len());
 
+        if let Some(ref model) = self.mode_choice {
+            let from = TripEndpoint::Bldg(from_bldg);
+            let to = self.goal.pick_endpoint(map, rng);
+            if let Some(trip) = model
+                .choose(&[(from.clone(), to.clone())], map, rng)
+                .and_then(|mode| SpawnTrip::new(from, to, mode, map))
+            {
+                scenario.people.push(PersonSpec {
+                    id,
+                    orig_id: None,
+                    trips: vec![IndividTrip::new(depart, trip)],
+                });
+            } else {
+                timer.warn(format!("Couldn't fulfill {:?} at all", self));
+            }
+            return;
+        }
+
         if rng.gen_bool(self.percent_driving) {
             if let Some(goal) = self
                 .goal
@@ -373,6 +397,16 @@ impl OriginDestination {
         }
     }
 
+    fn pick_endpoint(&self, map: &Map, rng: &mut XorShiftRng) -> TripEndpoint {
+        match self {
+            OriginDestination::Anywhere => {
+                TripEndpoint::Bldg(map.all_buildings().choose(rng).unwrap().id)
+            }
+            OriginDestination::GotoBldg(b) => TripEndpoint::Bldg(*b),
+            OriginDestination::EndOfRoad(dr) => TripEndpoint::Border(dr.dst_i(map), None),
+        }
+    }
+
     fn pick_walking_goal(
         &self,
         map: &Map,
@@ -1,5 +1,6 @@
-use crate::{AlertHandler, Scenario, Sim, SimOptions};
+use crate::{AlertHandler, DwellTime, Scenario, Sim, SimOptions, DEFAULT_FLEET_SIZE};
 use abstutil::CmdArgs;
+use geom::Duration;
 use map_model::{Map, MapEdits};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
@@ -47,6 +48,37 @@ impl SimFlags {
                     })
                     .unwrap_or(AlertHandler::Print),
                 pathfinding_upfront: args.enabled("--pathfinding_upfront"),
+                record_events: args.optional("--record_events"),
+                savestate_analytics: args.enabled("--savestate_analytics"),
+                ride_hail_fleet: args
+                    .optional_parse("--ride_hail_fleet", |s| s.parse())
+                    .unwrap_or(DEFAULT_FLEET_SIZE),
+                transit_dwell: {
+                    let mut dwell = if args.enabled("--passenger_dwell") {
+                        DwellTime::per_passenger()
+                    } else {
+                        DwellTime::new()
+                    };
+                    if let Some(secs) = args.optional_parse("--dwell_per_boarding", |s| s.parse()) {
+                        dwell.per_boarding = Duration::seconds(secs);
+                    }
+                    if let Some(secs) = args.optional_parse("--dwell_per_alighting", |s| s.parse())
+                    {
+                        dwell.per_alighting = Duration::seconds(secs);
+                    }
+                    if let Some(n) = args.optional_parse("--bus_doors", |s| s.parse()) {
+                        dwell.bus_doors = n;
+                    }
+                    if let Some(n) = args.optional_parse("--train_doors", |s| s.parse()) {
+                        dwell.train_doors = n;
+                    }
+                    dwell
+                },
+                reroute_blocked_after: args
+                    .optional_parse("--reroute_blocked_after", |s| s.parse())
+                    .map(Duration::seconds),
+                car_following: args.enabled("--car_following"),
+                mid_block_lanechanging: args.enabled("--mid_block_lanechanging"),
             },
         }
     }
@@ -77,14 +109,15 @@ impl SimFlags {
         if self.load.starts_with(&abstutil::path("player/saves/")) {
             timer.note(format!("Resuming from {}", self.load));
 
-            let mut sim: Sim = abstutil::read_binary(self.load.clone(), timer);
+            let mut sim: Sim = abstutil::read_versioned_binary(self.load.clone(), timer);
 
             let mut map = Map::new(abstutil::path_map(&sim.map_name), timer);
             if sim.edits_name != "untitled edits" {
                 map.must_apply_edits(MapEdits::load(&map, &sim.edits_name, timer).unwrap(), timer);
                 map.recalculate_pathfinding_after_edits(timer);
             }
-            sim.restore_paths(&map, timer);
+            sim.restore_paths(&map, timer)
+                .unwrap_or_else(|err| panic!("Couldn't restore {}: {}", self.load, err));
 
             (map, sim, rng)
         } else if self.load.starts_with(&abstutil::path("system/scenarios/")) {
@@ -93,7 +126,7 @@ impl SimFlags {
                 self.load
             ));
 
-            let scenario: Scenario = abstutil::read_binary(self.load.clone(), timer);
+            let scenario: Scenario = abstutil::read_versioned_binary(self.load.clone(), timer);
 
             let map = Map::new(abstutil::path_map(&scenario.map_name), timer);
 
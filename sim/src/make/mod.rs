@@ -1,5 +1,7 @@
+mod activity_model;
 mod generator;
 mod load;
+mod mode_choice;
 mod modifier;
 mod scenario;
 mod spawner;
@@ -8,6 +10,7 @@ pub use self::generator::{
     BorderSpawnOverTime, OriginDestination, ScenarioGenerator, SpawnOverTime,
 };
 pub use self::load::SimFlags;
+pub use self::mode_choice::ModeChoice;
 pub use self::modifier::ScenarioModifier;
 pub use self::scenario::{IndividTrip, OffMapLocation, PersonSpec, Scenario, SpawnTrip};
 pub use self::spawner::{TripSpawner, TripSpec};
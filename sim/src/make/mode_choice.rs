@@ -0,0 +1,262 @@
+use crate::{DrivingGoal, TripEndpoint, TripMode};
+use geom::{Distance, Duration, Speed};
+use map_model::{
+    BuildingID, BusRouteID, Map, Path, PathConstraints, PathRequest, Position, Traversable,
+};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+// Rough average speeds, including stops along the way
+const WALKING_SPEED: Speed = Speed::const_meters_per_second(1.2);
+const BIKING_SPEED: Speed = Speed::const_meters_per_second(4.0);
+
+// A multinomial logit mode choice model. For each trip, estimate the travel time and cost by every
+// mode, turn those into a utility, then pick a mode randomly, weighted by exp(utility). Better
+// options get picked more often, but not always.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
+pub struct ModeChoice {
+    // Utility per minute of travel time. Should be negative.
+    pub per_minute: f64,
+    // Utility per dollar spent. Should be negative.
+    pub per_dollar: f64,
+    pub driving_cost_per_mile: f64,
+    pub transit_fare: f64,
+    // Extra utility for driving to a building without off-street parking or any on-street parking
+    // along its road. Should be negative.
+    pub no_parking: f64,
+    // Everything about a mode besides time and cost. Only these modes are considered.
+    pub constants: BTreeMap<TripMode, f64>,
+}
+
+// By construction, there are no NaNs. Needed for ScenarioModifier.
+impl Eq for ModeChoice {}
+impl Ord for ModeChoice {
+    fn cmp(&self, other: &ModeChoice) -> Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+impl ModeChoice {
+    pub fn new() -> ModeChoice {
+        let mut constants = BTreeMap::new();
+        constants.insert(TripMode::Walk, 0.0);
+        constants.insert(TripMode::Bike, -1.5);
+        constants.insert(TripMode::Transit, -0.5);
+        constants.insert(TripMode::Drive, 0.5);
+        ModeChoice {
+            per_minute: -0.05,
+            per_dollar: -0.3,
+            driving_cost_per_mile: 0.6,
+            transit_fare: 2.75,
+            no_parking: -1.0,
+            constants,
+        }
+    }
+
+    // Pick one mode for all of these trips, so nobody leaves their car or bike behind. Only modes
+    // that work for every trip are considered. None if there aren't any.
+    pub fn choose(
+        &self,
+        trips: &[(TripEndpoint, TripEndpoint)],
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Option<TripMode> {
+        let mut choices: Vec<(TripMode, f64)> = Vec::new();
+        for (mode, constant) in &self.constants {
+            let mut total = 0.0;
+            let mut possible = true;
+            for (from, to) in trips {
+                if let Some(utility) = self.utility(from, to, *mode, map) {
+                    total += constant + utility;
+                } else {
+                    possible = false;
+                    break;
+                }
+            }
+            if possible {
+                choices.push((*mode, total));
+            }
+        }
+        if choices.is_empty() {
+            return None;
+        }
+
+        // Subtract the best utility before exponentiating, so long trips don't underflow to 0
+        let best = choices
+            .iter()
+            .map(|(_, u)| *u)
+            .fold(std::f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = choices.iter().map(|(_, u)| (u - best).exp()).collect();
+        let mut pick = rng.gen_range(0.0, weights.iter().sum::<f64>());
+        for ((mode, _), weight) in choices.iter().zip(weights.iter()) {
+            if pick < *weight {
+                return Some(*mode);
+            }
+            pick -= weight;
+        }
+        Some(choices.last().unwrap().0)
+    }
+
+    // The utility of one trip by some mode, not including the mode's constant. None if the mode
+    // can't be used.
+    pub fn utility(
+        &self,
+        from: &TripEndpoint,
+        to: &TripEndpoint,
+        mode: TripMode,
+        map: &Map,
+    ) -> Option<f64> {
+        // Time, cost, and anything else affecting the utility
+        let (time, cost, extra) = match mode {
+            TripMode::Walk => {
+                let path = map.pathfind(PathRequest {
+                    start: from.start_sidewalk_spot(map)?.sidewalk_pos,
+                    end: to.end_sidewalk_spot(map)?.sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                })?;
+                (path.total_length() / WALKING_SPEED, 0.0, 0.0)
+            }
+            TripMode::Bike => {
+                let path = vehicle_path(from, to, PathConstraints::Bike, map)?;
+                // Time spent riding in traffic feels longer
+                let time = path.total_length() * bike_discomfort(&path, map) / BIKING_SPEED;
+                (time, 0.0, 0.0)
+            }
+            TripMode::Drive => {
+                let path = vehicle_path(from, to, PathConstraints::Car, map)?;
+                let cost =
+                    self.driving_cost_per_mile * (path.total_length() / Distance::miles(1.0));
+                let extra = match to {
+                    TripEndpoint::Bldg(b) if !parking_near(*b, map) => self.no_parking,
+                    _ => 0.0,
+                };
+                (free_flow_time(&path, map), cost, extra)
+            }
+            TripMode::Transit => {
+                let start = from.start_sidewalk_spot(map)?.sidewalk_pos;
+                let end = to.end_sidewalk_spot(map)?.sidewalk_pos;
+                let (stop1, stop2, route) = map.should_use_transit(start, end)?;
+                let walk1 = walk_dist(start, map.get_bs(stop1).sidewalk_pos, map)?;
+                let walk2 = walk_dist(map.get_bs(stop2).sidewalk_pos, end, map)?;
+                let ride = map.pathfind(PathRequest {
+                    start: map.get_bs(stop1).driving_pos,
+                    end: map.get_bs(stop2).driving_pos,
+                    constraints: map.get_br(route).route_type,
+                })?;
+                let time = (walk1 + walk2) / WALKING_SPEED
+                    + average_wait(route, map)
+                    + free_flow_time(&ride, map);
+                (time, self.transit_fare, 0.0)
+            }
+            // TODO Not modeled yet. Would need some idea of the fleet size.
+            TripMode::RideHail => {
+                return None;
+            }
+        };
+        Some(self.per_minute * time.inner_seconds() / 60.0 + self.per_dollar * cost + extra)
+    }
+}
+
+fn vehicle_path(
+    from: &TripEndpoint,
+    to: &TripEndpoint,
+    constraints: PathConstraints,
+    map: &Map,
+) -> Option<Path> {
+    let start = match from {
+        TripEndpoint::Bldg(b) => DrivingGoal::ParkNear(*b).goal_pos(constraints, map),
+        TripEndpoint::Border(i, _) => {
+            let l = *map
+                .get_i(*i)
+                .some_outgoing_road(map)?
+                .lanes(constraints, map)
+                .get(0)?;
+            Position::new(l, Distance::ZERO)
+        }
+    };
+    let end = to
+        .driving_goal(constraints, map)?
+        .goal_pos(constraints, map);
+    map.pathfind(PathRequest {
+        start,
+        end,
+        constraints,
+    })
+}
+
+// Weights each lane like the bike pathfinding cost does, from 1 on bike lanes up to 1.5 when
+// sharing a driving lane, and averages that over the path
+fn bike_discomfort(path: &Path, map: &Map) -> f64 {
+    let mut total = Distance::ZERO;
+    let mut weighted = Distance::ZERO;
+    for step in path.get_steps() {
+        let (dist, weight) = match step.as_traversable() {
+            Traversable::Lane(l) => {
+                let lane = map.get_l(l);
+                let weight = if lane.is_biking() {
+                    1.0
+                } else if lane.is_bus() {
+                    1.1
+                } else {
+                    1.5
+                };
+                (lane.length(), weight)
+            }
+            Traversable::Turn(t) => (map.get_t(t).geom.length(), 1.0),
+        };
+        total += dist;
+        weighted += dist * weight;
+    }
+    if total == Distance::ZERO {
+        1.0
+    } else {
+        weighted / total
+    }
+}
+
+fn walk_dist(start: Position, end: Position, map: &Map) -> Option<Distance> {
+    if start.lane() == end.lane() {
+        return Some(if start.dist_along() > end.dist_along() {
+            start.dist_along() - end.dist_along()
+        } else {
+            end.dist_along() - start.dist_along()
+        });
+    }
+    map.pathfind(PathRequest {
+        start,
+        end,
+        constraints: PathConstraints::Pedestrian,
+    })
+    .map(|path| path.total_length())
+}
+
+fn free_flow_time(path: &Path, map: &Map) -> Duration {
+    let mut time = Duration::ZERO;
+    for step in path.get_steps() {
+        let t = step.as_traversable();
+        time += t.length(map) / t.speed_limit(map);
+    }
+    time
+}
+
+// Half of the average time between departures
+fn average_wait(route: BusRouteID, map: &Map) -> Duration {
+    let times = &map.get_br(route).spawn_times;
+    if times.len() < 2 {
+        return Duration::hours(1);
+    }
+    (*times.last().unwrap() - times[0]) / (2.0 * (times.len() - 1) as f64)
+}
+
+fn parking_near(b: BuildingID, map: &Map) -> bool {
+    let bldg = map.get_b(b);
+    bldg.parking.is_some()
+        || map
+            .get_parent(bldg.sidewalk())
+            .all_lanes()
+            .into_iter()
+            .any(|l| map.get_l(l).is_parking())
+}
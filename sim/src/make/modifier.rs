@@ -1,11 +1,18 @@
-use crate::{IndividTrip, Scenario, SpawnTrip, TripMode};
+use crate::{
+    BikeShareDock, DrivingGoal, IndividTrip, ModeChoice, PersonID, PersonSpec, Scenario,
+    SidewalkPOI, SidewalkSpot, SpawnTrip, TripMode,
+};
 use geom::{Duration, Time};
-use map_model::Map;
+use map_model::{
+    BuildingID, BuildingType, BusStopID, DirectedRoadID, LaneID, Map, PathConstraints, Position,
+};
+use rand::seq::SliceRandom;
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum ScenarioModifier {
     RepeatDays(usize),
     CancelPeople(usize),
@@ -15,6 +22,24 @@ pub enum ScenarioModifier {
         departure_filter: (Time, Time),
         from_modes: BTreeSet<TripMode>,
     },
+    AddDeliveries {
+        num_tours: usize,
+        stops_per_tour: usize,
+    },
+    ParkAndRide {
+        pct_ppl: usize,
+    },
+    BikeShare {
+        pct_ppl: usize,
+        dock_capacity: usize,
+    },
+    // Everybody picks a new mode for their trips of these types leaving in this window, by
+    // comparing travel time and cost.
+    ChooseModes {
+        model: ModeChoice,
+        departure_filter: (Time, Time),
+        from_modes: BTreeSet<TripMode>,
+    },
 }
 
 impl ScenarioModifier {
@@ -52,6 +77,20 @@ impl ScenarioModifier {
                 }
                 s
             }
+            ScenarioModifier::AddDeliveries {
+                num_tours,
+                stops_per_tour,
+            } => add_deliveries(s, *num_tours, *stops_per_tour, map, rng),
+            ScenarioModifier::ParkAndRide { pct_ppl } => park_and_ride(s, *pct_ppl, map, rng),
+            ScenarioModifier::BikeShare {
+                pct_ppl,
+                dock_capacity,
+            } => bike_share(s, *pct_ppl, *dock_capacity, map, rng),
+            ScenarioModifier::ChooseModes {
+                model,
+                departure_filter,
+                from_modes,
+            } => choose_modes(s, model, *departure_filter, from_modes, map, rng),
         }
     }
 
@@ -74,6 +113,36 @@ impl ScenarioModifier {
                 departure_filter.1.ampm_tostring(),
                 to_mode.verb()
             ),
+            ScenarioModifier::AddDeliveries {
+                num_tours,
+                stops_per_tour,
+            } => format!(
+                "add {} delivery trucks, each stopping at {} businesses",
+                num_tours, stops_per_tour
+            ),
+            ScenarioModifier::ParkAndRide { pct_ppl } => format!(
+                "{}% of people driving somewhere and back will park-and-ride instead",
+                pct_ppl
+            ),
+            ScenarioModifier::BikeShare {
+                pct_ppl,
+                dock_capacity,
+            } => format!(
+                "{}% of people walking or taking transit will use bike share, with docks holding \
+                 {} bikes",
+                pct_ppl, dock_capacity
+            ),
+            ScenarioModifier::ChooseModes {
+                departure_filter,
+                from_modes,
+                ..
+            } => format!(
+                "people pick a new mode for trips of types {:?} leaving between {} and {}, based \
+                 on travel time and cost",
+                from_modes,
+                departure_filter.0.ampm_tostring(),
+                departure_filter.1.ampm_tostring()
+            ),
         }
     }
 }
@@ -118,3 +187,290 @@ fn cancel_people(mut s: Scenario, pct: usize, rng: &mut XorShiftRng) -> Scenario
     }
     s
 }
+
+// Each truck enters and leaves from random borders, visiting random commercial buildings in
+// between. The stops are ordered greedily by distance, nothing smarter.
+fn add_deliveries(
+    mut s: Scenario,
+    num_tours: usize,
+    stops_per_tour: usize,
+    map: &Map,
+    rng: &mut XorShiftRng,
+) -> Scenario {
+    if stops_per_tour == 0 {
+        return s;
+    }
+    let businesses: Vec<BuildingID> = map
+        .all_buildings()
+        .iter()
+        .filter(|b| match b.bldg_type {
+            BuildingType::Commercial | BuildingType::ResidentialCommercial(_) => true,
+            BuildingType::Residential(_) | BuildingType::Empty => false,
+        })
+        .map(|b| b.id)
+        .collect();
+    let starts: Vec<DirectedRoadID> = map
+        .all_incoming_borders()
+        .into_iter()
+        .filter_map(|i| i.some_outgoing_road(map))
+        .filter(|dr| !dr.lanes(PathConstraints::Car, map).is_empty())
+        .collect();
+    let goals: Vec<DrivingGoal> = map
+        .all_outgoing_borders()
+        .into_iter()
+        .filter_map(|i| {
+            DrivingGoal::end_at_border(i.some_incoming_road(map)?, PathConstraints::Car, None, map)
+        })
+        .collect();
+    if businesses.is_empty() || starts.is_empty() || goals.is_empty() {
+        println!("No businesses or borders for delivery trucks to use; not adding any");
+        return s;
+    }
+
+    for _ in 0..num_tours {
+        let dr = *starts.choose(rng).unwrap();
+        let mut remaining: Vec<BuildingID> = businesses
+            .choose_multiple(rng, stops_per_tour)
+            .cloned()
+            .collect();
+        let mut at = map.get_i(dr.src_i(map)).polygon.center();
+        let mut stops = Vec::new();
+        while !remaining.is_empty() {
+            let idx = (0..remaining.len())
+                .min_by_key(|idx| map.get_b(remaining[*idx]).polygon.center().dist_to(at))
+                .unwrap();
+            let b = remaining.remove(idx);
+            at = map.get_b(b).polygon.center();
+            // Time spent double-parked, unloading
+            stops.push((b, Duration::seconds(rng.gen_range(180.0, 600.0))));
+        }
+        // During business hours
+        let depart = Time::START_OF_DAY
+            + Duration::hours(6)
+            + Duration::seconds(rng.gen_range(0.0, Duration::hours(12).inner_seconds()));
+
+        let mut trip = IndividTrip::new(
+            depart,
+            SpawnTrip::DeliveryTour {
+                dr,
+                stops,
+                goal: goals.choose(rng).unwrap().clone(),
+                origin: None,
+            },
+        );
+        trip.modified = true;
+        s.people.push(PersonSpec {
+            id: PersonID(s.people.len()),
+            orig_id: None,
+            trips: vec![trip],
+        });
+    }
+    s
+}
+
+// Look for people driving from A to B, then B back to A. Instead, they'll drive from A and park
+// near the first bus stop that transit routing from A to B would use, then ride the rest of the
+// way. Later, they'll ride transit back to the car.
+fn park_and_ride(mut s: Scenario, pct: usize, map: &Map, rng: &mut XorShiftRng) -> Scenario {
+    let pct = (pct as f64) / 100.0;
+    for person in &mut s.people {
+        if !rng.gen_bool(pct) {
+            continue;
+        }
+        for idx in 0..person.trips.len().saturating_sub(1) {
+            let (home, work) = match (&person.trips[idx].trip, &person.trips[idx + 1].trip) {
+                (
+                    SpawnTrip::UsingParkedCar(b1, DrivingGoal::ParkNear(b2)),
+                    SpawnTrip::UsingParkedCar(b3, DrivingGoal::ParkNear(b4)),
+                ) if b1 == b4 && b2 == b3 => (*b1, *b2),
+                _ => {
+                    continue;
+                }
+            };
+            let home_pos = map.get_b(home).front_path.sidewalk;
+            let work_pos = map.get_b(work).front_path.sidewalk;
+            let (stop1, stop2, route) = match map.should_use_transit(home_pos, work_pos) {
+                Some(x) => x,
+                None => {
+                    continue;
+                }
+            };
+            let park_near = match bldg_near_stop(stop1, map) {
+                Some(b) => b,
+                None => {
+                    continue;
+                }
+            };
+            let (return_stop1, return_stop2, return_route) =
+                match map.should_use_transit(work_pos, map.get_bs(stop1).sidewalk_pos) {
+                    Some(x) => x,
+                    None => {
+                        continue;
+                    }
+                };
+
+            person.trips[idx].trip = SpawnTrip::ParkAndRide {
+                start: home,
+                park_near,
+                route,
+                stop1,
+                stop2,
+                goal: SidewalkSpot::building(work, map),
+            };
+            person.trips[idx].modified = true;
+            person.trips[idx + 1].trip = SpawnTrip::ReturnFromParkAndRide {
+                start: SidewalkSpot::building(work, map),
+                route: return_route,
+                stop1: return_stop1,
+                stop2: return_stop2,
+                parked_near: park_near,
+                goal: home,
+            };
+            person.trips[idx + 1].modified = true;
+        }
+    }
+    s
+}
+
+// The closest building along the same sidewalk as the bus stop
+fn bldg_near_stop(stop: BusStopID, map: &Map) -> Option<BuildingID> {
+    let stop_pos = map.get_bs(stop).sidewalk_pos;
+    map.get_l(stop_pos.lane())
+        .building_paths
+        .iter()
+        .min_by_key(|b| {
+            let dist = map.get_b(**b).front_path.sidewalk.dist_along();
+            if dist > stop_pos.dist_along() {
+                dist - stop_pos.dist_along()
+            } else {
+                stop_pos.dist_along() - dist
+            }
+        })
+        .cloned()
+}
+
+// People walking or taking transit between buildings switch to bike share. Docks are placed
+// halfway along the sidewalk in front of every building they start or end at, starting half-full.
+fn bike_share(
+    mut s: Scenario,
+    pct: usize,
+    dock_capacity: usize,
+    map: &Map,
+    rng: &mut XorShiftRng,
+) -> Scenario {
+    let pct = (pct as f64) / 100.0;
+    let mut has_dock: BTreeSet<LaneID> = s
+        .bike_share_docks
+        .iter()
+        .map(|d| d.sidewalk_pos.lane())
+        .collect();
+    for person in &mut s.people {
+        if !rng.gen_bool(pct) {
+            continue;
+        }
+        for trip in &mut person.trips {
+            let (b1, b2) = match trip.trip {
+                SpawnTrip::JustWalking(ref from, ref to)
+                | SpawnTrip::UsingTransit(ref from, ref to, _, _, _) => {
+                    match (&from.connection, &to.connection) {
+                        (SidewalkPOI::Building(b1), SidewalkPOI::Building(b2)) if b1 != b2 => {
+                            (*b1, *b2)
+                        }
+                        _ => {
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    continue;
+                }
+            };
+            for b in &[b1, b2] {
+                let sidewalk = map.get_b(*b).sidewalk();
+                if has_dock.contains(&sidewalk)
+                    || map
+                        .get_parent(sidewalk)
+                        .sidewalk_to_bike(sidewalk)
+                        .is_none()
+                {
+                    continue;
+                }
+                has_dock.insert(sidewalk);
+                s.bike_share_docks.push(BikeShareDock {
+                    sidewalk_pos: Position::new(sidewalk, map.get_l(sidewalk).length() / 2.0),
+                    capacity: dock_capacity,
+                    initial_bikes: dock_capacity / 2,
+                });
+            }
+            trip.trip = SpawnTrip::UsingBikeShare(b1, b2);
+            trip.modified = true;
+        }
+    }
+    s
+}
+
+// All of a person's matching trips switch to the same mode, so a car or bike doesn't get left
+// somewhere.
+fn choose_modes(
+    mut s: Scenario,
+    model: &ModeChoice,
+    departure_filter: (Time, Time),
+    from_modes: &BTreeSet<TripMode>,
+    map: &Map,
+    rng: &mut XorShiftRng,
+) -> Scenario {
+    for person in &mut s.people {
+        let indices: Vec<usize> = person
+            .trips
+            .iter()
+            .enumerate()
+            .filter(|(_, trip)| {
+                trip.depart >= departure_filter.0
+                    && trip.depart <= departure_filter.1
+                    && from_modes.contains(&trip.trip.mode())
+                    && !planned_as_a_whole(&trip.trip)
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        if indices.is_empty() {
+            continue;
+        }
+        let endpoints: Vec<_> = indices
+            .iter()
+            .map(|idx| {
+                (
+                    person.trips[*idx].trip.start(map),
+                    person.trips[*idx].trip.end(map),
+                )
+            })
+            .collect();
+        let mode = match model.choose(&endpoints, map, rng) {
+            Some(mode) => mode,
+            None => {
+                continue;
+            }
+        };
+        for (idx, (from, to)) in indices.into_iter().zip(endpoints) {
+            let trip = &mut person.trips[idx];
+            if trip.trip.mode() == mode {
+                continue;
+            }
+            if let Some(new) = SpawnTrip::new(from, to, mode, map) {
+                trip.modified = true;
+                trip.trip = new;
+            }
+        }
+    }
+    s
+}
+
+// Delivery tours and the two halves of park-and-ride are more than a mode between two endpoints, so
+// replacing them would lose the stops or strand the parked car.
+fn planned_as_a_whole(trip: &SpawnTrip) -> bool {
+    match trip {
+        SpawnTrip::DeliveryTour { .. }
+        | SpawnTrip::ParkAndRide { .. }
+        | SpawnTrip::ReturnFromParkAndRide { .. } => true,
+        _ => false,
+    }
+}
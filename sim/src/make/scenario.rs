@@ -1,9 +1,9 @@
 use crate::{
-    CarID, DrivingGoal, OrigPersonID, ParkingSpot, PersonID, SidewalkPOI, SidewalkSpot, Sim,
-    TripEndpoint, TripMode, TripSpec, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH,
-    MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    BikeShareDock, CarID, DrivingGoal, OrigPersonID, ParkingSpot, PersonID, SidewalkPOI,
+    SidewalkSpot, Sim, TripEndpoint, TripMode, TripSpec, Vehicle, VehicleSpec, VehicleType,
+    BIKE_LENGTH, MAX_CAR_LENGTH, MAX_TRUCK_LENGTH, MIN_CAR_LENGTH, MIN_TRUCK_LENGTH,
 };
-use abstutil::{prettyprint_usize, Counter, Timer};
+use abstutil::{prettyprint_usize, Counter, Migration, Timer, Versioned};
 use geom::{Distance, Duration, LonLat, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, DirectedRoadID, Map, PathConstraints, Position, RoadID,
@@ -23,6 +23,7 @@ pub struct Scenario {
     pub people: Vec<PersonSpec>,
     // None means seed all buses. Otherwise the route name must be present here.
     pub only_seed_buses: Option<BTreeSet<String>>,
+    pub bike_share_docks: Vec<BikeShareDock>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -79,6 +80,38 @@ pub enum SpawnTrip {
         trip_time: Duration,
         mode: TripMode,
     },
+    // Added after Remote to keep existing scenario files readable
+    UsingRideHail(BuildingID, BuildingID),
+    // A truck enters from a border, unloads at each building for some time, and leaves.
+    DeliveryTour {
+        dr: DirectedRoadID,
+        stops: Vec<(BuildingID, Duration)>,
+        goal: DrivingGoal,
+        origin: Option<OffMapLocation>,
+    },
+    // Drive from a building and park near the first bus stop, then ride transit and walk the rest
+    // of the way
+    ParkAndRide {
+        start: BuildingID,
+        park_near: BuildingID,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        goal: SidewalkSpot,
+    },
+    // The reverse of ParkAndRide. Walk to a stop, ride transit, walk back to the car parked near
+    // some building, and drive to the goal.
+    ReturnFromParkAndRide {
+        start: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        parked_near: BuildingID,
+        goal: BuildingID,
+    },
+    // Walk to the nearest bike share dock with a bike, ride to a dock near the goal, and walk the
+    // rest of the way
+    UsingBikeShare(BuildingID, BuildingID),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -87,6 +120,31 @@ pub struct OffMapLocation {
     pub gps: LonLat,
 }
 
+impl Versioned for Scenario {
+    const FORMAT: &'static str = "scenario";
+    // 2: Bike share docks
+    const VERSION: u32 = 2;
+
+    fn migration(from_version: u32) -> Option<Migration> {
+        match from_version {
+            // Files from before versioning have the same format as version 1
+            0 => Some(unchanged),
+            1 => Some(add_bike_share_docks),
+            _ => None,
+        }
+    }
+}
+
+fn unchanged(payload: Vec<u8>) -> Result<Vec<u8>, String> {
+    Ok(payload)
+}
+
+// bike_share_docks is the last field, so just append an empty list
+fn add_bike_share_docks(mut payload: Vec<u8>) -> Result<Vec<u8>, String> {
+    payload.extend(bincode::serialize(&Vec::<BikeShareDock>::new()).map_err(|e| e.to_string())?);
+    Ok(payload)
+}
+
 impl Scenario {
     // Any case where map edits could change the calls to the RNG, we have to fork.
     pub fn instantiate(&self, sim: &mut Sim, map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) {
@@ -97,13 +155,22 @@ impl Scenario {
         if let Some(ref routes) = self.only_seed_buses {
             for route in map.all_bus_routes() {
                 if routes.contains(&route.full_name) {
-                    sim.seed_bus_route(route, map, timer);
+                    sim.seed_bus_route(route);
                 }
             }
         } else {
             // All of them
             for route in map.all_bus_routes() {
-                sim.seed_bus_route(route, map, timer);
+                sim.seed_bus_route(route);
+            }
+        }
+
+        for dock in &self.bike_share_docks {
+            if sim.add_bike_share_dock(dock, map).is_none() {
+                timer.warn(format!(
+                    "Can't bike away from {}, skipping that bike share dock",
+                    dock.sidewalk_pos
+                ));
             }
         }
 
@@ -159,7 +226,7 @@ impl Scenario {
     }
 
     pub fn save(&self) {
-        abstutil::write_binary(
+        abstutil::write_versioned_binary(
             abstutil::path_scenario(&self.map_name, &self.scenario_name),
             self,
         );
@@ -171,6 +238,7 @@ impl Scenario {
             map_name: map.get_name().to_string(),
             people: Vec::new(),
             only_seed_buses: Some(BTreeSet::new()),
+            bike_share_docks: Vec::new(),
         }
     }
 
@@ -180,6 +248,26 @@ impl Scenario {
             vehicle_type: VehicleType::Car,
             length,
             max_speed: None,
+            capacity: None,
+            max_accel: VehicleType::Car.default_max_accel(),
+            max_decel: VehicleType::Car.default_max_decel(),
+        }
+    }
+
+    pub fn rand_truck(rng: &mut XorShiftRng) -> VehicleSpec {
+        let length = Scenario::rand_dist(rng, MIN_TRUCK_LENGTH, MAX_TRUCK_LENGTH);
+        let max_speed = Some(Scenario::rand_speed(
+            rng,
+            Speed::miles_per_hour(30.0),
+            Speed::miles_per_hour(40.0),
+        ));
+        VehicleSpec {
+            vehicle_type: VehicleType::Truck,
+            length,
+            max_speed,
+            capacity: None,
+            max_accel: VehicleType::Truck.default_max_accel(),
+            max_decel: VehicleType::Truck.default_max_decel(),
         }
     }
 
@@ -193,6 +281,9 @@ impl Scenario {
             vehicle_type: VehicleType::Bike,
             length: BIKE_LENGTH,
             max_speed,
+            capacity: None,
+            max_accel: VehicleType::Bike.default_max_accel(),
+            max_decel: VehicleType::Bike.default_max_decel(),
         }
     }
 
@@ -392,7 +483,17 @@ impl SpawnTrip {
                     )
                     .choose(rng)
                     // TODO We could be more precise and say exactly what vehicle will be used here
-                    .and_then(|l| TripSpec::spawn_vehicle_at(Position::start(*l), is_bike, map))
+                    .and_then(|l| {
+                        TripSpec::spawn_vehicle_at(
+                            Position::start(*l),
+                            if is_bike {
+                                VehicleType::Bike
+                            } else {
+                                VehicleType::Car
+                            },
+                            map,
+                        )
+                    })
                 {
                     TripSpec::VehicleAppearing {
                         start_pos,
@@ -428,6 +529,67 @@ impl SpawnTrip {
                 stop1,
                 stop2,
             },
+            SpawnTrip::UsingRideHail(start, goal) => TripSpec::UsingRideHail { start, goal },
+            SpawnTrip::DeliveryTour {
+                dr,
+                stops,
+                goal,
+                origin,
+            } => {
+                if let Some(start_pos) =
+                    dr.lanes(PathConstraints::Car, map)
+                        .choose(rng)
+                        .and_then(|l| {
+                            TripSpec::spawn_vehicle_at(Position::start(*l), VehicleType::Truck, map)
+                        })
+                {
+                    TripSpec::DeliveryTour {
+                        start_pos,
+                        stops,
+                        goal,
+                        use_vehicle: use_vehicle.unwrap(),
+                        origin,
+                    }
+                } else {
+                    TripSpec::NoRoomToSpawn {
+                        i: dr.src_i(map),
+                        goal,
+                        use_vehicle: use_vehicle.unwrap(),
+                        origin,
+                    }
+                }
+            }
+            SpawnTrip::ParkAndRide {
+                start,
+                park_near,
+                route,
+                stop1,
+                stop2,
+                goal,
+            } => TripSpec::ParkAndRide {
+                car: use_vehicle.unwrap(),
+                start_bldg: start,
+                park_near,
+                route,
+                stop1,
+                stop2,
+                goal,
+            },
+            SpawnTrip::ReturnFromParkAndRide {
+                start,
+                route,
+                stop1,
+                stop2,
+                goal,
+                ..
+            } => TripSpec::ReturnFromParkAndRide {
+                start,
+                route,
+                stop1,
+                stop2,
+                car: use_vehicle.unwrap(),
+                goal,
+            },
             SpawnTrip::Remote {
                 from,
                 to,
@@ -439,6 +601,11 @@ impl SpawnTrip {
                 trip_time,
                 mode,
             },
+            SpawnTrip::UsingBikeShare(start, goal) => TripSpec::UsingBikeShare {
+                start,
+                goal,
+                bike: use_vehicle.unwrap(),
+            },
         }
     }
 
@@ -460,9 +627,14 @@ impl SpawnTrip {
                 }
             }
             SpawnTrip::UsingParkedCar(_, _) => TripMode::Drive,
-            SpawnTrip::UsingBike(_, _) => TripMode::Bike,
+            SpawnTrip::UsingBike(_, _) | SpawnTrip::UsingBikeShare(_, _) => TripMode::Bike,
             SpawnTrip::JustWalking(_, _) => TripMode::Walk,
             SpawnTrip::UsingTransit(_, _, _, _, _) => TripMode::Transit,
+            SpawnTrip::UsingRideHail(_, _) => TripMode::RideHail,
+            SpawnTrip::DeliveryTour { .. } => TripMode::Drive,
+            SpawnTrip::ParkAndRide { .. } | SpawnTrip::ReturnFromParkAndRide { .. } => {
+                TripMode::Transit
+            }
             // TODO Uh...
             SpawnTrip::Remote { .. } => TripMode::Drive,
         }
@@ -473,21 +645,27 @@ impl SpawnTrip {
             SpawnTrip::VehicleAppearing { ref start, .. } => {
                 TripEndpoint::Border(map.get_l(start.lane()).src_i, None)
             }
-            SpawnTrip::FromBorder { dr, ref origin, .. } => {
+            SpawnTrip::FromBorder { dr, ref origin, .. }
+            | SpawnTrip::DeliveryTour { dr, ref origin, .. } => {
                 TripEndpoint::Border(dr.src_i(map), origin.clone())
             }
             SpawnTrip::UsingParkedCar(b, _) => TripEndpoint::Bldg(*b),
             SpawnTrip::UsingBike(b, _) => TripEndpoint::Bldg(*b),
-            SpawnTrip::JustWalking(ref spot, _) | SpawnTrip::UsingTransit(ref spot, _, _, _, _) => {
-                match spot.connection {
-                    SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
-                    SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
-                    SidewalkPOI::SuddenlyAppear => {
-                        TripEndpoint::Border(map.get_l(spot.sidewalk_pos.lane()).src_i, None)
-                    }
-                    _ => unreachable!(),
+            SpawnTrip::UsingBikeShare(b, _) => TripEndpoint::Bldg(*b),
+            SpawnTrip::UsingRideHail(b, _) => TripEndpoint::Bldg(*b),
+            SpawnTrip::ParkAndRide { start, .. } => TripEndpoint::Bldg(*start),
+            SpawnTrip::JustWalking(ref spot, _)
+            | SpawnTrip::UsingTransit(ref spot, _, _, _, _)
+            | SpawnTrip::ReturnFromParkAndRide {
+                start: ref spot, ..
+            } => match spot.connection {
+                SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
+                SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
+                SidewalkPOI::SuddenlyAppear => {
+                    TripEndpoint::Border(map.get_l(spot.sidewalk_pos.lane()).src_i, None)
                 }
-            }
+                _ => unreachable!(),
+            },
             // Pick an arbitrary border
             SpawnTrip::Remote { ref from, .. } => {
                 TripEndpoint::Border(map.all_outgoing_borders()[0].id, Some(from.clone()))
@@ -500,17 +678,22 @@ impl SpawnTrip {
             SpawnTrip::VehicleAppearing { ref goal, .. }
             | SpawnTrip::FromBorder { ref goal, .. }
             | SpawnTrip::UsingParkedCar(_, ref goal)
-            | SpawnTrip::UsingBike(_, ref goal) => match goal {
+            | SpawnTrip::UsingBike(_, ref goal)
+            | SpawnTrip::DeliveryTour { ref goal, .. } => match goal {
                 DrivingGoal::ParkNear(b) => TripEndpoint::Bldg(*b),
                 DrivingGoal::Border(i, _, ref loc) => TripEndpoint::Border(*i, loc.clone()),
             },
-            SpawnTrip::JustWalking(_, ref spot) | SpawnTrip::UsingTransit(_, ref spot, _, _, _) => {
-                match spot.connection {
-                    SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
-                    SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
-                    _ => unreachable!(),
-                }
+            SpawnTrip::JustWalking(_, ref spot)
+            | SpawnTrip::UsingTransit(_, ref spot, _, _, _)
+            | SpawnTrip::ParkAndRide { goal: ref spot, .. } => match spot.connection {
+                SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
+                SidewalkPOI::Border(i, ref loc) => TripEndpoint::Border(i, loc.clone()),
+                _ => unreachable!(),
+            },
+            SpawnTrip::UsingRideHail(_, b) | SpawnTrip::UsingBikeShare(_, b) => {
+                TripEndpoint::Bldg(*b)
             }
+            SpawnTrip::ReturnFromParkAndRide { goal, .. } => TripEndpoint::Bldg(*goal),
             // Pick an arbitrary border
             SpawnTrip::Remote { ref to, .. } => {
                 TripEndpoint::Border(map.all_incoming_borders()[0].id, Some(to.clone()))
@@ -563,6 +746,15 @@ impl SpawnTrip {
                     SpawnTrip::JustWalking(start, goal)
                 }
             }
+            // Ride-hailing only serves trips between buildings for now
+            TripMode::RideHail => match (from, to) {
+                (TripEndpoint::Bldg(b1), TripEndpoint::Bldg(b2)) if b1 != b2 => {
+                    SpawnTrip::UsingRideHail(b1, b2)
+                }
+                _ => {
+                    return None;
+                }
+            },
         })
     }
 }
@@ -623,6 +815,8 @@ impl PersonSpec {
         let mut vehicle_foreach_trip = Vec::new();
 
         let mut bike_idx = None;
+        // Delivery tours always leave the map, so one truck is enough
+        let mut truck_idx = None;
         // For each indexed car, is it parked somewhere, or off-map?
         let mut car_locations: Vec<(usize, Option<BuildingID>)> = Vec::new();
 
@@ -670,6 +864,33 @@ impl PersonSpec {
                         Some(idx)
                     }
                 }
+                SpawnTrip::ParkAndRide {
+                    start: b,
+                    park_near: ref end,
+                    ..
+                }
+                | SpawnTrip::ReturnFromParkAndRide {
+                    parked_near: b,
+                    goal: ref end,
+                    ..
+                } => {
+                    // Same as UsingParkedCar
+                    let idx = if let Some(idx) = car_locations
+                        .iter()
+                        .find(|(_, parked_at)| *parked_at == Some(b))
+                        .map(|(idx, _)| *idx)
+                    {
+                        idx
+                    } else {
+                        let idx = vehicle_specs.len();
+                        vehicle_specs.push(Scenario::rand_car(rng));
+                        cars_initially_parked_at.push((idx, b));
+                        idx
+                    };
+                    car_locations.retain(|(i, _)| idx != *i);
+                    car_locations.push((idx, Some(*end)));
+                    Some(idx)
+                }
                 SpawnTrip::UsingParkedCar(b, ref goal) => {
                     // Is there already a car parked here?
                     let idx = if let Some(idx) = car_locations
@@ -699,7 +920,8 @@ impl PersonSpec {
 
                     Some(idx)
                 }
-                SpawnTrip::UsingBike(_, _) => {
+                // The spec for a shared bike doesn't matter much, so just reuse the person's own
+                SpawnTrip::UsingBike(_, _) | SpawnTrip::UsingBikeShare(_, _) => {
                     if bike_idx.is_none() {
                         bike_idx = Some(vehicle_specs.len());
                         vehicle_specs.push(Scenario::rand_bike(rng));
@@ -707,7 +929,15 @@ impl PersonSpec {
                     bike_idx
                 }
                 SpawnTrip::JustWalking(_, _) | SpawnTrip::UsingTransit(_, _, _, _, _) => None,
+                SpawnTrip::UsingRideHail(_, _) => None,
                 SpawnTrip::Remote { .. } => None,
+                SpawnTrip::DeliveryTour { .. } => {
+                    if truck_idx.is_none() {
+                        truck_idx = Some(vehicle_specs.len());
+                        vehicle_specs.push(Scenario::rand_truck(rng));
+                    }
+                    truck_idx
+                }
             };
             vehicle_foreach_trip.push(use_for_trip);
         }
@@ -1,6 +1,7 @@
 use crate::{
     CarID, Command, DrivingGoal, OffMapLocation, Person, PersonID, Scheduler, SidewalkSpot,
     TripEndpoint, TripLeg, TripManager, TripMode, VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH,
+    MAX_TRUCK_LENGTH,
 };
 use abstutil::Timer;
 use geom::{Duration, Time, EPSILON_DIST};
@@ -51,6 +52,39 @@ pub enum TripSpec {
         stop1: BusStopID,
         stop2: BusStopID,
     },
+    // Wait inside the building for a ride-hail vehicle to pick up at the curb
+    UsingRideHail {
+        start: BuildingID,
+        goal: BuildingID,
+    },
+    // A truck appears, unloads in front of each building in order, then leaves
+    DeliveryTour {
+        start_pos: Position,
+        stops: Vec<(BuildingID, Duration)>,
+        goal: DrivingGoal,
+        // This must be a currently off-map truck owned by the person.
+        use_vehicle: CarID,
+        origin: Option<OffMapLocation>,
+    },
+    ParkAndRide {
+        // This must be a currently parked vehicle owned by the person.
+        car: CarID,
+        start_bldg: BuildingID,
+        park_near: BuildingID,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        goal: SidewalkSpot,
+    },
+    ReturnFromParkAndRide {
+        start: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        stop2: BusStopID,
+        // Parked somewhere by an earlier ParkAndRide trip
+        car: CarID,
+        goal: BuildingID,
+    },
     // Completely off-map trip. Don't really simulate much of it.
     Remote {
         from: OffMapLocation,
@@ -58,6 +92,13 @@ pub enum TripSpec {
         trip_time: Duration,
         mode: TripMode,
     },
+    // Walk to a bike share dock, ride to a dock near the goal, and walk the rest of the way
+    UsingBikeShare {
+        start: BuildingID,
+        goal: BuildingID,
+        // This is only used while riding; the dock doesn't care which bike it gets back.
+        bike: CarID,
+    },
 }
 
 // This structure is created temporarily by a Scenario or to interactively spawn agents.
@@ -184,7 +225,49 @@ impl TripSpawner {
                 }
             }
             TripSpec::UsingTransit { .. } => {}
+            TripSpec::ParkAndRide { .. } | TripSpec::ReturnFromParkAndRide { .. } => {}
+            TripSpec::UsingRideHail { start, goal } => {
+                if start == goal {
+                    panic!(
+                        "A ride-hail trip from {} to itself doesn't make sense",
+                        start
+                    );
+                }
+            }
+            TripSpec::DeliveryTour {
+                start_pos,
+                stops,
+                goal,
+                use_vehicle,
+                ..
+            } => {
+                let vehicle = person.get_vehicle(*use_vehicle);
+                if start_pos.dist_along() < vehicle.length
+                    || start_pos.dist_along() >= map.get_l(start_pos.lane()).length()
+                {
+                    panic!(
+                        "Can't spawn a {:?} at {}",
+                        vehicle.vehicle_type,
+                        start_pos.dist_along()
+                    );
+                }
+                if stops.is_empty() {
+                    panic!("A delivery tour for {} has no stops", person.id);
+                }
+                // Trucks don't fit in normal parking spots
+                if let DrivingGoal::ParkNear(_) = goal {
+                    panic!("A delivery tour for {} must end at a border", person.id);
+                }
+            }
             TripSpec::Remote { .. } => {}
+            TripSpec::UsingBikeShare { start, goal, .. } => {
+                if start == goal {
+                    panic!(
+                        "A bike share trip from {} to itself doesn't make sense",
+                        start
+                    );
+                }
+            }
         };
 
         self.trips
@@ -212,7 +295,7 @@ impl TripSpawner {
                     tuple,
                     req.clone(),
                     if pathfinding_upfront {
-                        req.and_then(|r| map.pathfind(r))
+                        req.and_then(|r| map.pathfind_at(r, tuple.1))
                     } else {
                         None
                     },
@@ -352,6 +435,81 @@ impl TripSpawner {
                         map,
                     )
                 }
+                TripSpec::ParkAndRide {
+                    car,
+                    park_near,
+                    route,
+                    stop1,
+                    stop2,
+                    goal,
+                    ..
+                } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::Transit,
+                    modified,
+                    vec![
+                        TripLeg::Walk(SidewalkSpot::deferred_parking_spot()),
+                        TripLeg::Drive(car, DrivingGoal::ParkNear(park_near)),
+                        TripLeg::Walk(SidewalkSpot::bus_stop(stop1, map)),
+                        TripLeg::RideBus(route, stop2),
+                        TripLeg::Walk(goal),
+                    ],
+                    map,
+                ),
+                TripSpec::ReturnFromParkAndRide {
+                    route,
+                    stop1,
+                    stop2,
+                    car,
+                    goal,
+                    ..
+                } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::Transit,
+                    modified,
+                    vec![
+                        TripLeg::Walk(SidewalkSpot::bus_stop(stop1, map)),
+                        TripLeg::RideBus(route, stop2),
+                        TripLeg::Walk(SidewalkSpot::deferred_parking_spot()),
+                        TripLeg::Drive(car, DrivingGoal::ParkNear(goal)),
+                        TripLeg::Walk(SidewalkSpot::building(goal, map)),
+                    ],
+                    map,
+                ),
+                TripSpec::DeliveryTour {
+                    stops,
+                    goal,
+                    use_vehicle,
+                    ..
+                } => {
+                    let mut legs: Vec<TripLeg> = stops
+                        .into_iter()
+                        .map(|(b, dt)| TripLeg::Deliver(use_vehicle, b, dt))
+                        .collect();
+                    legs.push(TripLeg::Drive(use_vehicle, goal));
+                    trips.new_trip(
+                        person.id,
+                        start_time,
+                        trip_start,
+                        TripMode::Drive,
+                        modified,
+                        legs,
+                        map,
+                    )
+                }
+                TripSpec::UsingRideHail { goal, .. } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::RideHail,
+                    modified,
+                    vec![TripLeg::RideHail(goal)],
+                    map,
+                ),
                 TripSpec::Remote { to, mode, .. } => trips.new_trip(
                     person.id,
                     start_time,
@@ -361,6 +519,16 @@ impl TripSpawner {
                     vec![TripLeg::Remote(to)],
                     map,
                 ),
+                // The docks are picked when the trip starts
+                TripSpec::UsingBikeShare { goal, .. } => trips.new_trip(
+                    person.id,
+                    start_time,
+                    trip_start,
+                    TripMode::Bike,
+                    modified,
+                    vec![TripLeg::Walk(SidewalkSpot::building(goal, map))],
+                    map,
+                ),
             };
 
             if cancelled {
@@ -377,9 +545,13 @@ impl TripSpawner {
 
 impl TripSpec {
     // If possible, fixes problems that schedule_trip would hit.
-    pub fn spawn_vehicle_at(pos: Position, is_bike: bool, map: &Map) -> Option<Position> {
+    pub fn spawn_vehicle_at(pos: Position, vt: VehicleType, map: &Map) -> Option<Position> {
         let lane_len = map.get_l(pos.lane()).length();
-        let vehicle_len = if is_bike { BIKE_LENGTH } else { MAX_CAR_LENGTH };
+        let vehicle_len = match vt {
+            VehicleType::Bike => BIKE_LENGTH,
+            VehicleType::Truck => MAX_TRUCK_LENGTH,
+            VehicleType::Car | VehicleType::Bus | VehicleType::Train => MAX_CAR_LENGTH,
+        };
         // There's no hope.
         if lane_len <= vehicle_len {
             return None;
@@ -394,6 +566,18 @@ impl TripSpec {
         }
     }
 
+    // Where to stop in the street for somebody going in or out of a building
+    pub(crate) fn curb_pos(b: BuildingID, vt: VehicleType, map: &Map) -> Position {
+        let lane = map.find_driving_lane_near_building(b);
+        let sidewalk_pos = map.get_b(b).front_path.sidewalk;
+        let pos = if map.get_l(lane).parent == map.get_l(sidewalk_pos.lane()).parent {
+            sidewalk_pos.equiv_pos(lane, MAX_CAR_LENGTH, map)
+        } else {
+            Position::new(lane, map.get_l(lane).length() / 2.0)
+        };
+        TripSpec::spawn_vehicle_at(pos, vt, map).unwrap_or(pos)
+    }
+
     pub(crate) fn get_pathfinding_request(&self, map: &Map) -> Option<PathRequest> {
         match self {
             TripSpec::VehicleAppearing {
@@ -415,7 +599,7 @@ impl TripSpec {
             }
             TripSpec::NoRoomToSpawn { .. } => None,
             // We don't know where the parked car will be
-            TripSpec::UsingParkedCar { .. } => None,
+            TripSpec::UsingParkedCar { .. } | TripSpec::ParkAndRide { .. } => None,
             TripSpec::JustWalking { start, goal, .. } => Some(PathRequest {
                 start: start.sidewalk_pos,
                 end: goal.sidewalk_pos,
@@ -428,11 +612,23 @@ impl TripSpec {
                     .sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             }),
-            TripSpec::UsingTransit { start, stop1, .. } => Some(PathRequest {
+            TripSpec::UsingTransit { start, stop1, .. }
+            | TripSpec::ReturnFromParkAndRide { start, stop1, .. } => Some(PathRequest {
                 start: start.sidewalk_pos,
                 end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             }),
+            TripSpec::DeliveryTour {
+                start_pos, stops, ..
+            } => Some(PathRequest {
+                start: *start_pos,
+                end: TripSpec::curb_pos(stops[0].0, VehicleType::Truck, map),
+                constraints: PathConstraints::Car,
+            }),
+            // Depends on which vehicle gets dispatched
+            TripSpec::UsingRideHail { .. } => None,
+            // Depends on which docks have bikes when the trip starts
+            TripSpec::UsingBikeShare { .. } => None,
             TripSpec::Remote { .. } => None,
         }
     }
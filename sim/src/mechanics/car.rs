@@ -1,9 +1,9 @@
 use crate::{
-    CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, PersonID, Router, TimeInterval,
-    TransitSimState, TripID, Vehicle, VehicleType,
+    CarStatus, DistanceInterval, DrawCarInput, Event, ParkingSpot, PersonID, Router, TimeInterval,
+    TransitSimState, TripID, Vehicle, VehicleType, FOLLOWING_DISTANCE,
 };
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{Map, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{Map, Traversable, TurnType};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
@@ -17,15 +17,50 @@ pub struct Car {
     pub trip_and_person: Option<(TripID, PersonID)>,
     pub started_at: Time,
     pub total_blocked_time: Duration,
+    // How fast the car was going at the end of its last Crossing state. Only the car-following
+    // model uses this.
+    pub last_speed: Speed,
+    // When and how far along the current step of the path the car started, and total_blocked_time
+    // at that point. Just for measuring emissions.
+    pub entered_step: (Time, Distance, Duration),
 
     // In reverse order -- most recently left is first. The sum length of these must be >=
     // vehicle.length.
     pub last_steps: VecDeque<Traversable>,
 }
 
+// With the car-following model, cars slow down to about 15mph for left and right turns
+const TURNING_SPEED: Speed = Speed::const_meters_per_second(6.7);
+// Following drivers try to keep this much time between themselves and their leader
+const TIME_HEADWAY: Duration = Duration::const_seconds(1.5);
+// How often following drivers react to their leader again
+const FOLLOWING_STEP: Duration = Duration::const_seconds(1.0);
+// How many times harder than comfortable drivers will brake to avoid hitting their leader
+const EMERGENCY_BRAKING: f64 = 3.0;
+
 impl Car {
-    // Assumes the current head of the path is the thing to cross.
-    pub fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> CarState {
+    // Summarizes driving the current step of the path, up to end_dist
+    pub fn traversed(&self, end_dist: Distance, now: Time) -> Event {
+        let (entered_at, start_dist, blocked_before) = self.entered_step;
+        Event::VehicleTraversed {
+            car: self.vehicle.id,
+            trip: self.trip_and_person.map(|(t, _)| t),
+            on: self.router.head(),
+            dist: (end_dist - start_dist).max(Distance::ZERO),
+            duration: now - entered_at,
+            idle: self.total_blocked_time - blocked_before,
+        }
+    }
+
+    // Assumes the current head of the path is the thing to cross. With the car-following model,
+    // the car brakes for the next step, or to a stop at the end of its path.
+    pub fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        map: &Map,
+        car_following: bool,
+    ) -> CarState {
         let dist_int = DistanceInterval::new_driving(
             start_dist,
             if self.router.last_step() {
@@ -34,22 +69,172 @@ impl Car {
                 self.router.head().length(map)
             },
         );
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+        let end_speed = match self.router.maybe_next() {
+            Some(next) => self.speed_on(next, map, car_following),
+            None => Speed::ZERO,
+        };
+        self.crossing(dist_int, start_time, end_speed, map, car_following)
     }
 
+    // This doesn't brake at the end of the interval.
     pub fn crossing_state_with_end_dist(
         &self,
         dist_int: DistanceInterval,
         start_time: Time,
         map: &Map,
+        car_following: bool,
     ) -> CarState {
-        let on = self.router.head();
+        let end_speed = self.speed_on(self.router.head(), map, car_following);
+        self.crossing(dist_int, start_time, end_speed, map, car_following)
+    }
+
+    fn crossing(
+        &self,
+        dist_int: DistanceInterval,
+        start_time: Time,
+        end_speed: Speed,
+        map: &Map,
+        car_following: bool,
+    ) -> CarState {
+        let speed = self.speed_on(self.router.head(), map, car_following);
+        if !car_following {
+            let dt = (dist_int.end - dist_int.start) / speed;
+            return CarState::Crossing(
+                TimeInterval::new(start_time, start_time + dt),
+                dist_int,
+                None,
+            );
+        }
+        let profile = SpeedProfile::new(
+            self.current_speed(start_time),
+            speed,
+            end_speed,
+            dist_int.length(),
+            &self.vehicle,
+        );
+        CarState::Crossing(
+            TimeInterval::new(start_time, start_time + profile.duration()),
+            dist_int,
+            Some(profile),
+        )
+    }
+
+    // Car-following, roughly the Intelligent Driver Model: accelerate towards the speed limit, but
+    // keep a safe time gap behind the leader, braking harder when closing in on them. This only
+    // plans a short step, then the car reacts again. leader_back is where the leader's back is
+    // along the current step of the path, and leader_speed is None if they're not moving at all.
+    pub fn following_state(
+        &self,
+        front: Distance,
+        leader_back: Distance,
+        leader_speed: Option<Speed>,
+        now: Time,
+        map: &Map,
+    ) -> CarState {
+        let free = self.crossing_state(front, now, map, true);
+        let (end_dist, free_accel) = match free {
+            CarState::Crossing(_, ref dist_int, Some(ref profile)) => {
+                (dist_int.end, profile.initial_accel())
+            }
+            _ => unreachable!(),
+        };
+        let leader_speed = match leader_speed {
+            Some(speed) => speed.inner_meters_per_second(),
+            None => {
+                // Just brake to a stop behind them
+                let stop_at = leader_back - FOLLOWING_DISTANCE;
+                if stop_at >= end_dist {
+                    return free;
+                }
+                return self.crossing(
+                    DistanceInterval::new_driving(front, stop_at.max(front)),
+                    now,
+                    Speed::ZERO,
+                    map,
+                    true,
+                );
+            }
+        };
+
+        let a = self.vehicle.max_accel;
+        let b = self.vehicle.max_decel;
+        let v = self.current_speed(now).inner_meters_per_second();
+        let v_max = self
+            .speed_on(self.router.head(), map, true)
+            .inner_meters_per_second();
+        let gap = (leader_back - front).inner_meters().max(0.1);
+        let desired_gap = FOLLOWING_DISTANCE.inner_meters()
+            + (v * TIME_HEADWAY.inner_seconds() + v * (v - leader_speed) / (2.0 * (a * b).sqrt()))
+                .max(0.0);
+        let mut dt = FOLLOWING_STEP.inner_seconds();
+        let mut accel = (a * (1.0 - (v / v_max).powi(4) - (desired_gap / gap).powi(2)))
+            .max(-EMERGENCY_BRAKING * b)
+            // Still brake for the next step or the end of the path, and don't speed
+            .min(free_accel)
+            .min((v_max - v).max(0.0) / dt);
+        if v + accel * dt < 0.0 {
+            if v > 0.0 {
+                // Stop partway through the step
+                dt = v / -accel;
+            } else {
+                // Already stopped; there's no backing up
+                accel = 0.0;
+            }
+        }
+
+        let dist = Distance::meters(v * dt + accel * dt * dt / 2.0);
+        if front + dist >= end_dist {
+            return free;
+        }
+        let profile = SpeedProfile::following(v, accel, Duration::seconds(dt));
+        CarState::Crossing(
+            TimeInterval::new(now, now + profile.duration()),
+            DistanceInterval::new_driving(front, front + dist),
+            Some(profile),
+        )
+    }
+
+    // The fastest this car can go along something
+    pub fn speed_on(&self, on: Traversable, map: &Map, car_following: bool) -> Speed {
         let mut speed = on.speed_limit(map);
         if let Some(s) = self.vehicle.max_speed {
             speed = speed.min(s);
         }
-        let dt = (dist_int.end - dist_int.start) / speed;
-        CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
+        if let Traversable::Turn(t) = on {
+            let turn_type = map.get_t(t).turn_type;
+            if car_following && (turn_type == TurnType::Left || turn_type == TurnType::Right) {
+                speed = speed.min(TURNING_SPEED);
+            }
+        }
+        speed
+    }
+
+    // How fast the car is going right now. Only used by the car-following model, so without a
+    // speed profile, the car is stopped.
+    pub fn speed(&self, now: Time) -> Option<Speed> {
+        match self.state {
+            CarState::Crossing(ref time_int, _, Some(ref profile)) => {
+                Some(profile.speed_at(now.min(time_int.end) - time_int.start))
+            }
+            _ => None,
+        }
+    }
+
+    // How fast the car is going as it starts crossing something new
+    fn current_speed(&self, now: Time) -> Speed {
+        match self.state {
+            // Recalculating partway through
+            CarState::Crossing(ref time_int, _, Some(ref profile)) => {
+                profile.speed_at(now.min(time_int.end) - time_int.start)
+            }
+            // Just finished crossing something, without having to stop
+            CarState::Queued { blocked_since } | CarState::WaitingToAdvance { blocked_since }
+                if blocked_since == now =>
+            {
+                self.last_speed
+            }
+            _ => Speed::ZERO,
+        }
     }
 
     pub fn get_draw_car(
@@ -209,7 +394,7 @@ impl Car {
             status: match self.state {
                 CarState::Queued { .. } => CarStatus::Moving,
                 CarState::WaitingToAdvance { .. } => CarStatus::Moving,
-                CarState::Crossing(_, _) => CarStatus::Moving,
+                CarState::Crossing(_, _, _) => CarStatus::Moving,
                 // Eh they're technically moving, but this is a bit easier to spot
                 CarState::Unparking(_, _, _) => CarStatus::Parked,
                 CarState::Parking(_, _, _) => CarStatus::Parked,
@@ -243,7 +428,9 @@ impl Car {
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum CarState {
-    Crossing(TimeInterval, DistanceInterval),
+    // The profile is only used by the car-following model. Otherwise, cars cross at a constant
+    // speed.
+    Crossing(TimeInterval, DistanceInterval, Option<SpeedProfile>),
     Queued { blocked_since: Time },
     WaitingToAdvance { blocked_since: Time },
     // Where's the front of the car while this is happening?
@@ -255,7 +442,7 @@ pub enum CarState {
 impl CarState {
     pub fn get_end_time(&self) -> Time {
         match self {
-            CarState::Crossing(ref time_int, _) => time_int.end,
+            CarState::Crossing(ref time_int, _, _) => time_int.end,
             CarState::Queued { .. } => unreachable!(),
             CarState::WaitingToAdvance { .. } => unreachable!(),
             CarState::Unparking(_, _, ref time_int) => time_int.end,
@@ -264,3 +451,175 @@ impl CarState {
         }
     }
 }
+
+// Accelerate from the start speed to cruising speed, cruise, then brake to the end speed, all
+// within the vehicle's limits. Over short distances, the car might never reach cruising speed.
+// Behind a moving leader, the profile instead just covers one short step of following them.
+// TODO Cars still stop instantly at a red light, since it's not known in advance that they'll
+// have to.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct SpeedProfile {
+    start_speed: Speed,
+    cruise_speed: Speed,
+    end_speed: Speed,
+    accel_time: Duration,
+    cruise_time: Duration,
+    brake_time: Duration,
+    // A step of following a leader, rather than crossing all the way
+    following: bool,
+}
+
+impl SpeedProfile {
+    fn new(
+        start_speed: Speed,
+        cruise_speed: Speed,
+        end_speed: Speed,
+        dist: Distance,
+        vehicle: &Vehicle,
+    ) -> SpeedProfile {
+        let a = vehicle.max_accel;
+        let b = vehicle.max_decel;
+        let d = dist.inner_meters();
+        let v_max = cruise_speed.inner_meters_per_second();
+        let v0 = start_speed.inner_meters_per_second().min(v_max);
+        let mut v1 = end_speed.inner_meters_per_second().min(v_max);
+
+        let mut v_peak = v_max;
+        if (v_max * v_max - v0 * v0) / (2.0 * a) + (v_max * v_max - v1 * v1) / (2.0 * b) > d {
+            // Not enough room to reach cruising speed
+            v_peak = ((2.0 * a * b * d + b * v0 * v0 + a * v1 * v1) / (a + b)).sqrt();
+        }
+        if v_peak < v1 {
+            // Not even enough room to reach the end speed, so accelerate the whole way
+            v_peak = (v0 * v0 + 2.0 * a * d).sqrt();
+            v1 = v_peak;
+        }
+
+        let (accel_time, cruise_time, brake_time) = if v_peak < v0 {
+            // Brake harder than the limit; the alternative is overshooting
+            v_peak = v0;
+            let t = if v0 + v1 > 0.0 {
+                2.0 * d / (v0 + v1)
+            } else {
+                0.0
+            };
+            (0.0, 0.0, t)
+        } else {
+            let accel_time = (v_peak - v0) / a;
+            let brake_time = (v_peak - v1) / b;
+            let cruise_dist =
+                d - (v0 + v_peak) / 2.0 * accel_time - (v_peak + v1) / 2.0 * brake_time;
+            let cruise_time = if v_peak > 0.0 {
+                cruise_dist.max(0.0) / v_peak
+            } else {
+                0.0
+            };
+            (accel_time, cruise_time, brake_time)
+        };
+
+        SpeedProfile {
+            start_speed: Speed::meters_per_second(v0),
+            cruise_speed: Speed::meters_per_second(v_peak),
+            end_speed: Speed::meters_per_second(v1),
+            accel_time: Duration::seconds(accel_time),
+            cruise_time: Duration::seconds(cruise_time),
+            brake_time: Duration::seconds(brake_time),
+            following: false,
+        }
+    }
+
+    // Constant acceleration (or braking, if negative) for one step of following a leader
+    fn following(start_speed: f64, accel: f64, dt: Duration) -> SpeedProfile {
+        let end_speed = (start_speed + accel * dt.inner_seconds()).max(0.0);
+        let (cruise_speed, accel_time, cruise_time, brake_time) = if accel > 0.0 {
+            (end_speed, dt, Duration::ZERO, Duration::ZERO)
+        } else if accel < 0.0 {
+            (start_speed, Duration::ZERO, Duration::ZERO, dt)
+        } else {
+            (start_speed, Duration::ZERO, dt, Duration::ZERO)
+        };
+        SpeedProfile {
+            start_speed: Speed::meters_per_second(start_speed),
+            cruise_speed: Speed::meters_per_second(cruise_speed),
+            end_speed: Speed::meters_per_second(end_speed),
+            accel_time,
+            cruise_time,
+            brake_time,
+            following: true,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.accel_time + self.cruise_time + self.brake_time
+    }
+
+    pub fn end_speed(&self) -> Speed {
+        self.end_speed
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.following
+    }
+
+    // In meters per second squared, right at the start. Negative means braking.
+    fn initial_accel(&self) -> f64 {
+        if self.accel_time > Duration::ZERO {
+            (self.cruise_speed - self.start_speed).inner_meters_per_second()
+                / self.accel_time.inner_seconds()
+        } else if self.cruise_time > Duration::ZERO {
+            0.0
+        } else if self.brake_time > Duration::ZERO {
+            (self.end_speed - self.cruise_speed).inner_meters_per_second()
+                / self.brake_time.inner_seconds()
+        } else {
+            0.0
+        }
+    }
+
+    // How far the car has gone, this long after starting
+    pub fn dist_at(&self, t: Duration) -> Distance {
+        let v0 = self.start_speed.inner_meters_per_second();
+        let vp = self.cruise_speed.inner_meters_per_second();
+        let v1 = self.end_speed.inner_meters_per_second();
+        let ta = self.accel_time.inner_seconds();
+        let tc = self.cruise_time.inner_seconds();
+        let tb = self.brake_time.inner_seconds();
+        let t = t.inner_seconds().max(0.0);
+
+        let meters = if t < ta {
+            v0 * t + (vp - v0) * t * t / (2.0 * ta)
+        } else if t < ta + tc || tb == 0.0 {
+            (v0 + vp) / 2.0 * ta + vp * (t - ta).min(tc)
+        } else {
+            let t = (t - ta - tc).min(tb);
+            (v0 + vp) / 2.0 * ta + vp * tc + vp * t - (vp - v1) * t * t / (2.0 * tb)
+        };
+        Distance::meters(meters)
+    }
+
+    pub fn speed_at(&self, t: Duration) -> Speed {
+        if t < self.accel_time {
+            self.start_speed + (t / self.accel_time) * (self.cruise_speed - self.start_speed)
+        } else if t < self.accel_time + self.cruise_time || self.brake_time == Duration::ZERO {
+            self.cruise_speed
+        } else if t < self.duration() {
+            let t = t - self.accel_time - self.cruise_time;
+            self.cruise_speed - (t / self.brake_time) * (self.cruise_speed - self.end_speed)
+        } else {
+            self.end_speed
+        }
+    }
+
+    // Where the front of the car is
+    pub fn front(
+        &self,
+        time_int: &TimeInterval,
+        dist_int: &DistanceInterval,
+        now: Time,
+    ) -> Distance {
+        if now >= time_int.end {
+            return dist_int.end;
+        }
+        (dist_int.start + self.dist_at(now - time_int.start)).min(dist_int.end)
+    }
+}
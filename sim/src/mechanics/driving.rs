@@ -3,12 +3,12 @@ use crate::mechanics::Queue;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, Command, CreateCar, DistanceInterval,
     DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PersonID,
-    Scheduler, TimeInterval, TransitSimState, TripManager, UnzoomedAgent, Vehicle, WalkingSimState,
-    FOLLOWING_DISTANCE,
+    RideHailSimState, Scheduler, TimeInterval, TransitSimState, TripManager, UnzoomedAgent,
+    Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{LaneID, Map, Path, PathStep, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{LaneID, Map, Path, PathStep, Position, Traversable, TurnID};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet, VecDeque};
 
@@ -16,7 +16,6 @@ const TIME_TO_UNPARK_ONSTRET: Duration = Duration::const_seconds(10.0);
 const TIME_TO_PARK_ONSTREET: Duration = Duration::const_seconds(15.0);
 const TIME_TO_UNPARK_OFFSTREET: Duration = Duration::const_seconds(5.0);
 const TIME_TO_PARK_OFFSTREET: Duration = Duration::const_seconds(5.0);
-const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
 
 // TODO Do something else.
 pub(crate) const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
@@ -37,15 +36,27 @@ pub struct DrivingSimState {
     events: Vec<Event>,
 
     recalc_lanechanging: bool,
+    reroute_blocked_after: Option<Duration>,
+    car_following: bool,
+    mid_block_lanechanging: bool,
 }
 
 impl DrivingSimState {
-    pub fn new(map: &Map, recalc_lanechanging: bool) -> DrivingSimState {
+    pub fn new(
+        map: &Map,
+        recalc_lanechanging: bool,
+        reroute_blocked_after: Option<Duration>,
+        car_following: bool,
+        mid_block_lanechanging: bool,
+    ) -> DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
             queues: BTreeMap::new(),
             events: Vec::new(),
             recalc_lanechanging,
+            reroute_blocked_after,
+            car_following,
+            mid_block_lanechanging,
         };
 
         for l in map.all_lanes() {
@@ -94,6 +105,8 @@ impl DrivingSimState {
                 last_steps: VecDeque::new(),
                 started_at: now,
                 total_blocked_time: Duration::ZERO,
+                last_speed: Speed::ZERO,
+                entered_step: (now, params.start_dist, Duration::ZERO),
                 trip_and_person: params.trip_and_person,
             };
             if let Some(p) = params.maybe_parked_car {
@@ -119,7 +132,10 @@ impl DrivingSimState {
                         car.trip_and_person,
                         &mut self.events,
                     ) {
-                        None | Some(ActionAtEnd::GotoLaneEnd) => {}
+                        None
+                        | Some(ActionAtEnd::GotoLaneEnd)
+                        | Some(ActionAtEnd::RideHailAtCurb)
+                        | Some(ActionAtEnd::DeliveryAtCurb) => {}
                         x => {
                             panic!(
                                 "Car with one-step route {:?} had unexpected result from \
@@ -139,7 +155,7 @@ impl DrivingSimState {
                     }
                 }
 
-                car.state = car.crossing_state(params.start_dist, now, map);
+                car.state = car.crossing_state(params.start_dist, now, map, self.car_following);
             }
             scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             {
@@ -149,7 +165,12 @@ impl DrivingSimState {
                 // get_idx_to_insert_car does a more detailed check of the current space usage.
                 queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
             }
-            self.cars.insert(car.vehicle.id, car);
+            let id = car.vehicle.id;
+            self.cars.insert(id, car);
+            self.follow(id, now, map, scheduler);
+            if let Some(follower) = self.get_follower(id) {
+                self.follow(follower, now, map, scheduler);
+            }
             return true;
         }
         false
@@ -166,6 +187,53 @@ impl DrivingSimState {
         scheduler: &mut Scheduler,
         transit: &mut TransitSimState,
         walking: &mut WalkingSimState,
+        ride_hail: &mut RideHailSimState,
+    ) {
+        // With car-following, whoever's behind has to react to whatever this car does.
+        let follower = if self.car_following {
+            self.get_follower(id)
+        } else {
+            None
+        };
+        let following_step = match self.cars[&id].state {
+            CarState::Crossing(_, _, Some(ref profile)) => profile.is_following(),
+            _ => false,
+        };
+        if following_step {
+            // Just finished one step of following the leader, so react to them again.
+            self.follow(id, now, map, scheduler);
+        } else {
+            self.update_car_state(
+                id,
+                now,
+                map,
+                parking,
+                intersections,
+                trips,
+                scheduler,
+                transit,
+                walking,
+                ride_hail,
+            );
+            self.follow(id, now, map, scheduler);
+        }
+        if let Some(follower) = follower {
+            self.follow(follower, now, map, scheduler);
+        }
+    }
+
+    fn update_car_state(
+        &mut self,
+        id: CarID,
+        now: Time,
+        map: &Map,
+        parking: &mut ParkingSimState,
+        intersections: &mut IntersectionSimState,
+        trips: &mut TripManager,
+        scheduler: &mut Scheduler,
+        transit: &mut TransitSimState,
+        walking: &mut WalkingSimState,
+        ride_hail: &mut RideHailSimState,
     ) {
         // State transitions for this car:
         //
@@ -207,10 +275,16 @@ impl DrivingSimState {
                 map,
                 parking,
                 intersections,
+                trips,
                 transit,
+                ride_hail,
                 scheduler,
             );
             self.cars.insert(id, car);
+
+            if self.mid_block_lanechanging {
+                self.try_mid_block_lanechange(id, now, map, intersections, scheduler);
+            }
         }
 
         if need_distances {
@@ -228,6 +302,7 @@ impl DrivingSimState {
             // Responsibility of update_car_with_distances to manage scheduling stuff!
             if self.update_car_with_distances(
                 &mut car, &dists, idx, now, map, parking, trips, scheduler, transit, walking,
+                ride_hail,
             ) {
                 self.cars.insert(id, car);
             } else {
@@ -246,11 +321,17 @@ impl DrivingSimState {
         map: &Map,
         parking: &mut ParkingSimState,
         intersections: &mut IntersectionSimState,
+        trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ride_hail: &mut RideHailSimState,
         scheduler: &mut Scheduler,
     ) -> bool {
         match car.state {
-            CarState::Crossing(_, _) => {
+            CarState::Crossing(_, _, ref profile) => {
+                car.last_speed = profile
+                    .as_ref()
+                    .map(|p| p.end_speed())
+                    .unwrap_or(Speed::ZERO);
                 car.state = CarState::Queued { blocked_since: now };
                 if car.router.last_step() {
                     // Immediately run update_car_with_distances.
@@ -281,15 +362,31 @@ impl DrivingSimState {
                         &mut self.events,
                     );
                 }
-                car.state = car.crossing_state(front, now, map);
+                car.state = car.crossing_state(front, now, map, self.car_following);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                // The engine wasn't running yet
+                car.entered_step.0 = now;
             }
-            CarState::IdlingAtStop(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id, map);
+            CarState::IdlingAtStop(dist, ref idling) => {
+                // Buses keep their engine running at stops. Everybody else switches off.
+                let stopped = now - idling.start;
+                if car.vehicle.vehicle_type == VehicleType::Bus {
+                    car.entered_step.2 -= stopped;
+                } else {
+                    car.entered_step.0 += stopped;
+                }
+                car.router = if ride_hail.has_vehicle(car.vehicle.id) {
+                    ride_hail.vehicle_departed_curb(car.vehicle.id)
+                } else if car.vehicle.vehicle_type == VehicleType::Truck {
+                    trips.truck_departed_delivery_stop(car.vehicle.id)
+                } else {
+                    transit.bus_departed_from_stop(car.vehicle.id, map)
+                };
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
-                car.state = car.crossing_state(dist, now, map);
+                car.state = car.crossing_state(dist, now, map, self.car_following);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                transit_check_in(car, now, map, intersections, scheduler);
 
                 // Update our follower, so they know we stopped idling.
                 let queue = &self.queues[&car.router.head()];
@@ -311,6 +408,7 @@ impl DrivingSimState {
                                     dist - car.vehicle.length - FOLLOWING_DISTANCE,
                                     now,
                                     map,
+                                    self.car_following,
                                 );
                                 scheduler.update(
                                     follower.state.get_end_time(),
@@ -322,7 +420,7 @@ impl DrivingSimState {
                         // They weren't blocked. Note that there's no way the Crossing state could
                         // jump forwards here; the leader is still in front
                         // of them.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::IdlingAtStop(_, _) => {}
@@ -350,7 +448,41 @@ impl DrivingSimState {
                         scheduler,
                         Some((&car, &self.cars, &mut self.queues)),
                     ) {
-                        // Don't schedule a retry here.
+                        // Don't schedule a retry here, unless the driver might lose patience and
+                        // look for another way.
+                        if let Some(patience) = self.reroute_blocked_after {
+                            if now - blocked_since < patience {
+                                scheduler.update_if_sooner(
+                                    blocked_since + patience,
+                                    Command::UpdateCar(car.vehicle.id),
+                                );
+                                return false;
+                            }
+                            // Whether or not there's a better route, wait a while before looking
+                            // again.
+                            car.total_blocked_time += now - blocked_since;
+                            car.state = CarState::WaitingToAdvance { blocked_since: now };
+                            if let Some(old_path) = car.router.reroute_around_congestion(
+                                &car.vehicle,
+                                &self.queues,
+                                map,
+                            ) {
+                                intersections.cancel_request(AgentID::Car(car.vehicle.id), t);
+                                self.events.push(Event::CarRerouted {
+                                    car: car.vehicle.id,
+                                    lane: from.as_lane(),
+                                    old_path,
+                                    new_path: car.router.get_path().clone(),
+                                });
+                                scheduler.update(now, Command::UpdateCar(car.vehicle.id));
+                                self.reroute_followers(car.vehicle.id, from, goto, map);
+                            } else {
+                                scheduler.update_if_sooner(
+                                    now + patience,
+                                    Command::UpdateCar(car.vehicle.id),
+                                );
+                            }
+                        }
                         return false;
                     }
                 }
@@ -364,6 +496,10 @@ impl DrivingSimState {
                 // We do NOT need to update the follower. If they were Queued, they'll remain that
                 // way, until laggy_head is None.
 
+                car.total_blocked_time += now - blocked_since;
+                self.events
+                    .push(car.traversed(car.router.head().length(map), now));
+                car.entered_step = (now, Distance::ZERO, car.total_blocked_time);
                 let last_step = car.router.advance(
                     &car.vehicle,
                     parking,
@@ -371,9 +507,9 @@ impl DrivingSimState {
                     car.trip_and_person,
                     &mut self.events,
                 );
-                car.total_blocked_time += now - blocked_since;
-                car.state = car.crossing_state(Distance::ZERO, now, map);
+                car.state = car.crossing_state(Distance::ZERO, now, map, self.car_following);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                transit_check_in(car, now, map, intersections, scheduler);
                 self.events.push(Event::AgentEntersTraversable(
                     AgentID::Car(car.vehicle.id),
                     goto,
@@ -394,6 +530,7 @@ impl DrivingSimState {
                         ),
                         now,
                         map,
+                        self.car_following,
                     )
                     .get_end_time(),
                     Command::UpdateLaggyHead(car.vehicle.id),
@@ -423,11 +560,12 @@ impl DrivingSimState {
         scheduler: &mut Scheduler,
         transit: &mut TransitSimState,
         walking: &mut WalkingSimState,
+        ride_hail: &mut RideHailSimState,
     ) -> bool {
         let our_dist = dists[idx].1;
 
         match car.state {
-            CarState::Crossing(_, _)
+            CarState::Crossing(_, _, _)
             | CarState::Unparking(_, _, _)
             | CarState::IdlingAtStop(_, _)
             | CarState::WaitingToAdvance { .. } => unreachable!(),
@@ -490,7 +628,7 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::GotoLaneEnd) => {
                         car.total_blocked_time += now - blocked_since;
-                        car.state = car.crossing_state(our_dist, now, map);
+                        car.state = car.crossing_state(our_dist, now, map, self.car_following);
                         scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                         true
@@ -509,18 +647,17 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::BusAtStop) => {
                         car.total_blocked_time += now - blocked_since;
-                        if transit.bus_arrived_at_stop(
+                        if let Some(dt) = transit.bus_arrived_at_stop(
                             now,
                             car.vehicle.id,
                             trips,
                             walking,
+                            parking,
                             scheduler,
                             map,
                         ) {
-                            car.state = CarState::IdlingAtStop(
-                                our_dist,
-                                TimeInterval::new(now, now + TIME_TO_WAIT_AT_STOP),
-                            );
+                            car.state =
+                                CarState::IdlingAtStop(our_dist, TimeInterval::new(now, now + dt));
                             scheduler
                                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                             true
@@ -529,6 +666,48 @@ impl DrivingSimState {
                             false
                         }
                     }
+                    Some(ActionAtEnd::RideHailAtCurb) => {
+                        car.total_blocked_time += now - blocked_since;
+                        if let Some(dt) = ride_hail.vehicle_reached_curb(
+                            now,
+                            car.vehicle.id,
+                            Position::new(car.router.head().as_lane(), our_dist),
+                            trips,
+                            parking,
+                            scheduler,
+                            map,
+                        ) {
+                            car.state =
+                                CarState::IdlingAtStop(our_dist, TimeInterval::new(now, now + dt));
+                            scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            true
+                        } else {
+                            // Idle until dispatched again
+                            false
+                        }
+                    }
+                    Some(ActionAtEnd::DeliveryAtCurb) => {
+                        car.total_blocked_time += now - blocked_since;
+                        // There's no loading zone; just stop in the lane. Anybody behind has to
+                        // wait.
+                        if let Some(dt) = trips.truck_reached_delivery_stop(
+                            now,
+                            car.vehicle.id,
+                            Position::new(car.router.head().as_lane(), our_dist),
+                            map,
+                            parking,
+                            scheduler,
+                        ) {
+                            car.state =
+                                CarState::IdlingAtStop(our_dist, TimeInterval::new(now, now + dt));
+                            scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            true
+                        } else {
+                            false
+                        }
+                    }
                     None => {
                         scheduler.push(
                             now + BLIND_RETRY_TO_REACH_END_DIST,
@@ -631,6 +810,7 @@ impl DrivingSimState {
         }
 
         intersections.vehicle_gone(car.vehicle.id);
+        self.events.push(car.traversed(dists[idx].1, now));
 
         // We might be vanishing while partly clipping into other stuff.
         self.trim_last_steps(
@@ -646,37 +826,277 @@ impl DrivingSimState {
         scheduler.cancel(Command::UpdateLaggyHead(car.vehicle.id));
 
         // Update the follower so that they don't suddenly jump forwards.
+        // TODO If the leader vanished at a border node, this still jumps a bit -- the lead car's
+        // back is still sticking out. Need to still be bound by them, even though they don't
+        // exist! If the leader just parked, then we're fine.
         if idx != dists.len() - 1 {
-            let (follower_id, follower_dist) = dists[idx + 1];
-            let mut follower = self.cars.get_mut(&follower_id).unwrap();
-            // TODO If the leader vanished at a border node, this still jumps a bit -- the
-            // lead car's back is still sticking out. Need to still be bound by them, even
-            // though they don't exist! If the leader just parked, then we're fine.
-            match follower.state {
-                CarState::Queued { blocked_since } => {
-                    // Prevent them from jumping forwards.
-                    follower.total_blocked_time += now - blocked_since;
-                    follower.state = follower.crossing_state(follower_dist, now, map);
-                    scheduler.update(
-                        follower.state.get_end_time(),
-                        Command::UpdateCar(follower_id),
-                    );
+            self.leader_gone(dists[idx + 1], now, map, scheduler);
+        }
+    }
+
+    // The car in front of this follower just left their queue.
+    fn leader_gone(
+        &mut self,
+        (follower_id, follower_dist): (CarID, Distance),
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let follower = self.cars.get_mut(&follower_id).unwrap();
+        match follower.state {
+            CarState::Queued { blocked_since } => {
+                // Prevent them from jumping forwards.
+                follower.total_blocked_time += now - blocked_since;
+                follower.state =
+                    follower.crossing_state(follower_dist, now, map, self.car_following);
+                scheduler.update(
+                    follower.state.get_end_time(),
+                    Command::UpdateCar(follower_id),
+                );
+            }
+            CarState::Crossing(_, _, _) => {
+                // If the follower was still Crossing, they might not've been blocked by leader
+                // yet. In that case, recalculating their Crossing state is a no-op.
+                follower.state =
+                    follower.crossing_state(follower_dist, now, map, self.car_following);
+                scheduler.update(
+                    follower.state.get_end_time(),
+                    Command::UpdateCar(follower_id),
+                );
+            }
+            // They weren't blocked
+            CarState::Unparking(_, _, _)
+            | CarState::Parking(_, _, _)
+            | CarState::IdlingAtStop(_, _) => {}
+            CarState::WaitingToAdvance { .. } => unreachable!(),
+        }
+        // There might be somebody else in front of them now
+        self.follow(follower_id, now, map, scheduler);
+    }
+
+    // Whoever's right behind this car in their queue
+    fn get_follower(&self, id: CarID) -> Option<CarID> {
+        let queue = &self.queues[&self.cars[&id].router.head()];
+        let idx = queue.cars.iter().position(|c| *c == id)?;
+        queue.cars.get(idx + 1).cloned()
+    }
+
+    // Car-following: re-plan this car's next move, given the gap to whoever's in front and how fast
+    // they're going. Called whenever the car or their leader does something new.
+    fn follow(&mut self, id: CarID, now: Time, map: &Map, scheduler: &mut Scheduler) {
+        if !self.car_following {
+            return;
+        }
+        let car = if let Some(car) = self.cars.get(&id) {
+            car
+        } else {
+            return;
+        };
+        match car.state {
+            CarState::Crossing(_, _, _) => {}
+            // If they're on their last step, they might be ending early and not right behind
+            // their leader.
+            CarState::Queued { .. } if !car.router.last_step() => {}
+            _ => {
+                return;
+            }
+        }
+        let queue = &self.queues[&car.router.head()];
+        let dists = queue.get_car_positions(now, &self.cars, &self.queues);
+        let idx = dists.iter().position(|(c, _)| *c == id).unwrap();
+        let front = dists[idx].1;
+        let leader = if idx > 0 {
+            let leader = &self.cars[&dists[idx - 1].0];
+            Some((leader, dists[idx - 1].1 - leader.vehicle.length))
+        } else {
+            queue
+                .laggy_head_back(now, &self.cars, &self.queues)
+                .map(|(leader, back)| (&self.cars[&leader], back))
+        };
+
+        let new_state = match (leader, &car.state) {
+            (Some((leader, leader_back)), _) => {
+                let leader_speed = leader.speed(now);
+                // Queued cars only need to move once their leader does
+                if leader_speed.is_none() && matches!(car.state, CarState::Queued { .. }) {
+                    return;
                 }
-                CarState::Crossing(_, _) => {
-                    // If the follower was still Crossing, they might not've been blocked
-                    // by leader yet. In that case, recalculating their Crossing state is a
-                    // no-op.
-                    follower.state = follower.crossing_state(follower_dist, now, map);
-                    scheduler.update(
-                        follower.state.get_end_time(),
-                        Command::UpdateCar(follower_id),
-                    );
+                car.following_state(front, leader_back, leader_speed, now, map)
+            }
+            (None, CarState::Crossing(_, dist_int, profile)) => {
+                // Nobody's in front anymore. Unless the car was already going all the way, let
+                // them.
+                let free = car.crossing_state(front, now, map, true);
+                let all_the_way = match free {
+                    CarState::Crossing(_, ref free_dist, _) => free_dist.end == dist_int.end,
+                    _ => unreachable!(),
+                };
+                if all_the_way && !profile.as_ref().map_or(false, |p| p.is_following()) {
+                    return;
                 }
-                // They weren't blocked
-                CarState::Unparking(_, _, _)
-                | CarState::Parking(_, _, _)
-                | CarState::IdlingAtStop(_, _) => {}
-                CarState::WaitingToAdvance { .. } => unreachable!(),
+                free
+            }
+            (None, _) => {
+                return;
+            }
+        };
+
+        let car = self.cars.get_mut(&id).unwrap();
+        if let CarState::Queued { blocked_since } = car.state {
+            car.total_blocked_time += now - blocked_since;
+        }
+        car.state = new_state;
+        scheduler.update(car.state.get_end_time(), Command::UpdateCar(id));
+    }
+
+    // A car that just got stuck behind somebody slower partway along a lane -- a bike, a bus at a
+    // stop, a double-parked truck -- pulls into the lane next to them, if there's a gap there and
+    // the leader in that lane is farther ahead. It's an instant hop sideways, keeping the same
+    // relative position. Ordinary queues, like at a signal, don't count.
+    // TODO Only checked once, when the car first gets stuck. No passing through oncoming traffic.
+    fn try_mid_block_lanechange(
+        &mut self,
+        id: CarID,
+        now: Time,
+        map: &Map,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) {
+        let car = &self.cars[&id];
+        let blocked_since = match car.state {
+            CarState::Queued { blocked_since } => blocked_since,
+            _ => {
+                return;
+            }
+        };
+        if car.router.last_step() || !car.last_steps.is_empty() {
+            return;
+        }
+        let queue = &self.queues[&car.router.head()];
+        if queue.cars[0] == id {
+            return;
+        }
+        let dists = queue.get_car_positions(now, &self.cars, &self.queues);
+        let idx = dists.iter().position(|(c, _)| *c == id).unwrap();
+        let our_dist = dists[idx].1;
+        let leader_dist = dists[idx - 1].1;
+        let leader = &self.cars[&dists[idx - 1].0];
+        let leader_slower = match leader.state {
+            CarState::IdlingAtStop(_, _)
+            | CarState::Parking(_, _, _)
+            | CarState::Unparking(_, _, _) => true,
+            CarState::Crossing(_, _, _) => {
+                leader.speed_on(queue.id, map, self.car_following)
+                    < car.speed_on(queue.id, map, self.car_following)
+            }
+            // Also stuck behind somebody
+            CarState::Queued { .. } | CarState::WaitingToAdvance { .. } => false,
+        };
+        if !leader_slower {
+            return;
+        }
+
+        let mut best: Option<(LaneID, TurnID, Distance, usize)> = None;
+        for (lane, turn) in car.router.adjacent_lanes(map) {
+            let other = &self.queues[&Traversable::Lane(lane)];
+            if !other.room_for_car(car) {
+                continue;
+            }
+            // Lanes on the same road don't quite have the same length
+            let ratio = other.geom_len / queue.geom_len;
+            let new_dist = (our_dist * ratio)
+                .min(other.geom_len)
+                .max(car.vehicle.length);
+            if let Some(new_idx) = other.get_idx_to_insert_car(
+                new_dist,
+                car.vehicle.length,
+                now,
+                &self.cars,
+                &self.queues,
+            ) {
+                if new_idx != 0 {
+                    let new_leader_dist =
+                        other.get_car_positions(now, &self.cars, &self.queues)[new_idx - 1].1;
+                    if new_leader_dist <= leader_dist * ratio {
+                        continue;
+                    }
+                }
+                best = Some((lane, turn, new_dist, new_idx));
+                break;
+            }
+        }
+        let (lane, turn, new_dist, new_idx) = if let Some(x) = best {
+            x
+        } else {
+            return;
+        };
+
+        let mut car = self.cars.remove(&id).unwrap();
+        {
+            let queue = self.queues.get_mut(&car.router.head()).unwrap();
+            assert_eq!(queue.cars.remove(idx).unwrap(), id);
+            queue.free_reserved_space(&car);
+            intersections.space_freed(now, map.get_l(queue.id.as_lane()).src_i, scheduler, map);
+        }
+        {
+            let queue = self.queues.get_mut(&Traversable::Lane(lane)).unwrap();
+            queue.cars.insert(new_idx, id);
+            queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+        }
+        self.events.push(Event::CarChangedLanes {
+            car: id,
+            old_turn: car.router.next().as_turn(),
+            new_turn: turn,
+        });
+        car.router.change_current_lane(lane, turn, map);
+        car.total_blocked_time += now - blocked_since;
+        car.state = car.crossing_state(new_dist, now, map, self.car_following);
+        scheduler.update(car.state.get_end_time(), Command::UpdateCar(id));
+        self.cars.insert(id, car);
+        // Whoever's behind in the new lane has to react
+        if let Some(follower) = self.get_follower(id) {
+            self.follow(follower, now, map, scheduler);
+        }
+
+        if idx != dists.len() - 1 {
+            self.leader_gone(dists[idx + 1], now, map, scheduler);
+        }
+    }
+
+    // Cars queued behind a driver who just found a way around a jam would otherwise keep heading
+    // into it. They're still partway along the lane, so they just pick a new way from its end.
+    fn reroute_followers(
+        &mut self,
+        leader: CarID,
+        lane: Traversable,
+        jammed_turn: Traversable,
+        map: &Map,
+    ) {
+        let followers: Vec<CarID> = self.queues[&lane]
+            .cars
+            .iter()
+            .filter(|c| **c != leader)
+            .cloned()
+            .collect();
+        for id in followers {
+            let car = self.cars.get_mut(&id).unwrap();
+            if !matches!(car.state, CarState::Queued { .. })
+                || car.router.head() != lane
+                || car.router.last_step()
+                || car.router.next() != jammed_turn
+            {
+                continue;
+            }
+            if let Some(old_path) =
+                car.router
+                    .reroute_around_congestion(&car.vehicle, &self.queues, map)
+            {
+                self.events.push(Event::CarRerouted {
+                    car: id,
+                    lane: lane.as_lane(),
+                    old_path,
+                    new_path: car.router.get_path().clone(),
+                });
             }
         }
     }
@@ -735,6 +1155,7 @@ impl DrivingSimState {
                     ),
                     now,
                     map,
+                    self.car_following,
                 )
                 .get_end_time();
             // Sometimes due to rounding, retry_at will be exactly time, but we really need to
@@ -789,7 +1210,7 @@ impl DrivingSimState {
             if i == 0 {
                 // Wake up the follower
                 if let Some(follower_id) = old_queue.cars.front() {
-                    let mut follower = self.cars.get_mut(&follower_id).unwrap();
+                    let follower = self.cars.get_mut(&follower_id).unwrap();
 
                     match follower.state {
                         CarState::Queued { blocked_since } => {
@@ -812,7 +1233,7 @@ impl DrivingSimState {
                         // They weren't blocked. Note that there's no way the Crossing state
                         // could jump forwards here; the leader
                         // vanished from the end of the traversable.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::IdlingAtStop(_, _) => {}
@@ -907,6 +1328,19 @@ impl DrivingSimState {
         }
     }
 
+    // A virtual loop detector covering the last `length` of a lane. Is any vehicle over it?
+    pub fn detector_occupied(&self, now: Time, lane: LaneID, length: Distance) -> bool {
+        match self.queues.get(&Traversable::Lane(lane)) {
+            // The first car is the farthest along
+            Some(q) => q
+                .get_car_positions(now, &self.cars, &self.queues)
+                .get(0)
+                .map(|(_, dist)| *dist + length >= q.geom_len)
+                .unwrap_or(false),
+            None => false,
+        }
+    }
+
     pub fn debug_car(&self, id: CarID) {
         if let Some(ref car) = self.cars.get(&id) {
             println!("{}", abstutil::to_json(car));
@@ -1047,3 +1481,29 @@ impl DrivingSimState {
         std::mem::replace(&mut self.events, Vec::new())
     }
 }
+
+// Buses and trains check in with the traffic signal ahead as soon as they start moving along a
+// lane, so it has a chance to give them priority.
+fn transit_check_in(
+    car: &Car,
+    now: Time,
+    map: &Map,
+    intersections: &mut IntersectionSimState,
+    scheduler: &mut Scheduler,
+) {
+    if car.vehicle.vehicle_type != VehicleType::Bus
+        && car.vehicle.vehicle_type != VehicleType::Train
+    {
+        return;
+    }
+    if let Some(Traversable::Turn(t)) = car.router.maybe_next() {
+        intersections.transit_approaching(
+            now,
+            car.vehicle.id,
+            t,
+            car.state.get_end_time(),
+            map,
+            scheduler,
+        );
+    }
+}
@@ -1,10 +1,10 @@
 use crate::mechanics::car::Car;
-use crate::mechanics::Queue;
+use crate::mechanics::{DrivingSimState, Queue};
 use crate::{AgentID, AlertLocation, CarID, Command, Event, Scheduler, Speed, TripMode};
 use abstutil::{deserialize_btreemap, retain_btreeset, serialize_btreemap};
-use geom::{Duration, Time};
+use geom::{Distance, Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, PhaseType, RoadID,
+    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, Phase, PhaseType, RoadID,
     Traversable, TurnID, TurnPriority, TurnType,
 };
 use serde::{Deserialize, Serialize};
@@ -12,6 +12,8 @@ use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
+// Actuated signals detect vehicles over this much of the end of each incoming lane
+const DETECTOR_LENGTH: Distance = Distance::const_meters(20.0);
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct IntersectionSimState {
@@ -42,7 +44,10 @@ struct State {
 
     // Only relevant for traffic signals
     current_phase: usize,
+    phase_started_at: Time,
     phase_ends_at: Time,
+    // Has transit signal priority already adjusted this phase?
+    transit_priority_used: bool,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -76,11 +81,13 @@ impl IntersectionSimState {
                     waiting: BTreeMap::new(),
                     reserved: BTreeSet::new(),
                     current_phase: 0,
+                    phase_started_at: Time::START_OF_DAY,
                     phase_ends_at: Time::START_OF_DAY,
+                    transit_priority_used: false,
                 },
             );
             if i.is_traffic_signal() && !use_freeform_policy_everywhere {
-                sim.update_intersection(Time::START_OF_DAY, i.id, map, scheduler);
+                sim.start_first_phase(i.id, map, scheduler);
             }
         }
         sim
@@ -214,49 +221,165 @@ impl IntersectionSimState {
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
+        driving: &DrivingSimState,
     ) {
         let state = self.state.get_mut(&id).unwrap();
         let signal = map.get_traffic_signal(id);
 
         // Switch to a new phase?
-        if now != Time::START_OF_DAY {
-            assert_eq!(now, state.phase_ends_at);
-            let old_phase = &signal.phases[state.current_phase];
-            match old_phase.phase_type {
-                PhaseType::Fixed(_) => {
-                    state.current_phase += 1;
+        assert_eq!(now, state.phase_ends_at);
+        let old_phase = &signal.phases[state.current_phase];
+        match old_phase.phase_type {
+            PhaseType::Fixed(_) => {
+                state.current_phase += 1;
+            }
+            PhaseType::Actuated {
+                max_green, passage, ..
+            } => {
+                // Keep extending the green while somebody's still coming, until max_green
+                let max_end = state.phase_started_at + max_green;
+                if now < max_end
+                    && actuated_demand(now, old_phase, signal, &state.waiting, driving, map)
+                {
+                    state.phase_ends_at = max_end.min(now + passage);
+                    scheduler.push(state.phase_ends_at, Command::UpdateIntersection(id));
+                    return;
                 }
-                PhaseType::Adaptive(_) => {
-                    // TODO Make a better policy here. For now, if there's _anyone_ waiting to
-                    // start a protected turn, repeat this phase for the full duration. Note that
-                    // "waiting" is only defined as "at the end of the lane, ready to start the
-                    // turn." If a vehicle/ped is a second away from the intersection, this won't
-                    // detect that. We could pass in all of the Queues here and use that to count
-                    // all incoming agents, even ones a little farther away.
-                    if state.waiting.keys().all(|req| {
-                        old_phase.get_priority_of_turn(req.turn, signal) != TurnPriority::Protected
-                    }) {
-                        state.current_phase += 1;
-                        self.events.push(Event::Alert(
-                            AlertLocation::Intersection(id),
-                            "Repeating an adaptive phase".to_string(),
-                        ));
-                    }
+                state.current_phase += 1;
+            }
+            PhaseType::Adaptive(_) => {
+                // TODO Make a better policy here. For now, if there's _anyone_ waiting to
+                // start a protected turn, repeat this phase for the full duration. Note that
+                // "waiting" is only defined as "at the end of the lane, ready to start the
+                // turn." If a vehicle/ped is a second away from the intersection, this won't
+                // detect that. We could pass in all of the Queues here and use that to count
+                // all incoming agents, even ones a little farther away.
+                if state.waiting.keys().all(|req| {
+                    old_phase.get_priority_of_turn(req.turn, signal) != TurnPriority::Protected
+                }) {
+                    state.current_phase += 1;
+                    self.events.push(Event::Alert(
+                        AlertLocation::Intersection(id),
+                        "Repeating an adaptive phase".to_string(),
+                    ));
                 }
             }
-            if state.current_phase == signal.phases.len() {
-                state.current_phase = 0;
+        }
+        if state.current_phase == signal.phases.len() {
+            state.current_phase = 0;
+        }
+        self.start_phase(now, id, map, scheduler);
+    }
+
+    // Start the day partway through the cycle, so that phase 0 begins at the signal's offset, then
+    // every cycle length after that. Only fixed phases stay exactly in step.
+    fn start_first_phase(&mut self, id: IntersectionID, map: &Map, scheduler: &mut Scheduler) {
+        let signal = map.get_traffic_signal(id);
+        let cycle_length = signal.cycle_length();
+        let into_cycle = (cycle_length - signal.offset % cycle_length) % cycle_length;
+        if into_cycle == Duration::ZERO {
+            self.start_phase(Time::START_OF_DAY, id, map, scheduler);
+            return;
+        }
+
+        let state = self.state.get_mut(&id).unwrap();
+        let mut phase_start = Duration::ZERO;
+        for (idx, phase) in signal.phases.iter().enumerate() {
+            let phase_end = phase_start + phase.phase_type.simple_duration();
+            if into_cycle < phase_end {
+                state.current_phase = idx;
+                // Close enough, even though the phase really started before midnight
+                state.phase_started_at = Time::START_OF_DAY;
+                state.phase_ends_at = Time::START_OF_DAY + (phase_end - into_cycle);
+                break;
             }
+            phase_start = phase_end;
         }
+        scheduler.push(state.phase_ends_at, Command::UpdateIntersection(id));
+    }
+
+    fn start_phase(&mut self, now: Time, id: IntersectionID, map: &Map, scheduler: &mut Scheduler) {
+        let state = self.state.get_mut(&id).unwrap();
+        let signal = map.get_traffic_signal(id);
+        state.transit_priority_used = false;
 
+        state.phase_started_at = now;
         state.phase_ends_at = now
-            + signal.phases[state.current_phase]
-                .phase_type
-                .simple_duration();
+            + match signal.phases[state.current_phase].phase_type {
+                // Check the detectors once the minimum green is up
+                PhaseType::Actuated { min_green, .. } => min_green,
+                ref x => x.simple_duration(),
+            };
         scheduler.push(state.phase_ends_at, Command::UpdateIntersection(id));
         self.wakeup_waiting(now, id, scheduler, map);
     }
 
+    // Buses and trains call this when they start on a lane leading to a traffic signal, with the
+    // time they expect to reach the end of the lane. If the signal has transit priority, the
+    // current phase may be held open or ended early.
+    pub fn transit_approaching(
+        &mut self,
+        now: Time,
+        bus: CarID,
+        turn: TurnID,
+        eta: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        if self.use_freeform_policy_everywhere {
+            return;
+        }
+        let signal = if let Some(signal) = map.maybe_get_traffic_signal(turn.parent) {
+            signal
+        } else {
+            return;
+        };
+        let tsp = if let Some(ref tsp) = signal.transit_priority {
+            tsp
+        } else {
+            return;
+        };
+        let state = self.state.get_mut(&turn.parent).unwrap();
+        if state.transit_priority_used {
+            return;
+        }
+
+        let next_phase = &signal.phases[(state.current_phase + 1) % signal.phases.len()];
+        let new_end = if signal.phases[state.current_phase].get_priority_of_turn(turn, signal)
+            == TurnPriority::Protected
+        {
+            // Hold the green long enough for the bus to finish the turn
+            let done =
+                eta + map.get_t(turn).geom.length() / Traversable::Turn(turn).speed_limit(map);
+            if done <= state.phase_ends_at || done > state.phase_ends_at + tsp.max_extension {
+                return;
+            }
+            done
+        } else if next_phase.get_priority_of_turn(turn, signal) == TurnPriority::Protected {
+            // Bring the bus's phase forward
+            let earliest = now.max(state.phase_ends_at - tsp.max_early_end);
+            let end = eta.max(earliest);
+            if end >= state.phase_ends_at {
+                return;
+            }
+            end
+        } else {
+            return;
+        };
+
+        let extended = new_end > state.phase_ends_at;
+        let dt = if extended {
+            new_end - state.phase_ends_at
+        } else {
+            state.phase_ends_at - new_end
+        };
+        state.phase_ends_at = new_end;
+        state.transit_priority_used = true;
+        scheduler.update(new_end, Command::UpdateIntersection(turn.parent));
+        self.events
+            .push(Event::TransitSignalPriority(turn.parent, bus, dt, extended));
+    }
+
     // For cars: The head car calls this when they're at the end of the lane WaitingToAdvance. If
     // this returns true, then the head car MUST actually start this turn.
     // For peds: Likewise -- only called when the ped is at the start of the turn. They must
@@ -583,9 +706,29 @@ impl IntersectionSimState {
         let state = &self.state[&req.turn.parent];
         let phase = &signal.phases[state.current_phase];
         let full_phase_duration = phase.phase_type.simple_duration();
-        let remaining_phase_time = state.phase_ends_at - now;
+        let remaining_phase_time = match phase.phase_type {
+            // The phase might be extended up to max_green. If it ends sooner, the box is blocked
+            // briefly, but the detectors should keep the phase going while anyone's arriving.
+            PhaseType::Actuated { max_green, .. } => {
+                state.phase_ends_at.max(state.phase_started_at + max_green) - now
+            }
+            _ => state.phase_ends_at - now,
+        };
         let our_time = state.waiting[req];
 
+        // Some turns start partway into the phase, like vehicles behind a leading pedestrian
+        // interval.
+        if let Some(delay) = phase.get_start_delay(req.turn, signal) {
+            let starts_at = state.phase_started_at + delay;
+            if now < starts_at {
+                // The agent might already be scheduled to check again, like after a phase change
+                if let Some(s) = scheduler {
+                    s.update_if_sooner(starts_at, Command::update_agent(req.agent));
+                }
+                return false;
+            }
+        }
+
         // Can't go at all this phase.
         let our_priority = phase.get_priority_of_turn(req.turn, signal);
         if our_priority == TurnPriority::Banned {
@@ -742,3 +885,27 @@ fn allow_block_the_box(osm_node_id: i64) -> bool {
     // 23rd and Madison
     osm_node_id == 53211694 || osm_node_id == 53211693
 }
+
+// Should an actuated phase be extended? Virtual loop detectors cover the end of every lane feeding
+// a protected movement, and pedestrians waiting to cross count too.
+fn actuated_demand(
+    now: Time,
+    phase: &Phase,
+    signal: &ControlTrafficSignal,
+    waiting: &BTreeMap<Request, Time>,
+    driving: &DrivingSimState,
+    map: &Map,
+) -> bool {
+    if waiting
+        .keys()
+        .any(|req| phase.get_priority_of_turn(req.turn, signal) == TurnPriority::Protected)
+    {
+        return true;
+    }
+    phase
+        .protected_groups
+        .iter()
+        .filter(|g| !g.crosswalk)
+        .flat_map(|g| signal.turn_groups[g].members.iter())
+        .any(|t| driving.detector_occupied(now, t.src, DETECTOR_LENGTH))
+}
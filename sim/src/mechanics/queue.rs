@@ -42,6 +42,29 @@ impl Queue {
         self.inner_get_car_positions(now, cars, queues, &mut BTreeSet::new())
     }
 
+    // Where the back of the laggy head is, measured along this queue. Once they're out of the way,
+    // this is past the end.
+    pub fn laggy_head_back(
+        &self,
+        now: Time,
+        cars: &BTreeMap<CarID, Car>,
+        queues: &BTreeMap<Traversable, Queue>,
+    ) -> Option<(CarID, Distance)> {
+        let id = self.laggy_head?;
+        let leader = &cars[&id];
+        let (_, mut dist) = *queues[&leader.router.head()]
+            .get_car_positions(now, cars, queues)
+            .last()
+            .unwrap();
+        for on in &leader.last_steps {
+            if *on == self.id {
+                break;
+            }
+            dist += queues[on].geom_len;
+        }
+        Some((id, self.geom_len + dist - leader.vehicle.length))
+    }
+
     fn inner_get_car_positions(
         &self,
         now: Time,
@@ -143,10 +166,14 @@ impl Queue {
                     assert_eq!(bound, self.geom_len);
                     self.geom_len
                 }
-                CarState::Crossing(ref time_int, ref dist_int) => {
+                CarState::Crossing(ref time_int, ref dist_int, ref profile) => {
                     // TODO Why percent_clamp_end? We process car updates in any order, so we might
                     // calculate this before moving this car from Crossing to another state.
-                    dist_int.lerp(time_int.percent_clamp_end(now)).min(bound)
+                    match profile {
+                        Some(p) => p.front(time_int, dist_int, now),
+                        None => dist_int.lerp(time_int.percent_clamp_end(now)),
+                    }
+                    .min(bound)
                 }
                 CarState::Unparking(front, _, _) => front,
                 CarState::Parking(front, _, _) => front,
@@ -256,7 +283,7 @@ fn dump_cars(
         let car = &cars[id];
         println!("- {} @ {} (length {})", id, dist, car.vehicle.length);
         match car.state {
-            CarState::Crossing(ref time_int, ref dist_int) => {
+            CarState::Crossing(ref time_int, ref dist_int, _) => {
                 println!(
                     "  Going {} .. {} during {} .. {}",
                     dist_int.start, dist_int.end, time_int.start, time_int.end
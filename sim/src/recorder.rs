@@ -0,0 +1,196 @@
+use crate::{Analytics, Event};
+use abstutil::Timer;
+use geom::Time;
+use map_model::Map;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Write};
+
+// Streams every event the simulation produces to a file, so that a run can be analyzed later (or
+// by other tools) without rerunning it. Files ending in .jsonl get one JSON object per line;
+// anything else is written as a compact stream of bincode records.
+pub struct EventRecorder {
+    path: String,
+    // None for clones of the simulation. Only the original writes to the file.
+    writer: Option<BufWriter<File>>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum LogEntry {
+    // Always the first entry
+    Header { map_name: String, run_name: String },
+    Event(Time, Event),
+}
+
+impl EventRecorder {
+    pub fn new(path: String, map_name: &str, run_name: &str) -> EventRecorder {
+        if let Some(parent) = std::path::Path::new(&path).parent() {
+            std::fs::create_dir_all(parent).expect("Creating parent dir failed");
+        }
+        let file = File::create(&path)
+            .unwrap_or_else(|err| panic!("Can't record events to {}: {}", path, err));
+        let mut recorder = EventRecorder {
+            path,
+            writer: Some(BufWriter::new(file)),
+        };
+        recorder.write(&LogEntry::Header {
+            map_name: map_name.to_string(),
+            run_name: run_name.to_string(),
+        });
+        recorder
+    }
+
+    pub fn record(&mut self, time: Time, ev: &Event) {
+        // TODO Avoid the clone. LogEntry could borrow the Event, but then it can't also be used
+        // for reading.
+        self.write(&LogEntry::Event(time, ev.clone()));
+    }
+
+    pub fn flush(&mut self) {
+        if let Some(ref mut writer) = self.writer {
+            if let Err(err) = writer.flush() {
+                panic!("Can't flush events to {}: {}", self.path, err);
+            }
+        }
+    }
+
+    fn write(&mut self, entry: &LogEntry) {
+        let jsonl = is_jsonl(&self.path);
+        if let Some(ref mut writer) = self.writer {
+            let result = if jsonl {
+                serde_json::to_writer(&mut *writer, entry)
+                    .map_err(|err| Error::new(ErrorKind::Other, err))
+                    .and_then(|_| writer.write_all(b"\n"))
+            } else {
+                bincode::serialize_into(&mut *writer, entry)
+                    .map_err(|err| Error::new(ErrorKind::Other, err))
+            };
+            if let Err(err) = result {
+                panic!("Can't record events to {}: {}", self.path, err);
+            }
+        }
+    }
+}
+
+impl Clone for EventRecorder {
+    fn clone(&self) -> EventRecorder {
+        EventRecorder {
+            path: self.path.clone(),
+            writer: None,
+        }
+    }
+}
+
+fn is_jsonl(path: &str) -> bool {
+    path.ends_with(".jsonl")
+}
+
+// Rebuilds Analytics from a file written by EventRecorder. The map must be the same one (with the
+// same edits) that the simulation used.
+pub fn analytics_from_event_log(
+    path: String,
+    map: &Map,
+    timer: &mut Timer,
+) -> Result<Analytics, Error> {
+    timer.start(format!("replay events from {}", path));
+    let result = replay(&path, map);
+    timer.stop(format!("replay events from {}", path));
+    result
+}
+
+fn replay(path: &str, map: &Map) -> Result<Analytics, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut analytics = Analytics::new();
+    let mut first = true;
+    loop {
+        let entry: LogEntry = if is_jsonl(path) {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            serde_json::from_str(&line).map_err(|err| Error::new(ErrorKind::Other, err))?
+        } else {
+            if reader.fill_buf()?.is_empty() {
+                break;
+            }
+            bincode::deserialize_from(&mut reader)
+                .map_err(|err| Error::new(ErrorKind::Other, err))?
+        };
+
+        match entry {
+            LogEntry::Header { map_name, .. } => {
+                if !first {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("{} has more than one header", path),
+                    ));
+                }
+                if map_name != map.get_name() {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "{} was recorded on {}, not {}",
+                            path,
+                            map_name,
+                            map.get_name()
+                        ),
+                    ));
+                }
+            }
+            LogEntry::Event(time, ev) => {
+                if first {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("{} is missing a header", path),
+                    ));
+                }
+                analytics.event(ev, time, map);
+            }
+        }
+        first = false;
+    }
+    Ok(analytics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ScenarioGenerator, Sim, SimOptions};
+    use geom::Duration;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn test_replay_matches_live() {
+        let mut timer = Timer::throwaway();
+        let map = Map::new(abstutil::path_synthetic_map("signal_single"), &mut timer);
+        let path = std::env::temp_dir()
+            .join("abst_test_replay_events.bin")
+            .display()
+            .to_string();
+
+        let mut opts = SimOptions::new("test_replay");
+        opts.record_events = Some(path.clone());
+        let mut sim = Sim::new(&map, opts, &mut timer);
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        ScenarioGenerator::small_run(&map)
+            .generate(&map, &mut rng, &mut timer)
+            .instantiate(&mut sim, &map, &mut rng, &mut timer);
+        sim.timed_step(&map, Duration::minutes(10), &mut None, &mut timer);
+
+        let live = sim.get_analytics();
+        let replayed = analytics_from_event_log(path.clone(), &map, &mut timer).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(!live.finished_trips.is_empty());
+        assert_eq!(live.finished_trips, replayed.finished_trips);
+        assert_eq!(live.started_trips, replayed.started_trips);
+        assert_eq!(live.demand, replayed.demand);
+        assert_eq!(live.road_thruput.counts, replayed.road_thruput.counts);
+        assert_eq!(
+            live.intersection_thruput.counts,
+            replayed.intersection_thruput.counts
+        );
+        assert_eq!(live.intersection_delays, replayed.intersection_delays);
+    }
+}
@@ -0,0 +1,431 @@
+use crate::{
+    AlertLocation, CarID, Command, CreateCar, Event, ParkingSimState, PersonID, Router, Scheduler,
+    TripID, TripManager, TripSpec, Vehicle, VehicleSpec, VehicleType, MAX_CAR_LENGTH,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Time};
+use map_model::{BuildingID, LaneType, Map, Path, PathConstraints, PathRequest, Position};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+const TIME_TO_BOARD: Duration = Duration::const_seconds(30.0);
+const TIME_TO_ALIGHT: Duration = Duration::const_seconds(15.0);
+// Enough to serve trips on a small map without very long waits. Use --ride_hail_fleet on bigger
+// ones.
+pub const DEFAULT_FLEET_SIZE: usize = 50;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+struct Ride {
+    trip: TripID,
+    person: PersonID,
+    pickup: BuildingID,
+    dropoff: BuildingID,
+    requested_at: Time,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+struct RideHailVehicle {
+    vehicle: Vehicle,
+    // Where the vehicle waits when nobody needs a ride
+    home: Position,
+    state: RideHailState,
+    // How far the vehicle drives along the current path
+    leg_length: Distance,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+enum RideHailState {
+    // Not on the map, waiting to be dispatched from here
+    Idle(Position),
+    ToPickup(Ride),
+    ToDropoff(Ride),
+    // Driving empty back home
+    Repositioning,
+    // Somebody's getting in or out. Afterwards, follow the router and switch to the next state.
+    AtCurb(Box<RideHailState>, Router),
+}
+
+// An ownerless fleet of cars. Like TransitSimState, this manages transitions for the people
+// riding along.
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct RideHailSimState {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, RideHailVehicle>,
+    // Requests that couldn't be served immediately, oldest first
+    waiting: VecDeque<Ride>,
+
+    events: Vec<Event>,
+}
+
+impl RideHailSimState {
+    // Spread the fleet out evenly over the map.
+    pub fn new(fleet_size: usize, trips: &mut TripManager, map: &Map) -> RideHailSimState {
+        let mut state = RideHailSimState {
+            vehicles: BTreeMap::new(),
+            waiting: VecDeque::new(),
+            events: Vec::new(),
+        };
+        if fleet_size == 0 {
+            return state;
+        }
+
+        let lanes: Vec<Position> = map
+            .all_lanes()
+            .iter()
+            .filter(|l| {
+                l.lane_type == LaneType::Driving
+                    && l.parking_blackhole.is_none()
+                    && l.length() > MAX_CAR_LENGTH * 2.0
+            })
+            .map(|l| Position::new(l.id, l.length() / 2.0))
+            .collect();
+        if lanes.is_empty() {
+            println!("WARNING: No lanes to start a ride-hail fleet from");
+            return state;
+        }
+        for idx in 0..fleet_size {
+            let home = lanes[idx * lanes.len() / fleet_size];
+            let vehicle = VehicleSpec {
+                vehicle_type: VehicleType::Car,
+                length: MAX_CAR_LENGTH,
+                max_speed: None,
+                capacity: None,
+                max_accel: VehicleType::Car.default_max_accel(),
+                max_decel: VehicleType::Car.default_max_decel(),
+            }
+            .make(CarID(trips.new_car_id(), VehicleType::Car), None);
+            state.vehicles.insert(
+                vehicle.id,
+                RideHailVehicle {
+                    vehicle,
+                    home,
+                    state: RideHailState::Idle(home),
+                    leg_length: Distance::ZERO,
+                },
+            );
+        }
+        state
+    }
+
+    pub fn has_vehicle(&self, car: CarID) -> bool {
+        self.vehicles.contains_key(&car)
+    }
+
+    // Dispatches the closest idle vehicle, or waits for one to free up. Errors mean the trip
+    // can't be served at all.
+    pub fn request_ride(
+        &mut self,
+        now: Time,
+        trip: TripID,
+        person: PersonID,
+        pickup: BuildingID,
+        dropoff: BuildingID,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> Result<(), String> {
+        if self.vehicles.is_empty() {
+            return Err(format!("no ride-hail fleet to serve {}", trip));
+        }
+        let ride = Ride {
+            trip,
+            person,
+            pickup,
+            dropoff,
+            requested_at: now,
+        };
+        let pickup_pos = TripSpec::curb_pos(pickup, VehicleType::Car, map);
+        let closest = self
+            .vehicles
+            .values()
+            .filter_map(|v| match v.state {
+                RideHailState::Idle(pos) => Some((v.vehicle.id, pos)),
+                _ => None,
+            })
+            .min_by_key(|(_, pos)| pos.pt(map).dist_to(pickup_pos.pt(map)));
+        if let Some((car, pos)) = closest {
+            if !self.dispatch_idle(now, car, pos, ride, map, scheduler) {
+                return Err(format!(
+                    "no path for a ride-hail vehicle to pick up at {}",
+                    pickup
+                ));
+            }
+        } else {
+            self.waiting.push_back(ride);
+        }
+        Ok(())
+    }
+
+    // If Some, the vehicle idles at the curb this long, then asks for its next router. If None,
+    // the vehicle vanishes and waits to be dispatched.
+    pub fn vehicle_reached_curb(
+        &mut self,
+        now: Time,
+        car: CarID,
+        pos: Position,
+        trips: &mut TripManager,
+        parking: &mut ParkingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) -> Option<Duration> {
+        let mut v = self.vehicles.remove(&car).unwrap();
+        let state = std::mem::replace(&mut v.state, RideHailState::Repositioning);
+        let result = match state {
+            RideHailState::ToPickup(ride) => {
+                self.events
+                    .push(Event::RideHailLegFinished(car, v.leg_length, false));
+                let req = PathRequest {
+                    start: pos,
+                    end: TripSpec::curb_pos(ride.dropoff, VehicleType::Car, map),
+                    constraints: PathConstraints::Car,
+                };
+                if let Some(path) = map.pathfind(req.clone()) {
+                    self.events.push(Event::RideHailPickup(
+                        ride.trip,
+                        car,
+                        ride.pickup,
+                        now - ride.requested_at,
+                    ));
+                    trips.ride_hail_picked_up(ride.trip, car, req.clone());
+                    v.leg_length = leg_length(&path, pos.dist_along(), map);
+                    v.state = RideHailState::AtCurb(
+                        Box::new(RideHailState::ToDropoff(ride)),
+                        Router::ride_hail(path, req.end.dist_along()),
+                    );
+                    Some(TIME_TO_BOARD)
+                } else {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(ride.person),
+                        format!(
+                            "Aborting {} because no path for the ride-hail vehicle {}",
+                            ride.trip, req
+                        ),
+                    ));
+                    trips.abort_trip(now, ride.trip, None, parking, scheduler, map);
+                    let (next, router) =
+                        self.next_assignment(now, &mut v, pos, trips, parking, scheduler, map);
+                    v.state = RideHailState::AtCurb(Box::new(next), router);
+                    Some(Duration::ZERO)
+                }
+            }
+            RideHailState::ToDropoff(ride) => {
+                self.events
+                    .push(Event::RideHailLegFinished(car, v.leg_length, true));
+                self.events
+                    .push(Event::RideHailDropoff(ride.trip, car, ride.dropoff));
+                trips.ride_hail_dropped_off(now, car, map, parking, scheduler);
+                let (next, router) =
+                    self.next_assignment(now, &mut v, pos, trips, parking, scheduler, map);
+                v.state = RideHailState::AtCurb(Box::new(next), router);
+                Some(TIME_TO_ALIGHT)
+            }
+            RideHailState::Repositioning => {
+                self.events
+                    .push(Event::RideHailLegFinished(car, v.leg_length, false));
+                // Wait here, unless there's no room to respawn later
+                let idle_at =
+                    TripSpec::spawn_vehicle_at(pos, VehicleType::Car, map).unwrap_or(v.home);
+                v.state = RideHailState::Idle(idle_at);
+                None
+            }
+            RideHailState::Idle(_) | RideHailState::AtCurb(_, _) => unreachable!(),
+        };
+        self.vehicles.insert(car, v);
+
+        if result.is_none() {
+            // Somebody might've been waiting for a vehicle to free up
+            if let Some(ride) = self.waiting.pop_front() {
+                if let RideHailState::Idle(pos) = self.vehicles[&car].state {
+                    if !self.dispatch_idle(now, car, pos, ride.clone(), map, scheduler) {
+                        self.events.push(Event::Alert(
+                            AlertLocation::Person(ride.person),
+                            format!(
+                                "Aborting {} because no ride-hail vehicle can reach {}",
+                                ride.trip, ride.pickup
+                            ),
+                        ));
+                        trips.abort_trip(now, ride.trip, None, parking, scheduler, map);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub fn vehicle_departed_curb(&mut self, car: CarID) -> Router {
+        let v = self.vehicles.get_mut(&car).unwrap();
+        match std::mem::replace(&mut v.state, RideHailState::Repositioning) {
+            RideHailState::AtCurb(next, router) => {
+                v.state = *next;
+                router
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // The trip was aborted or cancelled, so forget about the ride. A vehicle already on its way
+    // finishes the leg empty and waits there.
+    pub fn cancel_ride(&mut self, trip: TripID) {
+        self.waiting.retain(|ride| ride.trip != trip);
+        for v in self.vehicles.values_mut() {
+            let state = match v.state {
+                RideHailState::AtCurb(ref mut next, _) => &mut **next,
+                ref mut state => state,
+            };
+            let cancelled = match *state {
+                RideHailState::ToPickup(ref ride) | RideHailState::ToDropoff(ref ride) => {
+                    ride.trip == trip
+                }
+                _ => false,
+            };
+            if cancelled {
+                *state = RideHailState::Repositioning;
+            }
+        }
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    // (idle, driving empty, carrying a passenger, requests waiting for a vehicle)
+    pub fn fleet_status(&self) -> (usize, usize, usize, usize) {
+        let mut idle = 0;
+        let mut empty = 0;
+        let mut occupied = 0;
+        for v in self.vehicles.values() {
+            match v.state {
+                RideHailState::Idle(_) => {
+                    idle += 1;
+                }
+                RideHailState::ToPickup(_) | RideHailState::Repositioning => {
+                    empty += 1;
+                }
+                RideHailState::ToDropoff(_) => {
+                    occupied += 1;
+                }
+                RideHailState::AtCurb(ref next, _) => match **next {
+                    RideHailState::ToDropoff(_) => {
+                        occupied += 1;
+                    }
+                    _ => {
+                        empty += 1;
+                    }
+                },
+            }
+        }
+        (idle, empty, occupied, self.waiting.len())
+    }
+
+    // True if the vehicle is on its way
+    fn dispatch_idle(
+        &mut self,
+        now: Time,
+        car: CarID,
+        from: Position,
+        ride: Ride,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let req = PathRequest {
+            start: from,
+            end: TripSpec::curb_pos(ride.pickup, VehicleType::Car, map),
+            constraints: PathConstraints::Car,
+        };
+        let path = if let Some(path) = map.pathfind(req.clone()) {
+            path
+        } else {
+            return false;
+        };
+        let v = self.vehicles.get_mut(&car).unwrap();
+        v.leg_length = leg_length(&path, from.dist_along(), map);
+        v.state = RideHailState::ToPickup(ride);
+        scheduler.push(
+            now,
+            Command::SpawnCar(
+                CreateCar {
+                    vehicle: v.vehicle.clone(),
+                    router: Router::ride_hail(path, req.end.dist_along()),
+                    req,
+                    start_dist: from.dist_along(),
+                    maybe_parked_car: None,
+                    trip_and_person: None,
+                    maybe_route: None,
+                },
+                true,
+            ),
+        );
+        true
+    }
+
+    // After finishing with somebody at the curb, go pick up the next waiting person, or head
+    // home.
+    fn next_assignment(
+        &mut self,
+        now: Time,
+        v: &mut RideHailVehicle,
+        from: Position,
+        trips: &mut TripManager,
+        parking: &mut ParkingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) -> (RideHailState, Router) {
+        while let Some(ride) = self.waiting.pop_front() {
+            let req = PathRequest {
+                start: from,
+                end: TripSpec::curb_pos(ride.pickup, VehicleType::Car, map),
+                constraints: PathConstraints::Car,
+            };
+            if let Some(path) = map.pathfind(req.clone()) {
+                v.leg_length = leg_length(&path, from.dist_along(), map);
+                return (
+                    RideHailState::ToPickup(ride),
+                    Router::ride_hail(path, req.end.dist_along()),
+                );
+            }
+            self.events.push(Event::Alert(
+                AlertLocation::Person(ride.person),
+                format!(
+                    "Aborting {} because no ride-hail vehicle can reach {}",
+                    ride.trip, ride.pickup
+                ),
+            ));
+            trips.abort_trip(now, ride.trip, None, parking, scheduler, map);
+        }
+
+        if from != v.home {
+            if let Some(path) = map.pathfind(PathRequest {
+                start: from,
+                end: v.home,
+                constraints: PathConstraints::Car,
+            }) {
+                v.leg_length = leg_length(&path, from.dist_along(), map);
+                return (
+                    RideHailState::Repositioning,
+                    Router::ride_hail(path, v.home.dist_along()),
+                );
+            }
+        }
+        // Can't get home, so just wait at the end of this lane
+        let lane = map.get_l(from.lane());
+        v.leg_length = lane.length() - from.dist_along();
+        (
+            RideHailState::Repositioning,
+            Router::ride_hail(Path::one_step(lane.id, map), lane.length()),
+        )
+    }
+}
+
+// Paths include every step in full, but the vehicle only drives from start_dist to the path's end
+// distance.
+fn leg_length(path: &Path, start_dist: Distance, map: &Map) -> Distance {
+    let last = path
+        .get_steps()
+        .back()
+        .unwrap()
+        .as_traversable()
+        .length(map);
+    (path.total_length() - start_dist - (last - path.end_dist())).max(Distance::ZERO)
+}
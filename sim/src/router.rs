@@ -1,6 +1,7 @@
 use crate::mechanics::Queue;
 use crate::{
-    Event, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID, TripPhaseType, Vehicle,
+    Event, ParkingSimState, ParkingSpot, PersonID, SidewalkPOI, SidewalkSpot, TripID,
+    TripPhaseType, Vehicle,
 };
 use geom::Distance;
 use map_model::{
@@ -10,6 +11,9 @@ use map_model::{
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+// When rerouting around congestion, how much does each car waiting in a lane add to its cost?
+const SECONDS_PER_QUEUED_CAR: usize = 5;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Router {
     // Front is always the current step
@@ -24,6 +28,8 @@ pub enum ActionAtEnd {
     GotoLaneEnd,
     StopBiking(SidewalkSpot),
     BusAtStop,
+    RideHailAtCurb,
+    DeliveryAtCurb,
     GiveUpOnParking,
 }
 
@@ -45,9 +51,22 @@ enum Goal {
     BikeThenStop {
         end_dist: Distance,
     },
+    // Return a shared bike to this dock
+    BikeThenDock {
+        end_dist: Distance,
+        dock: SidewalkSpot,
+    },
     FollowBusRoute {
         end_dist: Distance,
     },
+    // Pick up or drop off a ride-hail passenger, or just stop and wait to be dispatched
+    RideHailStop {
+        end_dist: Distance,
+    },
+    // Double-park at the curb to load or unload
+    DeliveryStop {
+        end_dist: Distance,
+    },
 }
 
 impl Router {
@@ -97,6 +116,17 @@ impl Router {
         }
     }
 
+    pub fn bike_then_dock(path: Path, dock: SidewalkSpot) -> Router {
+        let end_dist = match dock.connection {
+            SidewalkPOI::BikeRack(driving_pos) => driving_pos.dist_along(),
+            _ => unreachable!(),
+        };
+        Router {
+            path,
+            goal: Goal::BikeThenDock { end_dist, dock },
+        }
+    }
+
     pub fn follow_bus_route(path: Path, end_dist: Distance) -> Router {
         Router {
             path,
@@ -104,6 +134,20 @@ impl Router {
         }
     }
 
+    pub fn ride_hail(path: Path, end_dist: Distance) -> Router {
+        Router {
+            path,
+            goal: Goal::RideHailStop { end_dist },
+        }
+    }
+
+    pub fn deliver(path: Path, end_dist: Distance) -> Router {
+        Router {
+            path,
+            goal: Goal::DeliveryStop { end_dist },
+        }
+    }
+
     pub fn head(&self) -> Traversable {
         self.path.current_step().as_traversable()
     }
@@ -135,7 +179,10 @@ impl Router {
                 ..
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
             Goal::BikeThenStop { end_dist } => end_dist,
+            Goal::BikeThenDock { end_dist, .. } => end_dist,
             Goal::FollowBusRoute { end_dist } => end_dist,
+            Goal::RideHailStop { end_dist } => end_dist,
+            Goal::DeliveryStop { end_dist } => end_dist,
         }
     }
 
@@ -314,6 +361,13 @@ impl Router {
                     None
                 }
             }
+            Goal::BikeThenDock { end_dist, ref dock } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::StopBiking(dock.clone()))
+                } else {
+                    None
+                }
+            }
             Goal::FollowBusRoute { end_dist } => {
                 if end_dist == front {
                     Some(ActionAtEnd::BusAtStop)
@@ -321,6 +375,20 @@ impl Router {
                     None
                 }
             }
+            Goal::RideHailStop { end_dist } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::RideHailAtCurb)
+                } else {
+                    None
+                }
+            }
+            Goal::DeliveryStop { end_dist } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::DeliveryAtCurb)
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -401,6 +469,101 @@ impl Router {
         self.path.modify_step(3, PathStep::Turn(turn2), map);
     }
 
+    // Lanes right next to the current one, going the same way, that the path could continue from.
+    // Same lane type, for the same reason as opportunistically_lanechange.
+    pub fn adjacent_lanes(&self, map: &Map) -> Vec<(LaneID, TurnID)> {
+        if self.path.approaching_uber_turn() || self.path.currently_inside_ut().is_some() {
+            return Vec::new();
+        }
+        let (current_lane, current_turn) = match (self.head(), self.maybe_next()) {
+            (Traversable::Lane(l), Some(Traversable::Turn(t))) => (l, t),
+            _ => {
+                return Vec::new();
+            }
+        };
+        let parent = map.get_parent(current_lane);
+        let (fwds, offset) = parent.dir_and_offset(current_lane);
+        let lt = map.get_l(current_lane).lane_type;
+        parent
+            .children(fwds)
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (l, other_lt))| {
+                if (idx + 1 != offset && idx != offset + 1) || *other_lt != lt {
+                    return None;
+                }
+                let turn = TurnID {
+                    parent: current_turn.parent,
+                    src: *l,
+                    dst: current_turn.dst,
+                };
+                if map.maybe_get_t(turn).is_some() {
+                    Some((*l, turn))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn change_current_lane(&mut self, lane: LaneID, turn: TurnID, map: &Map) {
+        self.path.change_current_lane(lane, turn, map);
+    }
+
+    // The car is stuck on the current lane, at the end or queued behind others. Look for a way
+    // from the end of the lane to the same destination, avoiding lanes that're currently backed
+    // up. If a different route is found, returns the previous path.
+    pub fn reroute_around_congestion(
+        &mut self,
+        vehicle: &Vehicle,
+        queues: &BTreeMap<Traversable, Queue>,
+        map: &Map,
+    ) -> Option<Path> {
+        match self.goal {
+            Goal::EndAtBorder { .. } => {}
+            Goal::ParkNearBuilding {
+                started_looking, ..
+            } => {
+                if started_looking {
+                    return None;
+                }
+            }
+            // Buses have to serve their stops, bikes rarely get stuck, and everybody else is
+            // stopping somewhere specific soon.
+            _ => {
+                return None;
+            }
+        }
+        if self.path.approaching_uber_turn() || self.path.currently_inside_ut().is_some() {
+            return None;
+        }
+        let current = match self.head() {
+            Traversable::Lane(l) => l,
+            Traversable::Turn(_) => {
+                return None;
+            }
+        };
+        let old_turn = self.path.next_step();
+        let end = self.path.last_step().as_lane();
+        let req = PathRequest {
+            start: Position::new(current, map.get_l(current).length()),
+            end: Position::new(end, self.path.end_dist()),
+            constraints: vehicle.vehicle_type.to_constraints(),
+        };
+        // Count each car already waiting as a few seconds of delay. The lane the car is stuck on
+        // doesn't matter anymore.
+        let path = map.pathfind_with_penalties(req, |l| {
+            queues
+                .get(&Traversable::Lane(l))
+                .map(|q| SECONDS_PER_QUEUED_CAR * q.cars.len())
+                .unwrap_or(0)
+        })?;
+        if path.get_steps().len() < 2 || path.next_step() == old_turn {
+            return None;
+        }
+        Some(std::mem::replace(&mut self.path, path))
+    }
+
     pub fn replace_path_for_serialization(&mut self, path: Path) -> Path {
         std::mem::replace(&mut self.path, path)
     }
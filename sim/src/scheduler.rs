@@ -24,6 +24,7 @@ pub enum Command {
     Pandemic(pandemic::Cmd),
     FinishRemoteTrip(TripID),
     SeedBus(BusRouteID),
+    RequestRideHail(TripID),
 }
 
 impl Command {
@@ -48,6 +49,7 @@ impl Command {
             Command::Pandemic(ref p) => CommandType::Pandemic(p.clone()),
             Command::FinishRemoteTrip(t) => CommandType::FinishRemoteTrip(*t),
             Command::SeedBus(r) => CommandType::SeedBus(*r),
+            Command::RequestRideHail(t) => CommandType::RideHail(*t),
         }
     }
 }
@@ -65,6 +67,7 @@ pub enum CommandType {
     Pandemic(pandemic::Cmd),
     FinishRemoteTrip(TripID),
     SeedBus(BusRouteID),
+    RideHail(TripID),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -167,6 +170,16 @@ impl Scheduler {
         });
     }
 
+    // Like update, but never delays a command that's already scheduled sooner.
+    pub fn update_if_sooner(&mut self, new_time: Time, cmd: Command) {
+        if let Some((_, existing_time)) = self.queued_commands.get(&cmd.to_type()) {
+            if *existing_time <= new_time {
+                return;
+            }
+        }
+        self.update(new_time, cmd);
+    }
+
     pub fn cancel(&mut self, cmd: Command) {
         // It's fine if a previous command hasn't actually been scheduled.
         self.queued_commands.remove(&cmd.to_type());
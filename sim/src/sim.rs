@@ -1,14 +1,15 @@
 use crate::analytics::Window;
 use crate::{
-    AgentID, AgentType, AlertLocation, Analytics, CarID, Command, CreateCar, DrawCarInput,
-    DrawPedCrowdInput, DrawPedestrianInput, DrivingSimState, Event, GetDrawAgents,
-    IntersectionSimState, OrigPersonID, PandemicModel, ParkedCar, ParkingSimState, ParkingSpot,
-    PedestrianID, Person, PersonID, PersonState, Router, Scheduler, SidewalkPOI, SidewalkSpot,
-    TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, TripResult, TripSpawner,
-    UnzoomedAgent, Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
-    LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
+    AgentID, AgentType, AlertLocation, Analytics, BikeShareDock, CarID, Command, CreateCar, DockID,
+    DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput, DrivingSimState, DwellTime, Event,
+    EventRecorder, GetDrawAgents, IntersectionSimState, OrigPersonID, PandemicModel, ParkedCar,
+    ParkingSimState, ParkingSpot, PedestrianID, Person, PersonID, PersonState, RideHailSimState,
+    Router, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripEndpoint, TripID, TripInfo,
+    TripManager, TripPhaseType, TripResult, TripSpawner, UnzoomedAgent, Vehicle, VehicleSpec,
+    VehicleType, WalkingSimState, BUS_CAPACITY, BUS_LENGTH, DEFAULT_FLEET_SIZE,
+    LIGHT_RAIL_CAPACITY, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
 };
-use abstutil::Timer;
+use abstutil::{Timer, Versioned};
 use derivative::Derivative;
 use geom::{Distance, Duration, PolyLine, Pt2D, Speed, Time};
 use instant::Instant;
@@ -32,6 +33,7 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    ride_hail: RideHailSimState,
     trips: TripManager,
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
@@ -54,10 +56,21 @@ pub struct Sim {
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
     analytics: Analytics,
+    // Only filled out while saving, and only if SimOptions::savestate_analytics is set. Resuming
+    // from a savestate with this then shows the same dashboards as the original run.
+    #[derivative(PartialEq = "ignore")]
+    packed_analytics: Option<Vec<u8>>,
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    savestate_analytics: bool,
 
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
+
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    recorder: Option<EventRecorder>,
 }
 
 #[derive(Clone)]
@@ -70,6 +83,26 @@ pub struct SimOptions {
     pub enable_pandemic_model: Option<XorShiftRng>,
     pub alerts: AlertHandler,
     pub pathfinding_upfront: bool,
+    // If set, every event is written to this file. Use a .jsonl extension for JSON lines, anything
+    // else for a compact binary format. analytics_from_event_log can read either.
+    pub record_events: Option<String>,
+    // Include (compressed) Analytics in savestates. They're much larger than the rest of the sim
+    // state, so this is off by default.
+    pub savestate_analytics: bool,
+    // How many ownerless cars serve ride-hail trips. They start spread out over the map. With 0,
+    // every ride-hail trip is aborted.
+    pub ride_hail_fleet: usize,
+    pub transit_dwell: DwellTime,
+    // If set, drivers stuck at the end of a lane for this long look for another route, taking
+    // current congestion into account. They keep checking this often until they can move.
+    pub reroute_blocked_after: Option<Duration>,
+    // Vehicles accelerate and brake within their limits, instead of instantly moving at full
+    // speed, and followers react to the gap to and speed of their leader. Slower to simulate, but
+    // start-up delays at signals and stop-and-go waves show up.
+    pub car_following: bool,
+    // Opt-in. Vehicles stuck behind somebody slower partway along a lane can pull into a gap in
+    // the lane next to them.
+    pub mid_block_lanechanging: bool,
 }
 
 #[derive(Clone)]
@@ -99,16 +132,53 @@ impl SimOptions {
             enable_pandemic_model: None,
             alerts: AlertHandler::Print,
             pathfinding_upfront: false,
+            record_events: None,
+            savestate_analytics: false,
+            ride_hail_fleet: DEFAULT_FLEET_SIZE,
+            transit_dwell: DwellTime::new(),
+            reroute_blocked_after: None,
+            car_following: false,
+            mid_block_lanechanging: false,
         }
     }
 }
 
+// Savestates embed most of the sim's internal state, so this changes often.
+impl Versioned for Sim {
+    const FORMAT: &'static str = "savestate";
+    // 2: Ride-hailing fleet state
+    // 3: Delivery trucks waiting at a stop
+    // 4: Bike share docks
+    // 5: Transit vehicle capacity
+    // 6: Transit dwell time settings
+    // 7: Transit signal priority
+    // 8: Rerouting around congestion
+    // 9: Actuated traffic signals
+    // 10: Car-following model
+    // 11: Mid-block lane changing
+    // 12: Emissions
+    // 13: Car-following reacts to the leader
+    const VERSION: u32 = 13;
+}
+
 // Setup
 impl Sim {
     pub fn new(map: &Map, opts: SimOptions, timer: &mut Timer) -> Sim {
         let mut scheduler = Scheduler::new();
+        let recorder = opts
+            .record_events
+            .as_ref()
+            .map(|path| EventRecorder::new(path.clone(), map.get_name(), &opts.run_name));
+        let mut trips = TripManager::new(opts.pathfinding_upfront);
+        let ride_hail = RideHailSimState::new(opts.ride_hail_fleet, &mut trips, map);
         Sim {
-            driving: DrivingSimState::new(map, opts.recalc_lanechanging),
+            driving: DrivingSimState::new(
+                map,
+                opts.recalc_lanechanging,
+                opts.reroute_blocked_after,
+                opts.car_following,
+                opts.mid_block_lanechanging,
+            ),
             parking: ParkingSimState::new(map, timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(
@@ -118,8 +188,9 @@ impl Sim {
                 opts.dont_block_the_box,
                 opts.break_turn_conflict_cycles,
             ),
-            transit: TransitSimState::new(),
-            trips: TripManager::new(opts.pathfinding_upfront),
+            transit: TransitSimState::new(opts.transit_dwell),
+            ride_hail,
+            trips,
             pandemic: if let Some(rng) = opts.enable_pandemic_model {
                 Some(PandemicModel::new(rng))
             } else {
@@ -134,8 +205,11 @@ impl Sim {
             run_name: opts.run_name,
             step_count: 0,
             alerts: opts.alerts,
+            recorder,
 
             analytics: Analytics::new(),
+            packed_analytics: None,
+            savestate_analytics: opts.savestate_analytics,
         }
     }
 
@@ -169,6 +243,11 @@ impl Sim {
         self.parking.get_all_parking_spots()
     }
 
+    // (ID, position, bikes, capacity)
+    pub fn get_all_bike_share_docks(&self) -> Vec<(DockID, Position, usize, usize)> {
+        self.trips.get_all_bike_share_docks()
+    }
+
     // Also returns the start distance of the building. TODO Do that in the Path properly.
     pub fn walking_path_to_nearest_parking_spot(
         &self,
@@ -181,6 +260,9 @@ impl Sim {
             vehicle_type: VehicleType::Car,
             length: MIN_CAR_LENGTH,
             max_speed: None,
+            capacity: None,
+            max_accel: VehicleType::Car.default_max_accel(),
+            max_decel: VehicleType::Car.default_max_decel(),
         };
         let driving_lane = map.find_driving_lane_near_building(b);
 
@@ -228,21 +310,40 @@ impl Sim {
         self.parking.add_parked_car(ParkedCar { vehicle, spot });
     }
 
-    pub(crate) fn seed_bus_route(&mut self, route: &BusRoute, map: &Map, timer: &mut Timer) {
+    // None if there's nowhere to bike from the dock
+    pub(crate) fn add_bike_share_dock(
+        &mut self,
+        dock: &BikeShareDock,
+        map: &Map,
+    ) -> Option<DockID> {
+        self.trips.add_bike_share_dock(dock, map)
+    }
+
+    pub(crate) fn seed_bus_route(&mut self, route: &BusRoute) {
+        // Departures before the scenario starts are skipped
+        if let Some(t) = route.next_departure(self.time, true) {
+            self.scheduler.push(*t, Command::SeedBus(route.id));
+        }
+    }
+
+    fn spawn_bus(&mut self, route: &BusRoute, map: &Map, timer: &mut Timer) {
         // Spawn one bus for the first leg.
         let (req, path) = self.transit.create_empty_route(route, map);
 
         // For now, no desire for randomness. Caller can pass in list of specs if that ever
         // changes.
-        let (vehicle_type, length) = match route.route_type {
-            PathConstraints::Bus => (VehicleType::Bus, BUS_LENGTH),
-            PathConstraints::Train => (VehicleType::Train, LIGHT_RAIL_LENGTH),
+        let (vehicle_type, length, capacity) = match route.route_type {
+            PathConstraints::Bus => (VehicleType::Bus, BUS_LENGTH, BUS_CAPACITY),
+            PathConstraints::Train => (VehicleType::Train, LIGHT_RAIL_LENGTH, LIGHT_RAIL_CAPACITY),
             _ => unreachable!(),
         };
         let vehicle = VehicleSpec {
             vehicle_type,
             length,
             max_speed: None,
+            capacity: Some(capacity),
+            max_accel: vehicle_type.default_max_accel(),
+            max_decel: vehicle_type.default_max_decel(),
         }
         .make(CarID(self.trips.new_car_id(), vehicle_type), None);
 
@@ -268,10 +369,6 @@ impl Sim {
                 true,
             ),
         );
-
-        // TODO Change the rate of spawning based on a schedule from GTFS or player's choice
-        self.scheduler
-            .push(self.time + Duration::hours(1), Command::SeedBus(route.id));
     }
 
     pub fn set_name(&mut self, name: String) {
@@ -423,10 +520,10 @@ impl Sim {
                             trip,
                             person,
                             Some(create_car.req.clone()),
-                            if create_car.vehicle.id.1 == VehicleType::Car {
-                                TripPhaseType::Driving
-                            } else {
+                            if create_car.vehicle.id.1 == VehicleType::Bike {
                                 TripPhaseType::Biking
+                            } else {
+                                TripPhaseType::Driving
                             },
                         ));
                     }
@@ -441,10 +538,14 @@ impl Sim {
                         self.parking.remove_parked_car(parked_car);
                     }
                     if let Some(route) = create_car.maybe_route {
-                        self.transit.bus_created(create_car.vehicle.id, route);
+                        self.transit.bus_created(
+                            create_car.vehicle.id,
+                            route,
+                            create_car.vehicle.capacity,
+                        );
                     }
-                    self.analytics
-                        .record_demand(create_car.router.get_path(), map);
+                    // Through an event, so the demand is in event logs too
+                    events.push(Event::PathAmended(create_car.router.get_path().clone()));
                 } else if retry_if_no_room {
                     // TODO Record this in the trip log
                     self.scheduler.push(
@@ -479,7 +580,7 @@ impl Sim {
                     Some(create_ped.req.clone()),
                     TripPhaseType::Walking,
                 ));
-                self.analytics.record_demand(&create_ped.path, map);
+                events.push(Event::PathAmended(create_ped.path.clone()));
 
                 // Maybe there's actually no work to do!
                 match (&create_ped.start.connection, &create_ped.goal.connection) {
@@ -518,6 +619,7 @@ impl Sim {
                     &mut self.scheduler,
                     &mut self.transit,
                     &mut self.walking,
+                    &mut self.ride_hail,
                 );
             }
             Command::UpdateLaggyHead(car) => {
@@ -542,8 +644,13 @@ impl Sim {
                 );
             }
             Command::UpdateIntersection(i) => {
-                self.intersections
-                    .update_intersection(self.time, i, map, &mut self.scheduler);
+                self.intersections.update_intersection(
+                    self.time,
+                    i,
+                    map,
+                    &mut self.scheduler,
+                    &self.driving,
+                );
             }
             Command::Callback(frequency) => {
                 self.scheduler
@@ -568,7 +675,37 @@ impl Sim {
                 );
             }
             Command::SeedBus(r) => {
-                self.seed_bus_route(map.get_br(r), map, &mut Timer::throwaway());
+                let route = map.get_br(r);
+                self.spawn_bus(route, map, &mut Timer::throwaway());
+                // Only one SeedBus per route can be queued, so schedule the next departure now, even
+                // if it's tomorrow
+                if let Some(t) = route.next_departure(self.time, false) {
+                    self.scheduler.push(*t, Command::SeedBus(r));
+                }
+            }
+            Command::RequestRideHail(trip) => {
+                let info = self.trips.trip_info(trip);
+                let person = self.trips.trip_to_person(trip);
+                let result = match (info.start, info.end) {
+                    (TripEndpoint::Bldg(b1), TripEndpoint::Bldg(b2)) => self
+                        .ride_hail
+                        .request_ride(self.time, trip, person, b1, b2, map, &mut self.scheduler),
+                    _ => Err(format!("{} doesn't go between buildings", trip)),
+                };
+                if let Err(err) = result {
+                    events.push(Event::Alert(
+                        AlertLocation::Person(person),
+                        format!("Aborting ride-hail trip: {}", err),
+                    ));
+                    self.trips.abort_trip(
+                        self.time,
+                        trip,
+                        None,
+                        &mut self.parking,
+                        &mut self.scheduler,
+                        map,
+                    );
+                }
             }
         }
 
@@ -581,14 +718,21 @@ impl Sim {
     fn dispatch_events(&mut self, mut events: Vec<Event>, map: &Map) {
         events.extend(self.trips.collect_events());
         events.extend(self.transit.collect_events());
+        events.extend(self.ride_hail.collect_events());
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
         events.extend(self.parking.collect_events());
         for ev in events {
+            if let Event::TripAborted(trip) = ev {
+                self.ride_hail.cancel_ride(trip);
+            }
             if let Some(ref mut m) = self.pandemic {
                 m.handle_event(self.time, &ev, &mut self.scheduler);
             }
+            if let Some(ref mut r) = self.recorder {
+                r.record(self.time, &ev);
+            }
 
             self.analytics.event(ev, self.time, map);
         }
@@ -638,6 +782,9 @@ impl Sim {
                 last_update = Instant::now();
             }
         }
+        if let Some(ref mut r) = self.recorder {
+            r.flush();
+        }
         timer.stop(format!("Advance sim to {}", end_time));
     }
     pub fn tiny_step(&mut self, map: &Map, maybe_cb: &mut Option<Box<dyn SimCallback>>) {
@@ -808,10 +955,19 @@ impl Sim {
             );
         }
 
+        if self.savestate_analytics {
+            self.packed_analytics = Some(self.analytics.pack());
+            println!(
+                "- analytics: {} bytes compressed",
+                abstutil::prettyprint_usize(self.packed_analytics.as_ref().unwrap().len())
+            );
+        }
+
         let path = self.save_path(self.time);
-        abstutil::write_binary(path.clone(), self);
+        abstutil::write_versioned_binary(path.clone(), self);
 
         self.scheduler.after_savestate(restore);
+        self.packed_analytics = None;
 
         path
     }
@@ -829,18 +985,28 @@ impl Sim {
         map: &Map,
         timer: &mut Timer,
     ) -> Result<Sim, std::io::Error> {
-        let mut sim: Sim = abstutil::maybe_read_binary(path, timer)?;
-        sim.restore_paths(map, timer);
+        let mut sim: Sim = abstutil::maybe_read_versioned_binary(path, timer)?;
+        sim.restore_paths(map, timer)?;
         Ok(sim)
     }
 
-    pub fn restore_paths(&mut self, map: &Map, timer: &mut Timer) {
+    // Also restores Analytics, if the savestate has them.
+    pub fn restore_paths(&mut self, map: &Map, timer: &mut Timer) -> Result<(), std::io::Error> {
+        if let Some(bytes) = self.packed_analytics.take() {
+            timer.start("unpack analytics");
+            let result = Analytics::unpack(&bytes);
+            timer.stop("unpack analytics");
+            self.analytics = result?;
+            self.savestate_analytics = true;
+        }
+
         let paths = timer.parallelize(
             "calculate paths",
             self.scheduler.get_requests_for_savestate(),
             |req| map.pathfind(req).unwrap(),
         );
         self.scheduler.after_savestate(paths);
+        Ok(())
     }
 
     pub fn handle_live_edited_traffic_signals(&mut self, map: &Map) {
@@ -873,6 +1039,10 @@ impl Sim {
     pub fn num_ppl(&self) -> (usize, usize, usize) {
         self.trips.num_ppl()
     }
+    // (idle, driving empty, carrying a passenger, requests waiting for a vehicle)
+    pub fn ride_hail_fleet_status(&self) -> (usize, usize, usize, usize) {
+        self.ride_hail.fleet_status()
+    }
 
     pub fn debug_ped(&self, id: PedestrianID) {
         self.walking.debug_ped(id);
@@ -918,7 +1088,14 @@ impl Sim {
                 "Route".to_string(),
                 map.get_br(self.transit.bus_route(car)).full_name.clone(),
             ),
-            ("Passengers".to_string(), passengers.len().to_string()),
+            (
+                "Passengers".to_string(),
+                if let Some(cap) = self.transit.get_capacity(car) {
+                    format!("{} / {}", passengers.len(), cap)
+                } else {
+                    passengers.len().to_string()
+                },
+            ),
         ]
     }
 
@@ -994,6 +1171,7 @@ impl Sim {
             VehicleType::Bike,
             VehicleType::Bus,
             VehicleType::Train,
+            VehicleType::Truck,
         ] {
             let id = CarID(idx, *vt);
             if self.driving.does_car_exist(id) {
@@ -1,9 +1,9 @@
 use crate::{
-    CarID, Event, PedestrianID, PersonID, Router, Scheduler, TripID, TripManager, TripPhaseType,
-    VehicleType, WalkingSimState,
+    CarID, Event, ParkingSimState, PedestrianID, PersonID, Router, Scheduler, TripID, TripManager,
+    TripPhaseType, VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
+use geom::{Duration, Time};
 use map_model::{BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
@@ -32,9 +32,18 @@ struct Bus {
     route: BusRouteID,
     // Where does each passenger want to deboard?
     passengers: Vec<(PersonID, BusStopID)>,
+    capacity: Option<usize>,
     state: BusState,
 }
 
+impl Bus {
+    fn is_full(&self) -> bool {
+        self.capacity
+            .map(|cap| self.passengers.len() >= cap)
+            .unwrap_or(false)
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 enum BusState {
     DrivingToStop(StopIdx),
@@ -43,6 +52,53 @@ enum BusState {
     Done,
 }
 
+// How long buses and trains idle at a stop. Passengers board and alight through every door at
+// once.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DwellTime {
+    // Pulling in and out, opening and closing the doors
+    pub fixed: Duration,
+    pub per_boarding: Duration,
+    pub per_alighting: Duration,
+    pub bus_doors: usize,
+    pub train_doors: usize,
+}
+
+impl DwellTime {
+    // Always wait the same amount of time, no matter how many passengers there are
+    pub fn new() -> DwellTime {
+        DwellTime {
+            fixed: Duration::seconds(10.0),
+            per_boarding: Duration::ZERO,
+            per_alighting: Duration::ZERO,
+            bus_doors: 2,
+            train_doors: 6,
+        }
+    }
+
+    // Longer stops when more passengers board and alight
+    pub fn per_passenger() -> DwellTime {
+        DwellTime {
+            fixed: Duration::seconds(5.0),
+            per_boarding: Duration::seconds(3.0),
+            per_alighting: Duration::seconds(2.0),
+            bus_doors: 2,
+            train_doors: 6,
+        }
+    }
+
+    fn calculate(&self, vehicle: VehicleType, boarding: usize, alighting: usize) -> Duration {
+        let doors = if vehicle == VehicleType::Train {
+            self.train_doors
+        } else {
+            self.bus_doors
+        };
+        self.fixed
+            + (self.per_boarding * (boarding as f64) + self.per_alighting * (alighting as f64))
+                / (doors.max(1) as f64)
+    }
+}
+
 // This kind of acts like TripManager, managing transitions... but a bit more statefully.
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct TransitSimState {
@@ -62,16 +118,18 @@ pub struct TransitSimState {
         deserialize_with = "deserialize_btreemap"
     )]
     peds_waiting: BTreeMap<BusStopID, Vec<(PedestrianID, BusRouteID, BusStopID, Time)>>,
+    dwell: DwellTime,
 
     events: Vec<Event>,
 }
 
 impl TransitSimState {
-    pub fn new() -> TransitSimState {
+    pub fn new(dwell: DwellTime) -> TransitSimState {
         TransitSimState {
             buses: BTreeMap::new(),
             routes: BTreeMap::new(),
             peds_waiting: BTreeMap::new(),
+            dwell,
             events: Vec::new(),
         }
     }
@@ -152,7 +210,7 @@ impl TransitSimState {
         first_step
     }
 
-    pub fn bus_created(&mut self, bus: CarID, r: BusRouteID) {
+    pub fn bus_created(&mut self, bus: CarID, r: BusRouteID, capacity: Option<usize>) {
         let route = self.routes.get_mut(&r).unwrap();
         route.active_vehicles.insert(bus);
         self.buses.insert(
@@ -161,6 +219,7 @@ impl TransitSimState {
                 car: bus,
                 route: r,
                 passengers: Vec::new(),
+                capacity,
                 state: if route.start_from_border.is_some() {
                     BusState::DrivingToStop(0)
                 } else {
@@ -170,17 +229,18 @@ impl TransitSimState {
         );
     }
 
-    // If true, the bus is idling. If false, the bus actually arrived at a border and should now
-    // vanish.
+    // Returns how long the bus idles at the stop. None means the bus actually arrived at a border
+    // and should now vanish.
     pub fn bus_arrived_at_stop(
         &mut self,
         now: Time,
         id: CarID,
         trips: &mut TripManager,
         walking: &mut WalkingSimState,
+        parking: &mut ParkingSimState,
         scheduler: &mut Scheduler,
         map: &Map,
-    ) -> bool {
+    ) -> Option<Duration> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
@@ -191,21 +251,28 @@ impl TransitSimState {
 
                 // Deboard existing passengers.
                 let mut still_riding = Vec::new();
+                let mut alighting = 0;
                 for (person, stop2) in bus.passengers.drain(..) {
                     if stop1 == stop2 {
-                        trips.person_left_bus(now, person, bus.car, map, scheduler);
+                        alighting += 1;
+                        trips.person_left_bus(now, person, bus.car, map, parking, scheduler);
                     } else {
                         still_riding.push((person, stop2));
                     }
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                // Board new passengers, until the bus fills up.
                 let mut still_waiting = Vec::new();
+                let mut boarding = 0;
+                let mut passed_up = 0;
                 for (ped, route, stop2, started_waiting) in
                     self.peds_waiting.remove(&stop1).unwrap_or_else(Vec::new)
                 {
-                    if bus.route == route {
+                    if bus.route == route && bus.is_full() {
+                        passed_up += 1;
+                        still_waiting.push((ped, route, stop2, started_waiting));
+                    } else if bus.route == route {
                         let (trip, person) = trips.ped_boarded_bus(
                             now,
                             ped,
@@ -224,12 +291,19 @@ impl TransitSimState {
                             TripPhaseType::RidingBus(route, stop1, bus.car),
                         ));
                         bus.passengers.push((person, stop2));
+                        boarding += 1;
                     } else {
                         still_waiting.push((ped, route, stop2, started_waiting));
                     }
                 }
                 self.peds_waiting.insert(stop1, still_waiting);
-                true
+                if passed_up > 0 {
+                    self.events
+                        .push(Event::BusPassedUpRiders(id, bus.route, stop1, passed_up));
+                }
+                // TODO People who show up while the bus is already idling here don't extend the
+                // dwell time.
+                Some(self.dwell.calculate(id.1, boarding, alighting))
             }
             BusState::DrivingOffMap => {
                 self.routes
@@ -238,7 +312,7 @@ impl TransitSimState {
                     .active_vehicles
                     .remove(&id);
                 bus.state = BusState::Done;
-                false
+                None
             }
             BusState::AtStop(_) | BusState::Done => unreachable!(),
         }
@@ -251,8 +325,12 @@ impl TransitSimState {
             BusState::DrivingToStop(_) | BusState::DrivingOffMap | BusState::Done => unreachable!(),
             BusState::AtStop(stop_idx) => {
                 let stop = &route.stops[stop_idx];
-                self.events
-                    .push(Event::BusDepartedFromStop(id, bus.route, stop.id));
+                self.events.push(Event::BusDepartedFromStop(
+                    id,
+                    bus.route,
+                    stop.id,
+                    bus.passengers.len(),
+                ));
                 if let Some((req, path)) = stop.next_stop.clone() {
                     bus.state = BusState::DrivingToStop(stop_idx + 1);
                     Router::follow_bus_route(path, req.end.dist_along())
@@ -287,7 +365,10 @@ impl TransitSimState {
         if let Some(route) = self.routes.get(&route_id) {
             for bus in &route.active_vehicles {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
-                    if route.stops[idx].id == stop1 {
+                    if route.stops[idx].id == stop1 && self.buses[bus].is_full() {
+                        self.events
+                            .push(Event::BusPassedUpRiders(*bus, route_id, stop1, 1));
+                    } else if route.stops[idx].id == stop1 {
                         self.buses
                             .get_mut(bus)
                             .unwrap()
@@ -329,6 +410,10 @@ impl TransitSimState {
         &self.buses[&bus].passengers
     }
 
+    pub fn get_capacity(&self, bus: CarID) -> Option<usize> {
+        self.buses[&bus].capacity
+    }
+
     pub fn bus_route(&self, bus: CarID) -> BusRouteID {
         self.buses[&bus].route
     }
@@ -1,8 +1,9 @@
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, OffMapLocation, OrigPersonID, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID,
-    PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID, TripPhaseType,
-    TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
+    AgentID, AgentType, AlertLocation, BikeShareDock, BikeShareSimState, CarID, Command, CreateCar,
+    CreatePedestrian, DockID, DrivingGoal, Event, OffMapLocation, OrigPersonID, ParkedCar,
+    ParkingSimState, ParkingSpot, PedestrianID, PersonID, Router, Scheduler, SidewalkPOI,
+    SidewalkSpot, TransitSimState, TripID, TripPhaseType, TripSpec, Vehicle, VehicleSpec,
+    VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Duration, Speed, Time};
@@ -23,6 +24,13 @@ pub struct TripManager {
         deserialize_with = "deserialize_btreemap"
     )]
     active_trip_mode: BTreeMap<AgentID, TripID>,
+    // Trucks double-parked at a delivery stop already know where to go next
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    delivery_routers: BTreeMap<CarID, (PathRequest, Router)>,
+    bike_share: BikeShareSimState,
     unfinished_trips: usize,
     pub pathfinding_upfront: bool,
 
@@ -37,6 +45,8 @@ impl TripManager {
             trips: Vec::new(),
             people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
+            delivery_routers: BTreeMap::new(),
+            bike_share: BikeShareSimState::new(),
             unfinished_trips: 0,
             car_id_counter: 0,
             events: Vec::new(),
@@ -71,6 +81,7 @@ impl TripManager {
             vehicles,
             delayed_trips: Vec::new(),
             on_bus: None,
+            in_ride_hail: None,
         });
     }
     pub fn random_person(&mut self, ped_speed: Speed, vehicle_specs: Vec<VehicleSpec>) -> &Person {
@@ -109,6 +120,7 @@ impl TripManager {
                 DrivingGoal::ParkNear(b) => TripEndpoint::Bldg(*b),
                 DrivingGoal::Border(i, _, loc) => TripEndpoint::Border(*i, loc.clone()),
             },
+            Some(TripLeg::RideHail(b)) => TripEndpoint::Bldg(*b),
             Some(TripLeg::Remote(ref to)) => {
                 TripEndpoint::Border(map.all_incoming_borders()[0].id, Some(to.clone()))
             }
@@ -218,6 +230,7 @@ impl TripManager {
         if !trip.spawn_ped(
             now,
             SidewalkSpot::parking_spot(spot, map, parking),
+            None,
             &self.people[trip.person.0],
             map,
             scheduler,
@@ -274,7 +287,7 @@ impl TripManager {
             end,
             constraints: PathConstraints::Car,
         };
-        let path = if let Some(p) = map.pathfind(req.clone()) {
+        let path = if let Some(p) = map.pathfind_at(req.clone(), now) {
             p
         } else {
             self.events.push(Event::Alert(
@@ -328,25 +341,81 @@ impl TripManager {
         trip.total_blocked_time += blocked_time;
 
         trip.assert_walking_leg(spot.clone());
-        let (bike, drive_to) = match trip.legs[0] {
-            TripLeg::Drive(bike, ref to) => (bike, to.clone()),
-            _ => unreachable!(),
-        };
         let driving_pos = match spot.connection {
             SidewalkPOI::BikeRack(p) => p,
             _ => unreachable!(),
         };
+        let (bike, end) = match trip.legs[0] {
+            TripLeg::Drive(bike, ref to) => (bike, to.goal_pos(PathConstraints::Bike, map)),
+            TripLeg::BikeShare(bike, dropoff) => {
+                let pickup = self.bike_share.dock_at(&spot).unwrap();
+                if !self.bike_share.take_bike(pickup) {
+                    // Somebody else got the last bike. Walk to the next closest dock.
+                    if let Some(next) =
+                        self.bike_share
+                            .nearest_with_bikes(spot.sidewalk_pos, Some(pickup), map)
+                    {
+                        trip.legs
+                            .push_front(TripLeg::Walk(self.bike_share.get_spot(next).clone()));
+                        if !trip.spawn_ped(
+                            now,
+                            SidewalkSpot::suddenly_appear(
+                                spot.sidewalk_pos.lane(),
+                                spot.sidewalk_pos.dist_along(),
+                                map,
+                            ),
+                            None,
+                            &self.people[trip.person.0],
+                            map,
+                            scheduler,
+                            &mut self.events,
+                        ) {
+                            self.unfinished_trips -= 1;
+                        }
+                    } else {
+                        self.events.push(Event::Alert(
+                            AlertLocation::Person(trip.person),
+                            format!(
+                                "Aborting {} because there are no shared bikes left anywhere",
+                                trip.id
+                            ),
+                        ));
+                        let trip = trip.id;
+                        self.abort_trip(now, trip, None, parking, scheduler, map);
+                    }
+                    return;
+                }
+                self.events.push(Event::BikeShareDockUpdated(
+                    pickup,
+                    self.bike_share.bikes_at(pickup),
+                ));
+                let end = match self.bike_share.get_spot(dropoff).connection {
+                    SidewalkPOI::BikeRack(p) => p,
+                    _ => unreachable!(),
+                };
+                (bike, end)
+            }
+            _ => unreachable!(),
+        };
 
-        let end = drive_to.goal_pos(PathConstraints::Bike, map);
         let req = PathRequest {
             start: driving_pos,
             end,
             constraints: PathConstraints::Bike,
         };
-        if let Some(router) = map
-            .pathfind(req.clone())
-            .and_then(|path| drive_to.make_router(path, map, VehicleType::Bike))
-        {
+        let maybe_router = if let Some(path) = map.pathfind(req.clone()) {
+            match trip.legs[0] {
+                TripLeg::Drive(_, ref to) => to.make_router(path, map, VehicleType::Bike),
+                TripLeg::BikeShare(_, dropoff) => Some(Router::bike_then_dock(
+                    path,
+                    self.bike_share.get_spot(dropoff).clone(),
+                )),
+                _ => unreachable!(),
+            }
+        } else {
+            None
+        };
+        if let Some(router) = maybe_router {
             scheduler.push(
                 now,
                 Command::SpawnCar(
@@ -395,12 +464,69 @@ impl TripManager {
             Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
                 assert_eq!(c, bike);
             }
+            Some(TripLeg::BikeShare(c, dock)) => {
+                assert_eq!(c, bike);
+                if !self.bike_share.return_bike(dock, false) {
+                    // The dock is full. Ride to the next closest one with room and walk further
+                    // from there.
+                    if let Some(next) =
+                        self.bike_share
+                            .nearest_with_room(bike_rack.sidewalk_pos, Some(dock), map)
+                    {
+                        let driving_pos = match bike_rack.connection {
+                            SidewalkPOI::BikeRack(p) => p,
+                            _ => unreachable!(),
+                        };
+                        let next_spot = self.bike_share.get_spot(next).clone();
+                        let req = PathRequest {
+                            start: driving_pos,
+                            end: match next_spot.connection {
+                                SidewalkPOI::BikeRack(p) => p,
+                                _ => unreachable!(),
+                            },
+                            constraints: PathConstraints::Bike,
+                        };
+                        if let Some(path) = map.pathfind(req.clone()) {
+                            trip.legs.push_front(TripLeg::BikeShare(bike, next));
+                            scheduler.push(
+                                now,
+                                Command::SpawnCar(
+                                    CreateCar::for_appearing(
+                                        self.people[trip.person.0].get_vehicle(bike),
+                                        driving_pos,
+                                        Router::bike_then_dock(path, next_spot),
+                                        req,
+                                        trip.id,
+                                        trip.person,
+                                    ),
+                                    true,
+                                ),
+                            );
+                            return;
+                        }
+                    }
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(trip.person),
+                        format!(
+                            "{} is full, but there's nowhere else to return the bike, so leaving \
+                             it there anyway",
+                            dock
+                        ),
+                    ));
+                    self.bike_share.return_bike(dock, true);
+                }
+                self.events.push(Event::BikeShareDockUpdated(
+                    dock,
+                    self.bike_share.bikes_at(dock),
+                ));
+            }
             _ => unreachable!(),
         };
 
         if !trip.spawn_ped(
             now,
             bike_rack,
+            None,
             &self.people[trip.person.0],
             map,
             scheduler,
@@ -527,6 +653,7 @@ impl TripManager {
         person: PersonID,
         bus: CarID,
         map: &Map,
+        parking: &mut ParkingSimState,
         scheduler: &mut Scheduler,
     ) {
         let trip = &mut self.trips[self
@@ -540,9 +667,34 @@ impl TripManager {
         };
         self.people[person.0].on_bus.take().unwrap();
 
+        // Walking back to a car parked earlier, like the return half of a park-and-ride
+        let mut walk_to = None;
+        if trip.legs[0] == TripLeg::Walk(SidewalkSpot::deferred_parking_spot()) {
+            let car = match trip.legs[1] {
+                TripLeg::Drive(c, _) => c,
+                _ => unreachable!(),
+            };
+            if let Some(parked_car) = parking.lookup_parked_car(car) {
+                walk_to = Some(SidewalkSpot::parking_spot(parked_car.spot, map, parking));
+            } else {
+                self.events.push(Event::Alert(
+                    AlertLocation::Person(person),
+                    format!(
+                        "{} got off the bus to go get {}, but it's not parked anywhere, so \
+                         aborting {}",
+                        person, car, trip.id
+                    ),
+                ));
+                let id = trip.id;
+                self.abort_trip(now, id, None, parking, scheduler, map);
+                return;
+            }
+        }
+
         if !trip.spawn_ped(
             now,
             start,
+            walk_to,
             &self.people[trip.person.0],
             map,
             scheduler,
@@ -552,6 +704,130 @@ impl TripManager {
         }
     }
 
+    pub fn ride_hail_picked_up(&mut self, id: TripID, car: CarID, req: PathRequest) {
+        let trip = &self.trips[id.0];
+        let person = trip.person;
+        match trip.info.start {
+            TripEndpoint::Bldg(b) => {
+                self.events.push(Event::PersonLeavesBuilding(person, b));
+            }
+            TripEndpoint::Border(_, _) => unreachable!(),
+        }
+        self.events.push(Event::TripPhaseStarting(
+            id,
+            person,
+            Some(req),
+            TripPhaseType::RidingRideHail(car),
+        ));
+        self.people[person.0].in_ride_hail = Some(car);
+        self.agent_starting_trip_leg(AgentID::Car(car), id);
+    }
+
+    // TODO Need to characterize delay the ride-hail vehicle experienced
+    pub fn ride_hail_dropped_off(
+        &mut self,
+        now: Time,
+        car: CarID,
+        map: &Map,
+        parking: &mut ParkingSimState,
+        scheduler: &mut Scheduler,
+    ) {
+        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
+
+        let bldg = match trip.legs.pop_front() {
+            Some(TripLeg::RideHail(b)) => b,
+            _ => unreachable!(),
+        };
+        assert!(trip.legs.is_empty());
+        assert!(!trip.finished_at.is_some());
+        trip.finished_at = Some(now);
+        self.unfinished_trips -= 1;
+        self.events.push(Event::TripFinished {
+            trip: trip.id,
+            mode: trip.info.mode,
+            total_time: now - trip.info.departure,
+            blocked_time: trip.total_blocked_time,
+        });
+        let person = trip.person;
+        self.people[person.0].in_ride_hail.take().unwrap();
+        self.people[person.0].state = PersonState::Inside(bldg);
+        self.events.push(Event::PersonEntersBuilding(person, bldg));
+        self.person_finished_trip(now, person, parking, scheduler, map);
+    }
+
+    // Returns how long to double-park at the curb, or None if the tour was aborted and the truck
+    // should vanish.
+    pub fn truck_reached_delivery_stop(
+        &mut self,
+        now: Time,
+        car: CarID,
+        pos: Position,
+        map: &Map,
+        parking: &mut ParkingSimState,
+        scheduler: &mut Scheduler,
+    ) -> Option<Duration> {
+        let id = self.active_trip_mode[&AgentID::Car(car)];
+        let trip = &mut self.trips[id.0];
+        let (bldg, unload_time) = match trip.legs.pop_front() {
+            Some(TripLeg::Deliver(c, b, dt)) => {
+                assert_eq!(car, c);
+                (b, dt)
+            }
+            _ => unreachable!(),
+        };
+        let person = trip.person;
+        self.events
+            .push(Event::TruckDoubleParked(car, bldg, unload_time));
+        self.events.push(Event::TripPhaseStarting(
+            id,
+            person,
+            None,
+            TripPhaseType::Delivering(bldg),
+        ));
+
+        // Figure out the next leg now, while it's still easy to make the truck vanish.
+        let req = PathRequest {
+            start: pos,
+            end: match trip.legs[0] {
+                TripLeg::Deliver(_, b, _) => TripSpec::curb_pos(b, VehicleType::Truck, map),
+                TripLeg::Drive(_, ref goal) => goal.goal_pos(PathConstraints::Car, map),
+                _ => unreachable!(),
+            },
+            constraints: PathConstraints::Car,
+        };
+        let maybe_router = map
+            .pathfind_at(req.clone(), now)
+            .and_then(|path| match trip.legs[0] {
+                TripLeg::Deliver(_, _, _) => Some(Router::deliver(path, req.end.dist_along())),
+                TripLeg::Drive(_, ref goal) => goal.make_router(path, map, VehicleType::Truck),
+                _ => unreachable!(),
+            });
+        if let Some(router) = maybe_router {
+            self.delivery_routers.insert(car, (req, router));
+            Some(unload_time)
+        } else {
+            self.events.push(Event::Alert(
+                AlertLocation::Person(person),
+                format!("{} can't continue their delivery tour: {}", car, req),
+            ));
+            self.active_trip_mode.remove(&AgentID::Car(car));
+            self.abort_trip(now, id, None, parking, scheduler, map);
+            None
+        }
+    }
+
+    pub fn truck_departed_delivery_stop(&mut self, car: CarID) -> Router {
+        let (req, router) = self.delivery_routers.remove(&car).unwrap();
+        let id = self.active_trip_mode[&AgentID::Car(car)];
+        self.events.push(Event::TripPhaseStarting(
+            id,
+            self.trips[id.0].person,
+            Some(req),
+            TripPhaseType::Driving,
+        ));
+        router
+    }
+
     pub fn ped_reached_border(
         &mut self,
         now: Time,
@@ -755,7 +1031,7 @@ impl TripManager {
         } else {
             // If the trip was aborted because we'e totally out of parking, don't forget to clean
             // this up.
-            if let TripLeg::Drive(c, _) = &trip.legs[0] {
+            if let TripLeg::Drive(c, _) | TripLeg::BikeShare(c, _) = &trip.legs[0] {
                 if let Some(t) = self.active_trip_mode.remove(&AgentID::Car(*c)) {
                     assert_eq!(t, trip.id);
                 }
@@ -765,6 +1041,20 @@ impl TripManager {
         self.person_finished_trip(now, person, parking, scheduler, map);
     }
 
+    pub fn add_bike_share_dock(&mut self, dock: &BikeShareDock, map: &Map) -> Option<DockID> {
+        let id = self.bike_share.add_dock(dock, map)?;
+        self.events.push(Event::BikeShareDockUpdated(
+            id,
+            self.bike_share.bikes_at(id),
+        ));
+        Some(id)
+    }
+
+    // (ID, position, bikes, capacity)
+    pub fn get_all_bike_share_docks(&self) -> Vec<(DockID, Position, usize, usize)> {
+        self.bike_share.get_all_docks()
+    }
+
     pub fn active_agents(&self) -> Vec<AgentID> {
         self.active_trip_mode.keys().cloned().collect()
     }
@@ -795,8 +1085,18 @@ impl TripManager {
         let person = &self.people[trip.person.0];
         let a = match &trip.legs[0] {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
-            TripLeg::Drive(c, _) => AgentID::Car(*c),
+            TripLeg::Drive(c, _) | TripLeg::Deliver(c, _, _) | TripLeg::BikeShare(c, _) => {
+                AgentID::Car(*c)
+            }
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
+            TripLeg::RideHail(_) => {
+                if let Some(car) = person.in_ride_hail {
+                    AgentID::Car(car)
+                } else {
+                    // Still waiting to be picked up
+                    return TripResult::ModeChange;
+                }
+            }
             TripLeg::Remote(_) => {
                 return TripResult::RemoteTrip;
             }
@@ -938,7 +1238,7 @@ impl TripManager {
         assert!(!self.trips[trip.0].cancelled);
         assert!(!self.trips[trip.0].aborted);
         if !self.pathfinding_upfront && maybe_path.is_none() && maybe_req.is_some() {
-            maybe_path = map.pathfind(maybe_req.clone().unwrap());
+            maybe_path = map.pathfind_at(maybe_req.clone().unwrap(), now);
         }
 
         let person = &mut self.people[self.trips[trip.0].person.0];
@@ -1008,6 +1308,42 @@ impl TripManager {
                     self.abort_trip(now, trip, Some(vehicle), parking, scheduler, map);
                 }
             }
+            TripSpec::DeliveryTour {
+                start_pos,
+                use_vehicle,
+                origin,
+                ..
+            } => {
+                assert_eq!(person.state, PersonState::OffMap);
+                self.events.push(Event::PersonEntersMap(
+                    person.id,
+                    AgentID::Car(use_vehicle),
+                    map.get_l(start_pos.lane()).src_i,
+                    origin,
+                ));
+                person.state = PersonState::Trip(trip);
+
+                let vehicle = person.get_vehicle(use_vehicle);
+                let req = maybe_req.unwrap();
+                if let Some(path) = maybe_path {
+                    let router = Router::deliver(path, req.end.dist_along());
+                    scheduler.push(
+                        now,
+                        Command::SpawnCar(
+                            CreateCar::for_appearing(
+                                vehicle, start_pos, router, req, trip, person.id,
+                            ),
+                            true,
+                        ),
+                    );
+                } else {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(person.id),
+                        format!("DeliveryTour trip couldn't find the first path: {}", req),
+                    ));
+                    self.abort_trip(now, trip, Some(vehicle), parking, scheduler, map);
+                }
+            }
             TripSpec::NoRoomToSpawn { i, use_vehicle, .. } => {
                 self.events.push(Event::Alert(
                     AlertLocation::Intersection(i),
@@ -1021,6 +1357,9 @@ impl TripManager {
             }
             TripSpec::UsingParkedCar {
                 car, start_bldg, ..
+            }
+            | TripSpec::ParkAndRide {
+                car, start_bldg, ..
             } => {
                 assert_eq!(person.state, PersonState::Inside(start_bldg));
                 person.state = PersonState::Trip(trip);
@@ -1162,7 +1501,8 @@ impl TripManager {
                     self.abort_trip(now, trip, None, parking, scheduler, map);
                 }
             }
-            TripSpec::UsingTransit { start, stop1, .. } => {
+            TripSpec::UsingTransit { start, stop1, .. }
+            | TripSpec::ReturnFromParkAndRide { start, stop1, .. } => {
                 assert_eq!(
                     person.state,
                     match start.connection {
@@ -1216,6 +1556,63 @@ impl TripManager {
                     self.abort_trip(now, trip, None, parking, scheduler, map);
                 }
             }
+            TripSpec::UsingBikeShare { start, bike, .. } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+
+                // Pick the docks now, so the person walks to one that currently has a bike
+                let start = SidewalkSpot::building(start, map);
+                let goal_pos = match self.trips[trip.0].legs[0] {
+                    TripLeg::Walk(ref spot) => spot.sidewalk_pos,
+                    _ => unreachable!(),
+                };
+                let pickup = self
+                    .bike_share
+                    .nearest_with_bikes(start.sidewalk_pos, None, map);
+                let dropoff = self.bike_share.nearest_with_room(goal_pos, None, map);
+                let t = &mut self.trips[trip.0];
+                match (pickup, dropoff) {
+                    (Some(d1), Some(d2)) => {
+                        // If it's the same dock, just walk
+                        if d1 != d2 {
+                            t.legs.push_front(TripLeg::BikeShare(bike, d2));
+                            t.legs
+                                .push_front(TripLeg::Walk(self.bike_share.get_spot(d1).clone()));
+                        }
+                    }
+                    _ => {
+                        self.events.push(Event::Alert(
+                            AlertLocation::Person(t.person),
+                            format!(
+                                "No bike share docks with bikes or room for {}, so walking",
+                                trip
+                            ),
+                        ));
+                    }
+                }
+                if !t.spawn_ped(
+                    now,
+                    start,
+                    None,
+                    &self.people[t.person.0],
+                    map,
+                    scheduler,
+                    &mut self.events,
+                ) {
+                    self.abort_trip(now, trip, None, parking, scheduler, map);
+                }
+            }
+            TripSpec::UsingRideHail { start, .. } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+                self.events.push(Event::TripPhaseStarting(
+                    trip,
+                    person.id,
+                    None,
+                    TripPhaseType::WaitingForRideHail,
+                ));
+                scheduler.push(now, Command::RequestRideHail(trip));
+            }
             TripSpec::Remote {
                 trip_time, from, ..
             } => {
@@ -1246,7 +1643,7 @@ impl TripManager {
                     let agent_type = match t.info.mode {
                         TripMode::Walk => AgentType::Pedestrian,
                         TripMode::Bike => AgentType::Bike,
-                        TripMode::Drive => AgentType::Car,
+                        TripMode::Drive | TripMode::RideHail => AgentType::Car,
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
                         TripMode::Transit => AgentType::Pedestrian,
@@ -1286,19 +1683,21 @@ pub struct TripInfo {
 
 impl Trip {
     // Returns true if this succeeds. If not, trip aborted.
+    // If walk_to isn't specified, use the current walking leg.
     fn spawn_ped(
         &self,
         now: Time,
         start: SidewalkSpot,
+        walk_to: Option<SidewalkSpot>,
         person: &Person,
         map: &Map,
         scheduler: &mut Scheduler,
         events: &mut Vec<Event>,
     ) -> bool {
-        let walk_to = match self.legs[0] {
+        let walk_to = walk_to.unwrap_or_else(|| match self.legs[0] {
             TripLeg::Walk(ref to) => to.clone(),
             _ => unreachable!(),
-        };
+        });
 
         let req = PathRequest {
             start: start.sidewalk_pos,
@@ -1352,7 +1751,13 @@ pub enum TripLeg {
     // A person may own many vehicles, so specify which they use
     Drive(CarID, DrivingGoal),
     RideBus(BusRouteID, BusStopID),
+    // Wait inside for a ride-hail vehicle, then ride it to this building
+    RideHail(BuildingID),
     Remote(OffMapLocation),
+    // Drive a truck to this building, then double-park out front to unload for some time
+    Deliver(CarID, BuildingID, Duration),
+    // Ride a shared bike to this dock
+    BikeShare(CarID, DockID),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
@@ -1361,6 +1766,7 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    RideHail,
 }
 
 impl TripMode {
@@ -1370,6 +1776,7 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::RideHail,
         ]
     }
 
@@ -1382,6 +1789,7 @@ impl TripMode {
                 // TODO Little confusing; this means buses, not bus riders.
                 VehicleType::Bus => TripMode::Transit,
                 VehicleType::Train => TripMode::Transit,
+                VehicleType::Truck => TripMode::Drive,
             },
             // TODO Now we can detangle this, right?
             AgentID::BusPassenger(_, _) => TripMode::Transit,
@@ -1394,6 +1802,7 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::RideHail => "take a ride-hail",
         }
     }
 
@@ -1404,6 +1813,7 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::RideHail => "riding in a ride-hail",
         }
     }
 
@@ -1413,6 +1823,7 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::RideHail => "Ride-hail",
         }
     }
 
@@ -1422,7 +1833,7 @@ impl TripMode {
             TripMode::Bike => PathConstraints::Bike,
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
-            TripMode::Drive => PathConstraints::Car,
+            TripMode::Drive | TripMode::RideHail => PathConstraints::Car,
         }
     }
 
@@ -1491,6 +1902,7 @@ pub struct Person {
 
     delayed_trips: Vec<(TripID, TripSpec, Option<PathRequest>, Option<Path>)>,
     on_bus: Option<CarID>,
+    in_ride_hail: Option<CarID>,
 }
 
 impl Person {